@@ -0,0 +1,33 @@
+//! protoc plugin that generates Zod schemas
+//!
+//! Usage: `protoc --plugin=protoc-gen-zod --zod_out=out --zod_opt=int64=bigint foo.proto`
+
+use prost::Message;
+use prost_types::compiler::CodeGeneratorRequest;
+use protobuf_to_zod::plugin;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    env_logger::init();
+
+    let mut input = Vec::new();
+    if let Err(e) = io::stdin().read_to_end(&mut input) {
+        eprintln!("error: failed to read the request: {}", e);
+        return ExitCode::FAILURE;
+    }
+    let request = match CodeGeneratorRequest::decode(input.as_slice()) {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("error: failed to decode the request: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let response = plugin::generate(&request);
+    if let Err(e) = io::stdout().write_all(&response.encode_to_vec()) {
+        eprintln!("error: failed to write the response: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,789 @@
+//! Conversion of `FileDescriptorProto`s into the AST
+
+use std::collections::HashMap;
+
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::source_code_info::Location as SourceLocation;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, EnumOptions, EnumValueOptions, FieldDescriptorProto,
+    FieldOptions, FileDescriptorProto, FileOptions, MessageOptions, MethodOptions,
+    ServiceDescriptorProto, ServiceOptions, UninterpretedOption,
+};
+
+use super::escape::escape;
+use super::{child, path};
+use crate::generator::to_lower_camel_case;
+use crate::parser::ast::{
+    Comments, Enum, EnumValue, Extend, ExtensionRange, Field, FieldLabel, FieldType, Import,
+    ImportKind, Message, Method, NumberValue, OneOf, OptionName, OptionNamePart, OptionValue,
+    ProtoFile, ProtoOption, Reserved, ReservedKind, Service, Syntax, ZodAnnotation,
+};
+use crate::parser::error::Location;
+use crate::parser::parse_option_value_text;
+use crate::parser::zod::parse_zod_comment;
+use crate::ConversionError;
+
+impl ProtoFile {
+    /// Converts a compiled file descriptor into a `ProtoFile`.
+    ///
+    /// See [`from_file_descriptor_proto`] for how descriptors map onto the AST.
+    pub fn from_file_descriptor_proto(file: &FileDescriptorProto) -> Result<Self, ConversionError> {
+        from_file_descriptor_proto(file)
+    }
+}
+
+/// Converts a compiled file descriptor into a [`ProtoFile`].
+///
+/// Type references in descriptors are always fully-qualified, so fields refer
+/// to messages and enums as [`FieldType::Message`] and [`FieldType::Enum`],
+/// like after [`resolve_types`](crate::parser::resolver::resolve_types). Map
+/// fields are turned back into [`FieldType::Map`], and the synthetic oneofs of
+/// proto3 `optional` fields are dropped.
+///
+/// The standard options of `descriptor.proto` become [`ProtoOption`]s; custom
+/// options are extensions that the descriptor types do not retain, so they are
+/// lost. When the descriptor has `SourceCodeInfo`, comments and `@zod`
+/// annotations are restored from it. Nodes have no source spans.
+///
+/// # Arguments
+///
+/// * `file` - The descriptor of a single `.proto` file
+///
+/// # Returns
+///
+/// * `Result<ProtoFile, ConversionError>` - The converted file, or a
+///   `ParseError` if the descriptor is malformed or uses an unsupported syntax
+pub fn from_file_descriptor_proto(
+    file: &FileDescriptorProto,
+) -> Result<ProtoFile, ConversionError> {
+    let decoder = Decoder {
+        file,
+        locations: file
+            .source_code_info
+            .iter()
+            .flat_map(|info| &info.location)
+            .map(|location| (location.path.clone(), location))
+            .collect(),
+    };
+    decoder.file()
+}
+
+/// Converts the definitions of one file, looking up their comments by the
+/// path of each definition in the descriptor
+struct Decoder<'a> {
+    file: &'a FileDescriptorProto,
+    locations: HashMap<Vec<i32>, &'a SourceLocation>,
+}
+
+impl Decoder<'_> {
+    fn file(&self) -> Result<ProtoFile, ConversionError> {
+        let file = self.file;
+        let mut proto_file = ProtoFile::new();
+
+        proto_file.syntax = match file.syntax() {
+            "" | "proto2" => Syntax::Proto2,
+            "proto3" => Syntax::Proto3,
+            other => return Err(self.error(format!("unsupported syntax '{}'", other))),
+        };
+        proto_file.package = file.package.clone().filter(|p| !p.is_empty());
+
+        for (index, path) in file.dependency.iter().enumerate() {
+            let index = index as i32;
+            let kind = if file.public_dependency.contains(&index) {
+                ImportKind::Public
+            } else if file.weak_dependency.contains(&index) {
+                ImportKind::Weak
+            } else {
+                ImportKind::Default
+            };
+            proto_file.imports.push(Import {
+                path: path.clone(),
+                kind,
+                span: Default::default(),
+            });
+        }
+
+        if let Some(options) = &file.options {
+            proto_file.options = file_options(options)?;
+        }
+        for (index, message) in file.message_type.iter().enumerate() {
+            let path = child(&[], path::FILE_MESSAGE, index);
+            proto_file.messages.push(self.message(message, &path)?);
+        }
+        for (index, enum_def) in file.enum_type.iter().enumerate() {
+            let path = child(&[], path::FILE_ENUM, index);
+            proto_file.enums.push(self.enum_def(enum_def, &path)?);
+        }
+        for (index, service) in file.service.iter().enumerate() {
+            let path = child(&[], path::FILE_SERVICE, index);
+            proto_file.services.push(self.service(service, &path)?);
+        }
+        proto_file.extends = self.extends(&file.extension, &[path::FILE_EXTENSION])?;
+
+        Ok(proto_file)
+    }
+
+    fn message(&self, message: &DescriptorProto, path: &[i32]) -> Result<Message, ConversionError> {
+        let mut result = Message::new(message.name().to_string());
+        result.comments = self.comments(path);
+        result.zod = self.annotation(path)?;
+
+        for (index, nested) in message.nested_type.iter().enumerate() {
+            if !is_map_entry(nested) {
+                let path = child(path, path::MESSAGE_NESTED, index);
+                result.nested_messages.push(self.message(nested, &path)?);
+            }
+        }
+        for (index, enum_def) in message.enum_type.iter().enumerate() {
+            let path = child(path, path::MESSAGE_ENUM, index);
+            result.nested_enums.push(self.enum_def(enum_def, &path)?);
+        }
+
+        let mut oneofs: Vec<(OneOf, bool)> = message
+            .oneof_decl
+            .iter()
+            .map(|oneof| {
+                let mut result = OneOf::new(oneof.name().to_string());
+                if let Some(options) = &oneof.options {
+                    result.options = custom_options(&options.uninterpreted_option)?;
+                }
+                Ok((result, false))
+            })
+            .collect::<Result<_, ConversionError>>()?;
+
+        for (index, field) in message.field.iter().enumerate() {
+            let path = child(path, path::MESSAGE_FIELD, index);
+            let converted = self.field(field, Some(message), &path)?;
+            match field.oneof_index {
+                Some(index) => {
+                    let (oneof, synthetic) = oneofs.get_mut(index as usize).ok_or_else(|| {
+                        self.error(format!(
+                            "field '{}' of '{}' refers to missing oneof {}",
+                            field.name(),
+                            message.name(),
+                            index
+                        ))
+                    })?;
+                    if field.proto3_optional() {
+                        // proto3 `optional` fields are wrapped in a oneof of their own
+                        *synthetic = true;
+                        result.fields.push(converted);
+                    } else {
+                        oneof.fields.push(converted);
+                    }
+                }
+                None => result.fields.push(converted),
+            }
+        }
+
+        result.oneofs = oneofs
+            .into_iter()
+            .filter(|(_, synthetic)| !synthetic)
+            .map(|(oneof, _)| oneof)
+            .collect();
+
+        if let Some(options) = &message.options {
+            result.options = message_options(options)?;
+        }
+
+        // Descriptor ranges exclude their end, the AST's include it
+        for range in &message.reserved_range {
+            let (start, end) = (range.start(), range.end() - 1);
+            result.reserved.push(Reserved::new(if start == end {
+                ReservedKind::Number(start)
+            } else {
+                ReservedKind::Range(start, end)
+            }));
+        }
+        for name in &message.reserved_name {
+            result
+                .reserved
+                .push(Reserved::new(ReservedKind::FieldName(name.clone())));
+        }
+        for range in &message.extension_range {
+            result.extensions.push(ExtensionRange {
+                start: range.start(),
+                end: range.end() - 1,
+                options: Vec::new(),
+                span: Default::default(),
+            });
+        }
+
+        let mut extension_path = path.to_vec();
+        extension_path.push(path::MESSAGE_EXTENSION);
+        result.extends = self.extends(&message.extension, &extension_path)?;
+
+        Ok(result)
+    }
+
+    fn field(
+        &self,
+        field: &FieldDescriptorProto,
+        message: Option<&DescriptorProto>,
+        path: &[i32],
+    ) -> Result<Field, ConversionError> {
+        let mut label = match field.label() {
+            Label::Optional => FieldLabel::Optional,
+            Label::Required => FieldLabel::Required,
+            Label::Repeated => FieldLabel::Repeated,
+        };
+
+        let typ = match message.and_then(|message| map_entry(field, message)) {
+            Some(entry) => {
+                // Map fields are written without a label
+                label = FieldLabel::Optional;
+                let key = self.entry_field(entry, 1)?;
+                let value = self.entry_field(entry, 2)?;
+                FieldType::Map(Box::new(field_type(key)), Box::new(field_type(value)))
+            }
+            None => field_type(field),
+        };
+
+        let mut options = Vec::new();
+        if let Some(default) = &field.default_value {
+            options.push(option("default", default_value(field, default)));
+        }
+        if let Some(json_name) = &field.json_name {
+            // protoc fills in the JSON name of every field; only a custom
+            // one was written as an option
+            if *json_name != to_lower_camel_case(field.name()) {
+                let json_name = OptionValue::String(escape(json_name.as_bytes()));
+                options.push(option("json_name", json_name));
+            }
+        }
+        if let Some(field_options) = &field.options {
+            options.extend(field_options_of(field_options)?);
+        }
+
+        Ok(Field {
+            name: field.name().to_string(),
+            number: NumberValue::DecimalInt(field.number() as i64),
+            number_span: Default::default(),
+            label,
+            proto3_optional: field.proto3_optional(),
+            typ,
+            options,
+            zod: self.annotation(path)?,
+            comments: self.comments(path),
+            span: Default::default(),
+        })
+    }
+
+    fn entry_field<'b>(
+        &self,
+        entry: &'b DescriptorProto,
+        number: i32,
+    ) -> Result<&'b FieldDescriptorProto, ConversionError> {
+        entry
+            .field
+            .iter()
+            .find(|field| field.number() == number)
+            .ok_or_else(|| {
+                self.error(format!(
+                    "map entry '{}' has no field {}",
+                    entry.name(),
+                    number
+                ))
+            })
+    }
+
+    /// Groups extension fields by the message they extend, in order of
+    /// appearance. `path` is the path of the list of extension fields.
+    fn extends(
+        &self,
+        fields: &[FieldDescriptorProto],
+        path: &[i32],
+    ) -> Result<Vec<Extend>, ConversionError> {
+        let mut extends: Vec<Extend> = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            let mut field_path = path.to_vec();
+            field_path.push(index as i32);
+            let converted = self.field(field, None, &field_path)?;
+
+            match extends.iter_mut().find(|e| e.extendee == field.extendee()) {
+                Some(extend) => extend.fields.push(converted),
+                None => {
+                    let mut extend = Extend::new(field.extendee().to_string());
+                    extend.fields.push(converted);
+                    extends.push(extend);
+                }
+            }
+        }
+        Ok(extends)
+    }
+
+    fn enum_def(
+        &self,
+        enum_def: &EnumDescriptorProto,
+        path: &[i32],
+    ) -> Result<Enum, ConversionError> {
+        let mut result = Enum::new(enum_def.name().to_string());
+        result.comments = self.comments(path);
+        result.zod = self.annotation(path)?;
+        if let Some(options) = &enum_def.options {
+            result.options = enum_options(options)?;
+        }
+
+        for (index, value) in enum_def.value.iter().enumerate() {
+            let path = child(path, path::ENUM_VALUE, index);
+            result.values.push(EnumValue {
+                name: value.name().to_string(),
+                number: NumberValue::DecimalInt(value.number() as i64),
+                options: value
+                    .options
+                    .as_ref()
+                    .map(enum_value_options)
+                    .transpose()?
+                    .unwrap_or_default(),
+                comments: self.comments(&path),
+                span: Default::default(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn service(
+        &self,
+        service: &ServiceDescriptorProto,
+        path: &[i32],
+    ) -> Result<Service, ConversionError> {
+        let mut result = Service::new(service.name().to_string());
+        result.comments = self.comments(path);
+        if let Some(options) = &service.options {
+            result.options = service_options(options)?;
+        }
+
+        for (index, method) in service.method.iter().enumerate() {
+            let path = child(path, path::SERVICE_METHOD, index);
+            result.methods.push(Method {
+                name: method.name().to_string(),
+                input_type: method.input_type().to_string(),
+                output_type: method.output_type().to_string(),
+                client_streaming: method.client_streaming(),
+                server_streaming: method.server_streaming(),
+                options: method
+                    .options
+                    .as_ref()
+                    .map(method_options)
+                    .transpose()?
+                    .unwrap_or_default(),
+                comments: self.comments(&path),
+                span: Default::default(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn comments(&self, path: &[i32]) -> Comments {
+        match self.locations.get(path) {
+            Some(location) => Comments {
+                leading: location.leading_comments.clone(),
+                trailing: location.trailing_comments.clone(),
+                leading_detached: location.leading_detached_comments.clone(),
+            },
+            None => Comments::default(),
+        }
+    }
+
+    /// Finds a `@zod` annotation among the lines of the leading and trailing
+    /// comments of a definition.
+    fn annotation(&self, path: &[i32]) -> Result<Option<ZodAnnotation>, ConversionError> {
+        let Some(location) = self.locations.get(path) else {
+            return Ok(None);
+        };
+        // Spans start with the zero-based line and column
+        let position = Location::new(
+            location.span.first().map_or(0, |&line| line as usize + 1),
+            location
+                .span
+                .get(1)
+                .map_or(0, |&column| column as usize + 1),
+        );
+
+        let mut annotation = None;
+        let comments = location
+            .leading_comments
+            .iter()
+            .chain(&location.trailing_comments);
+        for line in comments.flat_map(|comment| comment.lines()) {
+            if let Some(parsed) =
+                parse_zod_comment(line, position).map_err(|e| self.error(e.to_string()))?
+            {
+                annotation = Some(parsed);
+            }
+        }
+        Ok(annotation)
+    }
+
+    fn error(&self, message: String) -> ConversionError {
+        ConversionError::ParseError(format!("{}: {}", self.file.name(), message))
+    }
+}
+
+/// Returns the AST type of a field; message and enum types keep the fully
+/// qualified name of the descriptor. Map fields are recognized by the caller.
+fn field_type(field: &FieldDescriptorProto) -> FieldType {
+    match field.r#type() {
+        Type::Double => FieldType::Double,
+        Type::Float => FieldType::Float,
+        Type::Int64 => FieldType::Int64,
+        Type::Uint64 => FieldType::UInt64,
+        Type::Int32 => FieldType::Int32,
+        Type::Fixed64 => FieldType::Fixed64,
+        Type::Fixed32 => FieldType::Fixed32,
+        Type::Bool => FieldType::Bool,
+        Type::String => FieldType::String,
+        Type::Bytes => FieldType::Bytes,
+        Type::Uint32 => FieldType::UInt32,
+        Type::Sfixed32 => FieldType::SFixed32,
+        Type::Sfixed64 => FieldType::SFixed64,
+        Type::Sint32 => FieldType::SInt32,
+        Type::Sint64 => FieldType::SInt64,
+        Type::Message | Type::Group => FieldType::Message(field.type_name().to_string()),
+        Type::Enum => FieldType::Enum(field.type_name().to_string()),
+    }
+}
+
+/// Returns the synthetic `...Entry` message of a map field, which protoc nests
+/// in the message that declares the field.
+fn map_entry<'a>(
+    field: &FieldDescriptorProto,
+    message: &'a DescriptorProto,
+) -> Option<&'a DescriptorProto> {
+    if field.label() != Label::Repeated || field.r#type() != Type::Message {
+        return None;
+    }
+    let entry_name = field.type_name().rsplit('.').next()?;
+    message
+        .nested_type
+        .iter()
+        .find(|nested| nested.name() == entry_name && is_map_entry(nested))
+}
+
+fn is_map_entry(message: &DescriptorProto) -> bool {
+    message
+        .options
+        .as_ref()
+        .is_some_and(|options| options.map_entry())
+}
+
+/// Converts the text form of a proto2 default value back into the value the
+/// `default` option was written with.
+fn default_value(field: &FieldDescriptorProto, default: &str) -> OptionValue {
+    match field.r#type() {
+        // protoc stores string defaults unescaped and bytes defaults escaped
+        Type::String => OptionValue::String(escape(default.as_bytes())),
+        Type::Bytes => OptionValue::String(default.to_string()),
+        Type::Bool => OptionValue::Bool(default == "true"),
+        Type::Enum => OptionValue::Identifier(default.to_string()),
+        _ => {
+            if let Ok(value) = default.parse::<i64>() {
+                OptionValue::DecimalInt(value)
+            } else if default.contains(['i', 'n']) {
+                // `inf`, `-inf` and `nan`
+                OptionValue::Identifier(default.to_string())
+            } else {
+                OptionValue::Float(default.parse().unwrap_or_default())
+            }
+        }
+    }
+}
+
+fn option(name: &str, value: OptionValue) -> ProtoOption {
+    ProtoOption {
+        name: OptionName::simple(name),
+        value,
+        span: Default::default(),
+    }
+}
+
+/// Converts the options that are set on a descriptor options message, given
+/// as `field => kind` where the kind is `string` or `bool`.
+macro_rules! options {
+    (@value string, $value:expr) => {
+        OptionValue::String($value.clone().unwrap_or_default())
+    };
+    (@value bool, $value:expr) => {
+        OptionValue::Bool($value.unwrap_or_default())
+    };
+    ($options:expr, { $($field:ident => $kind:ident),* $(,)? }) => {{
+        let options = $options;
+        let mut result = Vec::new();
+        $(
+            if options.$field.is_some() {
+                result.push(option(stringify!($field), options!(@value $kind, options.$field)));
+            }
+        )*
+        result
+    }};
+}
+
+fn file_options(options: &FileOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = options!(options, {
+        java_package => string,
+        java_outer_classname => string,
+        java_multiple_files => bool,
+        java_string_check_utf8 => bool,
+        go_package => string,
+        cc_generic_services => bool,
+        java_generic_services => bool,
+        py_generic_services => bool,
+        deprecated => bool,
+        cc_enable_arenas => bool,
+        objc_class_prefix => string,
+        csharp_namespace => string,
+        swift_prefix => string,
+        php_class_prefix => string,
+        php_namespace => string,
+        php_metadata_namespace => string,
+        ruby_package => string,
+    });
+    if options.optimize_for.is_some() {
+        let value = options.optimize_for().as_str_name().to_string();
+        result.push(option("optimize_for", OptionValue::Identifier(value)));
+    }
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+fn message_options(options: &MessageOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = options!(options, {
+        message_set_wire_format => bool,
+        no_standard_descriptor_accessor => bool,
+        deprecated => bool,
+    });
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+fn field_options_of(options: &FieldOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = Vec::new();
+    if options.ctype.is_some() {
+        let value = options.ctype().as_str_name().to_string();
+        result.push(option("ctype", OptionValue::Identifier(value)));
+    }
+    if options.jstype.is_some() {
+        let value = options.jstype().as_str_name().to_string();
+        result.push(option("jstype", OptionValue::Identifier(value)));
+    }
+    result.extend(options!(options, {
+        packed => bool,
+        lazy => bool,
+        deprecated => bool,
+        weak => bool,
+    }));
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+fn service_options(options: &ServiceOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = options!(options, { deprecated => bool });
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+fn method_options(options: &MethodOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = options!(options, { deprecated => bool });
+    if options.idempotency_level.is_some() {
+        let value = options.idempotency_level().as_str_name().to_string();
+        result.push(option("idempotency_level", OptionValue::Identifier(value)));
+    }
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+fn enum_options(options: &EnumOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = options!(options, { allow_alias => bool, deprecated => bool });
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+fn enum_value_options(options: &EnumValueOptions) -> Result<Vec<ProtoOption>, ConversionError> {
+    let mut result = options!(options, { deprecated => bool });
+    result.extend(custom_options(&options.uninterpreted_option)?);
+    Ok(result)
+}
+
+/// Converts uninterpreted options, which is how options without a field in
+/// the descriptor options messages are stored, e.g. custom options.
+fn custom_options(options: &[UninterpretedOption]) -> Result<Vec<ProtoOption>, ConversionError> {
+    options
+        .iter()
+        .map(|option| {
+            Ok(ProtoOption {
+                name: option_name(option),
+                value: option_value(option)?,
+                span: Default::default(),
+            })
+        })
+        .collect()
+}
+
+fn option_name(option: &UninterpretedOption) -> OptionName {
+    OptionName::new(
+        option
+            .name
+            .iter()
+            .map(|part| {
+                if part.is_extension {
+                    OptionNamePart::Extension(part.name_part.clone())
+                } else {
+                    OptionNamePart::Field(part.name_part.clone())
+                }
+            })
+            .collect(),
+    )
+}
+
+fn option_value(option: &UninterpretedOption) -> Result<OptionValue, ConversionError> {
+    if let Some(identifier) = &option.identifier_value {
+        return Ok(match identifier.as_str() {
+            "true" => OptionValue::Bool(true),
+            "false" => OptionValue::Bool(false),
+            _ => OptionValue::Identifier(identifier.clone()),
+        });
+    }
+    if let Some(value) = option.positive_int_value {
+        return Ok(match i64::try_from(value) {
+            Ok(value) => OptionValue::DecimalInt(value),
+            Err(_) => OptionValue::Float(value as f64),
+        });
+    }
+    if let Some(value) = option.negative_int_value {
+        return Ok(OptionValue::DecimalInt(value));
+    }
+    if let Some(value) = option.double_value {
+        return Ok(OptionValue::Float(value));
+    }
+    if let Some(value) = &option.string_value {
+        return Ok(OptionValue::String(escape(value)));
+    }
+
+    // Aggregates are stored without their braces; lists only come from this crate
+    let aggregate = option.aggregate_value.as_deref().unwrap_or_default();
+    let text = if aggregate.starts_with('[') {
+        aggregate.to_string()
+    } else {
+        format!("{{{}}}", aggregate)
+    };
+    parse_option_value_text(&text).map_err(|e| {
+        ConversionError::ParseError(format!(
+            "Invalid value for option '{}': {}",
+            option_name(option),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message as _;
+    use prost_types::FileDescriptorSet;
+    use std::path::Path;
+
+    fn shop() -> FileDescriptorSet {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("files/descriptors/shop.binpb");
+        FileDescriptorSet::decode(std::fs::read(path).unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_from_file_descriptor_proto() {
+        let set = shop();
+        let proto_file = from_file_descriptor_proto(&set.file[1]).unwrap();
+
+        assert_eq!(proto_file.syntax, Syntax::Proto3);
+        assert_eq!(proto_file.package.as_deref(), Some("shop.v1"));
+        assert_eq!(
+            proto_file.imports[0].path,
+            "google/protobuf/timestamp.proto"
+        );
+        assert_eq!(proto_file.imports[0].kind, ImportKind::Default);
+
+        let order = &proto_file.messages[0];
+        let names: Vec<&str> = order.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["id", "status", "items", "totals", "note", "created_at"]
+        );
+        assert_eq!(
+            order.fields[1].typ,
+            FieldType::Enum(".shop.v1.Order.Status".into())
+        );
+        assert_eq!(order.fields[2].label, FieldLabel::Repeated);
+        assert_eq!(
+            order.fields[3].typ,
+            FieldType::Map(Box::new(FieldType::String), Box::new(FieldType::Int64))
+        );
+        assert_eq!(order.fields[3].label, FieldLabel::Optional);
+
+        // The map entry and the synthetic oneof of `note` are not part of the AST
+        assert_eq!(order.nested_messages.len(), 1);
+        assert_eq!(order.nested_messages[0].name, "Item");
+        assert_eq!(order.nested_enums[0].values.len(), 2);
+        assert_eq!(order.oneofs.len(), 1);
+        assert_eq!(order.oneofs[0].name, "payment");
+        assert_eq!(order.oneofs[0].fields.len(), 2);
+        assert!(order.fields[4].proto3_optional);
+        assert!(!order.fields[0].proto3_optional);
+
+        let method = &proto_file.services[0].methods[0];
+        assert_eq!(method.input_type, ".shop.v1.Order");
+        assert_eq!(
+            method.comments.leading.as_deref(),
+            Some(" Looks up an order\n")
+        );
+    }
+
+    #[test]
+    fn test_options_and_comments() {
+        let set = shop();
+        let proto_file = ProtoFile::from_file_descriptor_proto(&set.file[1]).unwrap();
+
+        assert_eq!(
+            proto_file.options,
+            vec![option(
+                "go_package",
+                OptionValue::String("example.com/shop/v1;shopv1".into())
+            )]
+        );
+
+        let order = &proto_file.messages[0];
+        assert_eq!(
+            order.comments.leading.as_deref(),
+            Some(" An order placed in the shop\n")
+        );
+        let reserved: Vec<&ReservedKind> = order.reserved.iter().map(|r| &r.kind).collect();
+        assert_eq!(
+            reserved,
+            vec![
+                &ReservedKind::Number(9),
+                &ReservedKind::Range(10, 12),
+                &ReservedKind::FieldName("legacy".into())
+            ]
+        );
+        assert!(order.fields[0].zod.as_ref().unwrap().uuid);
+
+        // Only JSON names that differ from the default were written as options
+        let payment = &order.oneofs[0];
+        assert_eq!(
+            payment.fields[0].options,
+            vec![option("json_name", OptionValue::String("card".into()))]
+        );
+        assert_eq!(
+            payment.fields[1].options,
+            vec![option("deprecated", OptionValue::Bool(true))]
+        );
+        assert!(order.fields[1].options.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_syntax() {
+        let file = FileDescriptorProto {
+            name: Some("editions.proto".to_string()),
+            syntax: Some("editions".to_string()),
+            ..Default::default()
+        };
+        assert!(from_file_descriptor_proto(&file).is_err());
+    }
+}
@@ -0,0 +1,903 @@
+//! Conversion of the AST into `FileDescriptorProto`s
+
+use prost_types::descriptor_proto::{ExtensionRange as DescriptorExtensionRange, ReservedRange};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::field_options::{CType, JsType};
+use prost_types::file_options::OptimizeMode;
+use prost_types::method_options::IdempotencyLevel;
+use prost_types::source_code_info::Location as SourceLocation;
+use prost_types::uninterpreted_option::NamePart;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, EnumOptions, EnumValueDescriptorProto, EnumValueOptions,
+    FieldDescriptorProto, FieldOptions, FileDescriptorProto, FileOptions, MessageOptions,
+    MethodDescriptorProto, MethodOptions, OneofDescriptorProto, OneofOptions,
+    ServiceDescriptorProto, ServiceOptions, SourceCodeInfo, UninterpretedOption,
+};
+use std::collections::HashSet;
+
+use super::escape::{escape, unescape};
+use super::{child, path};
+use crate::generator::to_lower_camel_case;
+use crate::parser::ast::{
+    Comments, Enum, Field, FieldLabel, FieldType, ImportKind, Message, OptionName, OptionNamePart,
+    OptionValue, ProtoFile, ProtoOption, ReservedKind, Service, Syntax,
+};
+use crate::parser::error::Span;
+use crate::parser::validator::{enum_value_number, field_tag};
+use crate::ConversionError;
+
+impl ProtoFile {
+    /// Converts the file into a compiled file descriptor.
+    ///
+    /// See [`to_file_descriptor_proto`] for the requirements on the AST.
+    pub fn to_file_descriptor_proto(
+        &self,
+        name: &str,
+        source_info: bool,
+    ) -> Result<FileDescriptorProto, ConversionError> {
+        to_file_descriptor_proto(name, self, source_info)
+    }
+}
+
+/// Converts a parsed and resolved file into a [`FileDescriptorProto`], the
+/// form protoc compiles `.proto` files into.
+///
+/// Type references must have been resolved, e.g. with
+/// [`CompilationUnit::resolve`](crate::loader::CompilationUnit::resolve).
+/// The standard options become fields of the descriptor options messages;
+/// all other options are kept as uninterpreted options. proto3 `optional`
+/// fields are marked with `proto3_optional` and wrapped in a synthetic oneof.
+///
+/// With `source_info`, the descriptor gets a `SourceCodeInfo` with the span
+/// and comments of every definition that has a source span.
+///
+/// # Arguments
+///
+/// * `name` - The import name of the file, e.g. `foo/bar.proto`
+/// * `proto_file` - The resolved file
+/// * `source_info` - Whether to include `SourceCodeInfo`
+///
+/// # Returns
+///
+/// * `Result<FileDescriptorProto, ConversionError>` - The descriptor, or a
+///   `GenerationError` for unresolved types and invalid numbers
+pub fn to_file_descriptor_proto(
+    name: &str,
+    proto_file: &ProtoFile,
+    source_info: bool,
+) -> Result<FileDescriptorProto, ConversionError> {
+    let mut encoder = Encoder {
+        name,
+        locations: source_info.then(Vec::new),
+    };
+    encoder.file(proto_file)
+}
+
+/// Converts the definitions of one file, collecting the source locations of
+/// the definitions on the way if requested
+struct Encoder<'a> {
+    name: &'a str,
+    locations: Option<Vec<SourceLocation>>,
+}
+
+impl Encoder<'_> {
+    fn file(&mut self, proto_file: &ProtoFile) -> Result<FileDescriptorProto, ConversionError> {
+        let mut file = FileDescriptorProto {
+            name: Some(self.name.to_string()),
+            package: proto_file.package.clone(),
+            // protoc leaves the syntax of proto2 files unset
+            syntax: match proto_file.syntax {
+                Syntax::Proto2 => None,
+                Syntax::Proto3 => Some("proto3".to_string()),
+            },
+            ..Default::default()
+        };
+
+        for (index, import) in proto_file.imports.iter().enumerate() {
+            file.dependency.push(import.path.clone());
+            match import.kind {
+                ImportKind::Public => file.public_dependency.push(index as i32),
+                ImportKind::Weak => file.weak_dependency.push(index as i32),
+                ImportKind::Default => {}
+            }
+        }
+
+        file.options = options(&proto_file.options).map(file_options);
+        let scope = proto_file.package.as_deref().unwrap_or_default();
+        for (index, message) in proto_file.messages.iter().enumerate() {
+            let path = child(&[], path::FILE_MESSAGE, index);
+            file.message_type.push(self.message(message, scope, &path)?);
+        }
+        for (index, enum_def) in proto_file.enums.iter().enumerate() {
+            let path = child(&[], path::FILE_ENUM, index);
+            file.enum_type.push(self.enum_def(enum_def, &path)?);
+        }
+        for (index, service) in proto_file.services.iter().enumerate() {
+            let path = child(&[], path::FILE_SERVICE, index);
+            file.service.push(self.service(service, &path)?);
+        }
+        for extend in &proto_file.extends {
+            for field in &extend.fields {
+                let path = child(&[], path::FILE_EXTENSION, file.extension.len());
+                let (mut field, _) = self.field(field, None, &path)?;
+                field.extendee = Some(extend.extendee.clone());
+                file.extension.push(field);
+            }
+        }
+
+        file.source_code_info = self
+            .locations
+            .take()
+            .map(|location| SourceCodeInfo { location });
+        Ok(file)
+    }
+
+    fn message(
+        &mut self,
+        message: &Message,
+        scope: &str,
+        path: &[i32],
+    ) -> Result<DescriptorProto, ConversionError> {
+        self.locate(path, message.span, Some(&message.comments));
+        let full_name = qualify(scope, &message.name);
+        let mut result = DescriptorProto {
+            name: Some(message.name.clone()),
+            options: options(&message.options).map(message_options),
+            ..Default::default()
+        };
+
+        for (index, nested) in message.nested_messages.iter().enumerate() {
+            let path = child(path, path::MESSAGE_NESTED, index);
+            result
+                .nested_type
+                .push(self.message(nested, &full_name, &path)?);
+        }
+        for (index, enum_def) in message.nested_enums.iter().enumerate() {
+            let path = child(path, path::MESSAGE_ENUM, index);
+            result.enum_type.push(self.enum_def(enum_def, &path)?);
+        }
+
+        // protoc lists fields in declaration order, oneof members included
+        let mut fields: Vec<(&Field, Option<i32>)> =
+            message.fields.iter().map(|field| (field, None)).collect();
+        for (index, oneof) in message.oneofs.iter().enumerate() {
+            let path = child(path, path::MESSAGE_ONEOF, index);
+            self.locate(&path, oneof.span, None);
+            result.oneof_decl.push(OneofDescriptorProto {
+                name: Some(oneof.name.clone()),
+                options: options(&oneof.options).map(|uninterpreted_option| OneofOptions {
+                    uninterpreted_option,
+                }),
+            });
+            fields.extend(oneof.fields.iter().map(|field| (field, Some(index as i32))));
+        }
+        fields.sort_by_key(|(field, _)| field.span.start.offset);
+
+        // Like protoc, every proto3 `optional` field gets a synthetic oneof of
+        // its own, declared after the real oneofs
+        let mut names: HashSet<String> = fields
+            .iter()
+            .map(|(field, _)| field.name.clone())
+            .chain(message.oneofs.iter().map(|oneof| oneof.name.clone()))
+            .collect();
+
+        for (index, (field, mut oneof_index)) in fields.into_iter().enumerate() {
+            if field.proto3_optional {
+                let name = synthetic_oneof_name(&field.name, &names);
+                names.insert(name.clone());
+                oneof_index = Some(result.oneof_decl.len() as i32);
+                result.oneof_decl.push(OneofDescriptorProto {
+                    name: Some(name),
+                    options: None,
+                });
+            }
+
+            let path = child(path, path::MESSAGE_FIELD, index);
+            let (mut descriptor, map_entry) = self.field(field, Some(&full_name), &path)?;
+            descriptor.oneof_index = oneof_index;
+            if field.proto3_optional {
+                descriptor.proto3_optional = Some(true);
+            }
+            result.field.push(descriptor);
+            result.nested_type.extend(map_entry);
+        }
+
+        // The AST's ranges include their end, descriptor ranges exclude it
+        for reserved in &message.reserved {
+            match &reserved.kind {
+                ReservedKind::Number(number) => result.reserved_range.push(ReservedRange {
+                    start: Some(*number),
+                    end: Some(number + 1),
+                }),
+                ReservedKind::Range(start, end) => result.reserved_range.push(ReservedRange {
+                    start: Some(*start),
+                    end: Some(end + 1),
+                }),
+                ReservedKind::FieldName(name) => result.reserved_name.push(name.clone()),
+            }
+        }
+        for range in &message.extensions {
+            result.extension_range.push(DescriptorExtensionRange {
+                start: Some(range.start),
+                end: Some(range.end + 1),
+                options: None,
+            });
+        }
+        for extend in &message.extends {
+            for field in &extend.fields {
+                let path = child(path, path::MESSAGE_EXTENSION, result.extension.len());
+                let (mut field, _) = self.field(field, None, &path)?;
+                field.extendee = Some(extend.extendee.clone());
+                result.extension.push(field);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Converts a field, returning the synthetic entry message of a map field
+    /// alongside it. `message` is the full name of the declaring message,
+    /// `None` for extensions, which cannot be maps.
+    fn field(
+        &mut self,
+        field: &Field,
+        message: Option<&str>,
+        path: &[i32],
+    ) -> Result<(FieldDescriptorProto, Option<DescriptorProto>), ConversionError> {
+        self.locate(path, field.span, Some(&field.comments));
+
+        let mut result = FieldDescriptorProto {
+            name: Some(field.name.clone()),
+            number: Some(field_tag(field).map_err(|e| self.error(e.message()))? as i32),
+            label: Some(match field.label {
+                FieldLabel::Optional => Label::Optional,
+                FieldLabel::Required => Label::Required,
+                FieldLabel::Repeated => Label::Repeated,
+            } as i32),
+            json_name: Some(to_lower_camel_case(&field.name)),
+            ..Default::default()
+        };
+
+        let mut map_entry = None;
+        match (&field.typ, message) {
+            (FieldType::Map(key, value), Some(message)) => {
+                let entry_name = map_entry_name(&field.name);
+                let entry = DescriptorProto {
+                    name: Some(entry_name.clone()),
+                    field: vec![
+                        self.entry_field("key", 1, key, &field.name)?,
+                        self.entry_field("value", 2, value, &field.name)?,
+                    ],
+                    options: Some(MessageOptions {
+                        map_entry: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                result.label = Some(Label::Repeated as i32);
+                result.r#type = Some(Type::Message as i32);
+                result.type_name = Some(format!(".{}", qualify(message, &entry_name)));
+                map_entry = Some(entry);
+            }
+            (typ, _) => {
+                let (typ, type_name) = self.field_type(typ, &field.name)?;
+                result.r#type = Some(typ as i32);
+                result.type_name = type_name;
+            }
+        }
+
+        let mut uninterpreted = Vec::new();
+        for option in &field.options {
+            match (simple_name(&option.name), &option.value) {
+                (Some("default"), value) => {
+                    result.default_value = Some(default_value(&field.typ, value))
+                }
+                (Some("json_name"), OptionValue::String(json_name)) => {
+                    result.json_name = Some(String::from_utf8_lossy(&unescape(json_name)).into())
+                }
+                _ => uninterpreted.push(uninterpreted_option(option)),
+            }
+        }
+        if !uninterpreted.is_empty() {
+            result.options = Some(field_options(uninterpreted));
+        }
+
+        Ok((result, map_entry))
+    }
+
+    fn entry_field(
+        &self,
+        name: &str,
+        number: i32,
+        typ: &FieldType,
+        map_name: &str,
+    ) -> Result<FieldDescriptorProto, ConversionError> {
+        let (typ, type_name) = self.field_type(typ, map_name)?;
+        Ok(FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(typ as i32),
+            type_name,
+            json_name: Some(name.to_string()),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the descriptor type of a field and, for messages and enums,
+    /// the fully-qualified type name.
+    fn field_type(
+        &self,
+        typ: &FieldType,
+        field: &str,
+    ) -> Result<(Type, Option<String>), ConversionError> {
+        let typ = match typ {
+            FieldType::Double => Type::Double,
+            FieldType::Float => Type::Float,
+            FieldType::Int32 => Type::Int32,
+            FieldType::Int64 => Type::Int64,
+            FieldType::UInt32 => Type::Uint32,
+            FieldType::UInt64 => Type::Uint64,
+            FieldType::SInt32 => Type::Sint32,
+            FieldType::SInt64 => Type::Sint64,
+            FieldType::Fixed32 => Type::Fixed32,
+            FieldType::Fixed64 => Type::Fixed64,
+            FieldType::SFixed32 => Type::Sfixed32,
+            FieldType::SFixed64 => Type::Sfixed64,
+            FieldType::Bool => Type::Bool,
+            FieldType::String => Type::String,
+            FieldType::Bytes => Type::Bytes,
+            FieldType::Message(name) => return Ok((Type::Message, Some(name.clone()))),
+            FieldType::Enum(name) => return Ok((Type::Enum, Some(name.clone()))),
+            FieldType::MessageOrEnum(name) => {
+                return Err(self.error(format!(
+                    "type '{}' of field '{}' is not resolved",
+                    name, field
+                )))
+            }
+            FieldType::Map(..) => {
+                return Err(self.error(format!("field '{}' cannot be a map here", field)))
+            }
+        };
+        Ok((typ, None))
+    }
+
+    fn enum_def(
+        &mut self,
+        enum_def: &Enum,
+        path: &[i32],
+    ) -> Result<EnumDescriptorProto, ConversionError> {
+        self.locate(path, enum_def.span, Some(&enum_def.comments));
+        let mut result = EnumDescriptorProto {
+            name: Some(enum_def.name.clone()),
+            ..Default::default()
+        };
+
+        if !enum_def.options.is_empty() {
+            let mut uninterpreted = enum_def.options.iter().map(uninterpreted_option).collect();
+            result.options = Some(EnumOptions {
+                allow_alias: take(&mut uninterpreted, "allow_alias", boolean),
+                deprecated: take(&mut uninterpreted, "deprecated", boolean),
+                uninterpreted_option: uninterpreted,
+            });
+        }
+
+        for (index, value) in enum_def.values.iter().enumerate() {
+            let path = child(path, path::ENUM_VALUE, index);
+            self.locate(&path, value.span, Some(&value.comments));
+
+            let mut options = None;
+            if !value.options.is_empty() {
+                let mut uninterpreted = value.options.iter().map(uninterpreted_option).collect();
+                options = Some(EnumValueOptions {
+                    deprecated: take(&mut uninterpreted, "deprecated", boolean),
+                    uninterpreted_option: uninterpreted,
+                });
+            }
+
+            result.value.push(EnumValueDescriptorProto {
+                name: Some(value.name.clone()),
+                number: Some(enum_value_number(value).map_err(|e| self.error(e.message()))?),
+                options,
+            });
+        }
+        Ok(result)
+    }
+
+    fn service(
+        &mut self,
+        service: &Service,
+        path: &[i32],
+    ) -> Result<ServiceDescriptorProto, ConversionError> {
+        self.locate(path, service.span, Some(&service.comments));
+        let mut result = ServiceDescriptorProto {
+            name: Some(service.name.clone()),
+            options: options(&service.options).map(|mut uninterpreted| ServiceOptions {
+                deprecated: take(&mut uninterpreted, "deprecated", boolean),
+                uninterpreted_option: uninterpreted,
+            }),
+            ..Default::default()
+        };
+
+        for (index, method) in service.methods.iter().enumerate() {
+            let path = child(path, path::SERVICE_METHOD, index);
+            self.locate(&path, method.span, Some(&method.comments));
+
+            for type_name in [&method.input_type, &method.output_type] {
+                if !type_name.starts_with('.') {
+                    return Err(self.error(format!(
+                        "type '{}' of method '{}' is not resolved",
+                        type_name, method.name
+                    )));
+                }
+            }
+
+            result.method.push(MethodDescriptorProto {
+                name: Some(method.name.clone()),
+                input_type: Some(method.input_type.clone()),
+                output_type: Some(method.output_type.clone()),
+                options: options(&method.options).map(method_options),
+                client_streaming: method.client_streaming.then_some(true),
+                server_streaming: method.server_streaming.then_some(true),
+            });
+        }
+        Ok(result)
+    }
+
+    /// Records the location of a definition that has a source span.
+    fn locate(&mut self, path: &[i32], span: Span, comments: Option<&Comments>) {
+        let Some(locations) = &mut self.locations else {
+            return;
+        };
+        if span == Span::default() {
+            return;
+        }
+
+        // Lines and columns are zero-based; the end line is omitted when it
+        // is the start line
+        let (start, end) = (span.start, span.end);
+        let mut location_span = vec![start.line as i32 - 1, start.column as i32 - 1];
+        if end.line != start.line {
+            location_span.push(end.line as i32 - 1);
+        }
+        location_span.push(end.column as i32 - 1);
+
+        let comments = comments.cloned().unwrap_or_default();
+        locations.push(SourceLocation {
+            path: path.to_vec(),
+            span: location_span,
+            leading_comments: comments.leading,
+            trailing_comments: comments.trailing,
+            leading_detached_comments: comments.leading_detached,
+        });
+    }
+
+    fn error(&self, message: String) -> ConversionError {
+        ConversionError::GenerationError(format!("{}: {}", self.name, message))
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+/// Returns the name protoc gives the entry message of a map field, e.g.
+/// `LabelValuesEntry` for `label_values`.
+fn map_entry_name(field_name: &str) -> String {
+    let camel = to_lower_camel_case(field_name);
+    let mut chars = camel.chars();
+    match chars.next() {
+        Some(first) => format!("{}{}Entry", first.to_uppercase(), chars.as_str()),
+        None => "Entry".to_string(),
+    }
+}
+
+/// Returns the text form protoc stores proto2 default values in: strings
+/// unescaped, bytes C-escaped.
+fn default_value(typ: &FieldType, value: &OptionValue) -> String {
+    match value {
+        OptionValue::String(s) if *typ == FieldType::Bytes => escape(&unescape(s)),
+        OptionValue::String(s) => String::from_utf8_lossy(&unescape(s)).into_owned(),
+        OptionValue::Identifier(s) => s.clone(),
+        OptionValue::DecimalInt(n) | OptionValue::Octal(n) | OptionValue::Hex(n) => n.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Returns the name of the synthetic oneof of a proto3 `optional` field: the
+/// field name with a leading underscore, prefixed with `X` until it clashes
+/// with no other field or oneof, as protoc names it.
+fn synthetic_oneof_name(field: &str, names: &HashSet<String>) -> String {
+    let mut name = if field.starts_with('_') {
+        field.to_string()
+    } else {
+        format!("_{}", field)
+    };
+    while names.contains(&name) {
+        name.insert(0, 'X');
+    }
+    name
+}
+
+fn simple_name(name: &OptionName) -> Option<&str> {
+    match &name.parts[..] {
+        [OptionNamePart::Field(name)] => Some(name),
+        _ => None,
+    }
+}
+
+fn name_parts(name: &OptionName) -> Vec<NamePart> {
+    name.parts
+        .iter()
+        .map(|part| match part {
+            OptionNamePart::Field(name) => NamePart {
+                name_part: name.clone(),
+                is_extension: false,
+            },
+            OptionNamePart::Extension(name) => NamePart {
+                name_part: name.clone(),
+                is_extension: true,
+            },
+        })
+        .collect()
+}
+
+/// Converts an option that has no field in the descriptor options messages,
+/// e.g. a custom option.
+fn uninterpreted_option(option: &ProtoOption) -> UninterpretedOption {
+    let mut result = UninterpretedOption {
+        name: name_parts(&option.name),
+        ..Default::default()
+    };
+    match &option.value {
+        OptionValue::Identifier(s) | OptionValue::Enum(_, s) => {
+            result.identifier_value = Some(s.clone())
+        }
+        OptionValue::Bool(b) => result.identifier_value = Some(b.to_string()),
+        OptionValue::String(s) => result.string_value = Some(unescape(s)),
+        OptionValue::DecimalInt(n) | OptionValue::Octal(n) | OptionValue::Hex(n) => {
+            set_int(&mut result, *n)
+        }
+        OptionValue::Float(f) => result.double_value = Some(*f),
+        // protoc stores the text between the braces of aggregates
+        value @ (OptionValue::Message(_) | OptionValue::Map(_)) => {
+            let text = value.to_string();
+            result.aggregate_value = Some(text[1..text.len() - 1].trim().to_string());
+        }
+        value @ OptionValue::List(_) => result.aggregate_value = Some(value.to_string()),
+    }
+    result
+}
+
+fn set_int(option: &mut UninterpretedOption, value: i64) {
+    if value < 0 {
+        option.negative_int_value = Some(value);
+    } else {
+        option.positive_int_value = Some(value as u64);
+    }
+}
+
+/// Converts options into uninterpreted options, or `None` if there are none.
+/// The standard options are then taken out of them into their fields.
+fn options(options: &[ProtoOption]) -> Option<Vec<UninterpretedOption>> {
+    if options.is_empty() {
+        None
+    } else {
+        Some(options.iter().map(uninterpreted_option).collect())
+    }
+}
+
+fn file_options(mut uninterpreted: Vec<UninterpretedOption>) -> FileOptions {
+    let u = &mut uninterpreted;
+    let optimize_for = take(u, "optimize_for", |o| {
+        identifier(o).and_then(OptimizeMode::from_str_name)
+    });
+    FileOptions {
+        java_package: take(u, "java_package", string),
+        java_outer_classname: take(u, "java_outer_classname", string),
+        java_multiple_files: take(u, "java_multiple_files", boolean),
+        java_string_check_utf8: take(u, "java_string_check_utf8", boolean),
+        optimize_for: optimize_for.map(|mode| mode as i32),
+        go_package: take(u, "go_package", string),
+        cc_generic_services: take(u, "cc_generic_services", boolean),
+        java_generic_services: take(u, "java_generic_services", boolean),
+        py_generic_services: take(u, "py_generic_services", boolean),
+        deprecated: take(u, "deprecated", boolean),
+        cc_enable_arenas: take(u, "cc_enable_arenas", boolean),
+        objc_class_prefix: take(u, "objc_class_prefix", string),
+        csharp_namespace: take(u, "csharp_namespace", string),
+        swift_prefix: take(u, "swift_prefix", string),
+        php_class_prefix: take(u, "php_class_prefix", string),
+        php_namespace: take(u, "php_namespace", string),
+        php_metadata_namespace: take(u, "php_metadata_namespace", string),
+        ruby_package: take(u, "ruby_package", string),
+        uninterpreted_option: uninterpreted,
+        ..Default::default()
+    }
+}
+
+fn message_options(mut uninterpreted: Vec<UninterpretedOption>) -> MessageOptions {
+    let u = &mut uninterpreted;
+    MessageOptions {
+        message_set_wire_format: take(u, "message_set_wire_format", boolean),
+        no_standard_descriptor_accessor: take(u, "no_standard_descriptor_accessor", boolean),
+        deprecated: take(u, "deprecated", boolean),
+        uninterpreted_option: uninterpreted,
+        ..Default::default()
+    }
+}
+
+fn field_options(mut uninterpreted: Vec<UninterpretedOption>) -> FieldOptions {
+    let u = &mut uninterpreted;
+    let ctype = take(u, "ctype", |o| identifier(o).and_then(CType::from_str_name));
+    let jstype = take(u, "jstype", |o| {
+        identifier(o).and_then(JsType::from_str_name)
+    });
+    FieldOptions {
+        ctype: ctype.map(|ctype| ctype as i32),
+        jstype: jstype.map(|jstype| jstype as i32),
+        packed: take(u, "packed", boolean),
+        lazy: take(u, "lazy", boolean),
+        deprecated: take(u, "deprecated", boolean),
+        weak: take(u, "weak", boolean),
+        uninterpreted_option: uninterpreted,
+    }
+}
+
+fn method_options(mut uninterpreted: Vec<UninterpretedOption>) -> MethodOptions {
+    let u = &mut uninterpreted;
+    let idempotency_level = take(u, "idempotency_level", |o| {
+        identifier(o).and_then(IdempotencyLevel::from_str_name)
+    });
+    MethodOptions {
+        deprecated: take(u, "deprecated", boolean),
+        idempotency_level: idempotency_level.map(|level| level as i32),
+        uninterpreted_option: uninterpreted,
+    }
+}
+
+/// Removes the first option named `name` whose value `value` accepts, and
+/// returns the accepted value.
+fn take<T>(
+    options: &mut Vec<UninterpretedOption>,
+    name: &str,
+    value: impl Fn(&UninterpretedOption) -> Option<T>,
+) -> Option<T> {
+    let (index, value) = options.iter().enumerate().find_map(|(index, option)| {
+        let is_named = matches!(
+            &option.name[..],
+            [part] if !part.is_extension && part.name_part == name
+        );
+        if is_named {
+            value(option).map(|value| (index, value))
+        } else {
+            None
+        }
+    })?;
+    options.remove(index);
+    Some(value)
+}
+
+fn identifier(option: &UninterpretedOption) -> Option<&str> {
+    option.identifier_value.as_deref()
+}
+
+fn boolean(option: &UninterpretedOption) -> Option<bool> {
+    match identifier(option) {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None,
+    }
+}
+
+fn string(option: &UninterpretedOption) -> Option<String> {
+    String::from_utf8(option.string_value.clone()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::{CompilationUnit, Loader};
+    use crate::parser::parse_proto_file;
+    use prost::Message as _;
+    use prost_types::FileDescriptorSet;
+    use std::path::Path;
+
+    /// `shop.binpb` is built by hand; its README lists how it differs from protoc
+    fn fixtures() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("files/descriptors")
+    }
+
+    fn load_shop() -> CompilationUnit {
+        let mut unit = Loader::new([fixtures()])
+            .load(&[fixtures().join("shop.proto")])
+            .unwrap();
+        unit.resolve().unwrap();
+        unit
+    }
+
+    #[test]
+    fn test_matches_fixture() {
+        let unit = load_shop();
+        let shop = unit.get("shop.proto").unwrap();
+        let encoded = shop
+            .proto
+            .to_file_descriptor_proto("shop.proto", false)
+            .unwrap();
+
+        let bytes = std::fs::read(fixtures().join("shop.binpb")).unwrap();
+        let mut expected = FileDescriptorSet::decode(bytes.as_slice()).unwrap().file[1].clone();
+        expected.source_code_info = None;
+
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn test_source_code_info() {
+        let unit = load_shop();
+        let shop = unit.get("shop.proto").unwrap();
+        let encoded = shop
+            .proto
+            .to_file_descriptor_proto("shop.proto", true)
+            .unwrap();
+        let locations = encoded.source_code_info.unwrap().location;
+
+        let order = locations.iter().find(|l| l.path == [4, 0]).unwrap();
+        assert_eq!(order.span, vec![9, 0, 34, 1]);
+        assert_eq!(
+            order.leading_comments.as_deref(),
+            Some(" An order placed in the shop\n")
+        );
+        let id = locations.iter().find(|l| l.path == [4, 0, 2, 0]).unwrap();
+        assert_eq!(id.span, vec![23, 2, 16]);
+        let method = locations.iter().find(|l| l.path == [6, 0, 2, 0]).unwrap();
+        assert_eq!(
+            method.leading_comments.as_deref(),
+            Some(" Looks up an order\n")
+        );
+    }
+
+    #[test]
+    fn test_custom_options_roundtrip() {
+        let input = r#"
+            syntax = "proto2";
+            message Foo {
+                option (my.message) = { name: "foo" count: 2 };
+                optional int32 bar = 1 [default = -3, (my.field).min = 1];
+            }
+            enum Kind {
+                option allow_alias = true;
+                KIND_A = 0 [(my.value) = "a"];
+            }
+        "#;
+        let proto_file = parse_proto_file(input).unwrap();
+        let encoded = proto_file
+            .to_file_descriptor_proto("foo.proto", false)
+            .unwrap();
+
+        assert_eq!(encoded.syntax, None);
+        let message = &encoded.message_type[0];
+        let custom = &message.options.as_ref().unwrap().uninterpreted_option[0];
+        assert_eq!(
+            custom.aggregate_value.as_deref(),
+            Some("name: \"foo\", count: 2")
+        );
+        assert_eq!(message.field[0].default_value.as_deref(), Some("-3"));
+        assert_eq!(
+            encoded.enum_type[0].options.as_ref().unwrap().allow_alias,
+            Some(true)
+        );
+
+        // Spans are not part of descriptors without source info
+        let decoded = ProtoFile::from_file_descriptor_proto(&encoded).unwrap();
+        let values = |options: &[ProtoOption]| -> Vec<OptionValue> {
+            options.iter().map(|o| o.value.clone()).collect()
+        };
+        assert_eq!(
+            values(&decoded.messages[0].options),
+            values(&proto_file.messages[0].options)
+        );
+        assert_eq!(
+            values(&decoded.messages[0].fields[0].options),
+            values(&proto_file.messages[0].fields[0].options)
+        );
+        let value = &decoded.enums[0].values[0].options[0];
+        assert_eq!(value.value, OptionValue::String("a".into()));
+    }
+
+    #[test]
+    fn test_escaped_strings() {
+        let input = r#"
+            syntax = "proto2";
+            message M {
+                optional string text = 1 [default = "a\nb", (my.label) = "tab\t\"q\""];
+                optional bytes data = 2 [default = "\x00\377z"];
+            }
+        "#;
+        let proto_file = parse_proto_file(input).unwrap();
+        let encoded = proto_file
+            .to_file_descriptor_proto("m.proto", false)
+            .unwrap();
+
+        let fields = &encoded.message_type[0].field;
+        assert_eq!(fields[0].default_value.as_deref(), Some("a\nb"));
+        let label = &fields[0].options.as_ref().unwrap().uninterpreted_option[0];
+        assert_eq!(label.string_value.as_deref(), Some(&b"tab\t\"q\""[..]));
+        assert_eq!(fields[1].default_value.as_deref(), Some("\\000\\377z"));
+
+        // Decoding escapes them again, as they were written
+        let decoded = ProtoFile::from_file_descriptor_proto(&encoded).unwrap();
+        let fields = &decoded.messages[0].fields;
+        assert_eq!(
+            fields[0].options[0].value,
+            OptionValue::String(r"a\nb".into())
+        );
+        assert_eq!(
+            fields[0].options[1].value,
+            OptionValue::String(r#"tab\t\"q\""#.into())
+        );
+        assert_eq!(
+            fields[1].options[0].value,
+            OptionValue::String(r"\000\377z".into())
+        );
+    }
+
+    #[test]
+    fn test_proto3_optional_roundtrip() {
+        let input = r#"
+            syntax = "proto3";
+            message M {
+                oneof choice {
+                    string a = 1;
+                    string _b = 2;
+                }
+                optional string b = 3;
+                optional int32 _c = 4;
+            }
+        "#;
+        let proto_file = parse_proto_file(input).unwrap();
+        let encoded = proto_file
+            .to_file_descriptor_proto("m.proto", false)
+            .unwrap();
+
+        let message = &encoded.message_type[0];
+        let oneofs: Vec<&str> = message.oneof_decl.iter().map(|o| o.name()).collect();
+        assert_eq!(oneofs, vec!["choice", "X_b", "X_c"]);
+        let presence: Vec<(Option<i32>, bool)> = message
+            .field
+            .iter()
+            .map(|f| (f.oneof_index, f.proto3_optional()))
+            .collect();
+        assert_eq!(
+            presence,
+            vec![
+                (Some(0), false),
+                (Some(0), false),
+                (Some(1), true),
+                (Some(2), true)
+            ]
+        );
+
+        let decoded = ProtoFile::from_file_descriptor_proto(&encoded).unwrap();
+        let message = &decoded.messages[0];
+        assert_eq!(message.oneofs.len(), 1);
+        assert!(message.fields.iter().all(|f| f.proto3_optional));
+    }
+
+    #[test]
+    fn test_unresolved_type() {
+        let proto_file = parse_proto_file("syntax = \"proto3\"; message A { B b = 1; }").unwrap();
+        let error = to_file_descriptor_proto("a.proto", &proto_file, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            ConversionError::GenerationError(
+                "a.proto: type 'B' of field 'b' is not resolved".into()
+            )
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Escaping of string literals
+//!
+//! The AST keeps string option values as written between the quotes, escapes
+//! included. Descriptors hold the actual bytes instead, except for the
+//! defaults of `bytes` fields, which protoc stores C-escaped.
+
+/// Resolves the escapes of a protobuf string literal, e.g. `a\nb` or `\x41`,
+/// into the bytes it denotes. Unknown escapes are kept as written.
+pub(crate) fn unescape(text: &str) -> Vec<u8> {
+    let mut result = Vec::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buffer = [0; 4];
+            result.extend_from_slice(ch.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            result.push(b'\\');
+            break;
+        };
+        match escape {
+            'a' => result.push(0x07),
+            'b' => result.push(0x08),
+            'f' => result.push(0x0c),
+            'n' => result.push(b'\n'),
+            'r' => result.push(b'\r'),
+            't' => result.push(b'\t'),
+            'v' => result.push(0x0b),
+            '\\' | '\'' | '"' | '?' => result.push(escape as u8),
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                result.push(value as u8);
+            }
+            'x' | 'X' => match digits(&mut chars, 16, 2) {
+                Some(value) => result.push(value as u8),
+                None => result.extend_from_slice(&[b'\\', escape as u8]),
+            },
+            'u' | 'U' => {
+                let count = if escape == 'u' { 4 } else { 8 };
+                match digits(&mut chars, 16, count).and_then(char::from_u32) {
+                    Some(ch) => {
+                        let mut buffer = [0; 4];
+                        result.extend_from_slice(ch.encode_utf8(&mut buffer).as_bytes());
+                    }
+                    None => result.extend_from_slice(&[b'\\', escape as u8]),
+                }
+            }
+            other => {
+                result.push(b'\\');
+                let mut buffer = [0; 4];
+                result.extend_from_slice(other.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
+    }
+    result
+}
+
+/// Escapes bytes the way protoc's `CEscape` does, so that the result can be
+/// written between the quotes of a string literal.
+pub(crate) fn escape(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'\n' => result.push_str("\\n"),
+            b'\r' => result.push_str("\\r"),
+            b'\t' => result.push_str("\\t"),
+            b'"' => result.push_str("\\\""),
+            b'\'' => result.push_str("\\'"),
+            b'\\' => result.push_str("\\\\"),
+            0x20..=0x7e => result.push(byte as char),
+            _ => result.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    result
+}
+
+/// Consumes up to `max` digits of `radix`, or none if the first is missing.
+fn digits(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    radix: u32,
+    max: usize,
+) -> Option<u32> {
+    let mut value = None;
+    for _ in 0..max {
+        let Some(digit) = chars.peek().and_then(|c| c.to_digit(radix)) else {
+            break;
+        };
+        value = Some(value.unwrap_or(0) * radix + digit);
+        chars.next();
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r#"a\nb"#), b"a\nb");
+        assert_eq!(unescape(r#"\"q\" \'s\' \\"#), b"\"q\" 's' \\");
+        assert_eq!(unescape(r"\x41\101\0\7"), b"AA\0\x07");
+        assert_eq!(unescape(r"é\U0001F600"), "é😀".as_bytes());
+        assert_eq!(unescape(r"\d \xZ"), br"\d \xZ");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(b"a\nb\"c\\"), r#"a\nb\"c\\"#);
+        assert_eq!(escape(&[0, 0x7f, 0xff]), r"\000\177\377");
+        for text in [r"a\nb", r#"tab\there \"q\""#, r"\000\377"] {
+            assert_eq!(escape(&unescape(text)), text);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Conversion between compiled protobuf descriptors and the AST
+//!
+//! protoc and buf describe `.proto` files as `FileDescriptorProto` messages,
+//! e.g. in the `CodeGeneratorRequest` sent to plugins or in the descriptor
+//! sets written by `buf build -o`. Converting them into
+//! [`ProtoFile`](crate::parser::ast::ProtoFile)s lets the generator work on
+//! files it has no source for. The conversion also works the other way, so
+//! parsed files can be written out as descriptor sets.
+
+mod decode;
+mod encode;
+mod escape;
+
+pub use decode::from_file_descriptor_proto;
+pub use encode::to_file_descriptor_proto;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use prost::Message;
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+
+use crate::loader::{CompilationUnit, LoadError, SourceFile};
+use crate::ConversionError;
+
+/// Field numbers that make up the paths of `SourceCodeInfo` locations
+pub(crate) mod path {
+    pub const FILE_MESSAGE: i32 = 4;
+    pub const FILE_ENUM: i32 = 5;
+    pub const FILE_SERVICE: i32 = 6;
+    pub const FILE_EXTENSION: i32 = 7;
+    pub const MESSAGE_FIELD: i32 = 2;
+    pub const MESSAGE_NESTED: i32 = 3;
+    pub const MESSAGE_ENUM: i32 = 4;
+    pub const MESSAGE_EXTENSION: i32 = 6;
+    pub const MESSAGE_ONEOF: i32 = 8;
+    pub const ENUM_VALUE: i32 = 2;
+    pub const SERVICE_METHOD: i32 = 2;
+}
+
+/// Appends the path of the `index`th element of the repeated `field` to `path`.
+pub(crate) fn child(path: &[i32], field: i32, index: usize) -> Vec<i32> {
+    let mut child = path.to_vec();
+    child.push(field);
+    child.push(index as i32);
+    child
+}
+
+/// Reads a binary `FileDescriptorSet`, e.g. one written by `buf build -o` or
+/// `protoc --descriptor_set_out`, into a compilation unit.
+///
+/// A descriptor set does not record which files were requested and which were
+/// only imported, so every file is a root. The files are already resolved.
+///
+/// # Arguments
+///
+/// * `path` - The path of the descriptor set
+///
+/// # Returns
+///
+/// * `Result<CompilationUnit, LoadError>` - The converted files, or an `Io`
+///   or `InvalidDescriptor` error
+pub fn load_descriptor_set<P: AsRef<Path>>(path: P) -> Result<CompilationUnit, LoadError> {
+    let path = path.as_ref();
+    let invalid = |message: String| LoadError::InvalidDescriptor {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let bytes = fs::read(path).map_err(|error| LoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let set = FileDescriptorSet::decode(bytes.as_slice()).map_err(|e| invalid(e.to_string()))?;
+
+    let roots = set
+        .file
+        .iter()
+        .map(|file| file.name().to_string())
+        .collect();
+    unit_from_descriptors(&set.file, roots).map_err(|e| invalid(e.to_string()))
+}
+
+/// Converts the descriptors of a set of files into a compilation unit.
+///
+/// # Arguments
+///
+/// * `descriptors` - The files, imports before the files that import them
+/// * `roots` - The names of the requested files
+///
+/// # Returns
+///
+/// * `Result<CompilationUnit, ConversionError>` - The converted files, or the
+///   error of the first descriptor that cannot be converted
+pub fn unit_from_descriptors(
+    descriptors: &[FileDescriptorProto],
+    roots: Vec<String>,
+) -> Result<CompilationUnit, ConversionError> {
+    let mut files = Vec::new();
+    for descriptor in descriptors {
+        files.push(SourceFile {
+            name: descriptor.name().to_string(),
+            path: None,
+            source: String::new(),
+            proto: from_file_descriptor_proto(descriptor)?,
+        });
+    }
+    Ok(CompilationUnit::from_files(files, roots))
+}
+
+/// Converts the files of a resolved compilation unit into a
+/// `FileDescriptorSet`, like `protoc --descriptor_set_out` does.
+///
+/// # Arguments
+///
+/// * `unit` - The resolved compilation unit
+/// * `include_imports` - Whether to include the files the roots import,
+///   directly or not, like protoc's `--include_imports`
+/// * `source_info` - Whether to include `SourceCodeInfo`, like protoc's
+///   `--include_source_info`
+///
+/// # Returns
+///
+/// * `Result<FileDescriptorSet, ConversionError>` - The set, with imports
+///   before the files that import them, or the error of the first file that
+///   cannot be converted
+pub fn to_file_descriptor_set(
+    unit: &CompilationUnit,
+    include_imports: bool,
+    source_info: bool,
+) -> Result<FileDescriptorSet, ConversionError> {
+    let mut names: HashSet<&str> = unit.roots().map(|file| file.name.as_str()).collect();
+    if include_imports {
+        let mut pending: Vec<&str> = names.iter().copied().collect();
+        while let Some(name) = pending.pop() {
+            let Some(file) = unit.get(name) else {
+                continue;
+            };
+            for import in &file.proto.imports {
+                if names.insert(import.path.as_str()) {
+                    pending.push(import.path.as_str());
+                }
+            }
+        }
+    }
+
+    // The files of a unit are already ordered by their imports
+    let mut set = FileDescriptorSet::default();
+    for file in unit.files() {
+        if names.contains(file.name.as_str()) {
+            set.file.push(to_file_descriptor_proto(
+                &file.name,
+                &file.proto,
+                source_info,
+            )?);
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_descriptor_set() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("files/descriptors");
+        let unit = load_descriptor_set(fixtures.join("shop.binpb")).unwrap();
+
+        let roots: Vec<&str> = unit.roots().map(|f| f.name.as_str()).collect();
+        assert_eq!(roots, vec!["google/protobuf/timestamp.proto", "shop.proto"]);
+        let visible: Vec<&str> = unit
+            .visible_files("shop.proto")
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(visible, vec!["google/protobuf/timestamp.proto"]);
+
+        let error = load_descriptor_set(fixtures.join("shop.proto")).unwrap_err();
+        assert!(matches!(error, LoadError::InvalidDescriptor { .. }));
+    }
+
+    #[test]
+    fn test_to_file_descriptor_set() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("files/descriptors");
+        let mut unit = crate::loader::Loader::new([&fixtures])
+            .load(&[fixtures.join("shop.proto")])
+            .unwrap();
+        unit.resolve().unwrap();
+
+        let names = |set: &FileDescriptorSet| -> Vec<String> {
+            set.file.iter().map(|f| f.name().to_string()).collect()
+        };
+        let set = to_file_descriptor_set(&unit, false, false).unwrap();
+        assert_eq!(names(&set), vec!["shop.proto"]);
+        assert!(set.file[0].source_code_info.is_none());
+
+        let set = to_file_descriptor_set(&unit, true, true).unwrap();
+        assert_eq!(
+            names(&set),
+            vec!["google/protobuf/timestamp.proto", "shop.proto"]
+        );
+        assert!(set.file[1].source_code_info.is_some());
+    }
+}
@@ -0,0 +1,431 @@
+//! Reporting of parse errors
+//!
+//! For people, errors are rendered like rustc's: the message, the file and
+//! position, the offending source line with a caret underline, and a help
+//! note if there is one.
+//!
+//! ```text
+//! error: Unexpected token: Expected field number, found Semicolon
+//!  --> broken.proto:2:24
+//!   |
+//! 2 | message A { string a = ; }
+//!   |                        ^
+//! ```
+//!
+//! For tools, errors are converted into [`Diagnostic`]s and emitted as JSON
+//! or SARIF, see [`to_json`] and [`to_sarif`].
+
+mod report;
+
+pub use report::{to_json, to_sarif};
+
+use std::env;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+use crate::loader::LoadError;
+use crate::parser::error::Location;
+use crate::parser::ParseError;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// How errors are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Rendered for people, see [`render`]
+    #[default]
+    Human,
+    /// A JSON array of records, see [`to_json`]
+    Json,
+    /// A SARIF log, see [`to_sarif`]
+    Sarif,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            _ => Err(format!(
+                "Unknown error format '{}', expected 'human', 'json' or 'sarif'",
+                s
+            )),
+        }
+    }
+}
+
+/// An error as a structured record, for machine-readable output
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The name of the file the error is in
+    pub file: String,
+    /// The stable code of the error's kind, e.g. `E0003`
+    pub code: &'static str,
+    pub message: String,
+    pub help: Option<&'static str>,
+    /// The start and end of the offending source text, or `None` if the
+    /// error has no location. Lines and columns are 1-based, columns count
+    /// characters and the end is exclusive.
+    pub range: Option<(Location, Location)>,
+}
+
+impl Diagnostic {
+    /// Converts a parse error into a diagnostic. The range covers the token
+    /// at the error's location, as far as it can be told from `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The name of the file
+    /// * `source` - The text of the file
+    /// * `error` - The error to convert
+    ///
+    /// # Returns
+    ///
+    /// * `Diagnostic` - The diagnostic of the error
+    pub fn from_parse_error(file: &str, source: &str, error: &ParseError) -> Self {
+        let location = error.location();
+        let range = (location.line > 0).then(|| {
+            let Some(line) = source.lines().nth(location.line - 1) else {
+                let end = Location::new(location.line, location.column + 1);
+                return (location, end);
+            };
+            let start = floor_char_boundary(line, location.column.saturating_sub(1));
+            let column = line[..start].chars().count() + 1;
+            let width = token_width(&line[start..]);
+            (
+                Location::new(location.line, column),
+                Location::new(location.line, column + width),
+            )
+        });
+
+        Diagnostic {
+            file: file.to_string(),
+            code: error.code(),
+            message: error.message(),
+            help: error.help(),
+            range,
+        }
+    }
+
+    /// Converts an error of loading files into diagnostics: one for every
+    /// parse error, or a single one for other errors.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error to convert
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Diagnostic>` - The diagnostics of the error
+    pub fn from_load_error(error: &LoadError) -> Vec<Self> {
+        match error {
+            LoadError::Resolve {
+                file,
+                source,
+                error,
+            } => vec![Diagnostic::from_parse_error(file, source, error)],
+            LoadError::Syntax {
+                file,
+                source,
+                errors,
+            } => errors
+                .iter()
+                .map(|error| Diagnostic::from_parse_error(file, source, error))
+                .collect(),
+            LoadError::NotFound {
+                imported_from,
+                location,
+                ..
+            } => vec![Diagnostic {
+                file: imported_from.clone(),
+                code: error.code(),
+                message: error.to_string(),
+                help: Some("add the directory that contains the file with -I"),
+                range: Some((*location, Location::new(location.line, location.column + 1))),
+            }],
+            LoadError::Io { path, .. } | LoadError::InvalidDescriptor { path, .. } => {
+                vec![Diagnostic {
+                    file: path.display().to_string(),
+                    code: error.code(),
+                    message: error.to_string(),
+                    help: None,
+                    range: None,
+                }]
+            }
+            LoadError::ImportCycle(names) => vec![Diagnostic {
+                file: names[0].clone(),
+                code: error.code(),
+                message: error.to_string(),
+                help: None,
+                range: None,
+            }],
+        }
+    }
+}
+
+/// Returns whether diagnostics written to stderr should be coloured: stderr
+/// is a terminal and `NO_COLOR` is not set.
+pub fn use_color() -> bool {
+    std::io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// Renders a parse error with a snippet of the source it was found in.
+///
+/// The underline covers the token at the error's location. Errors without a
+/// location, or with one outside of `source`, are rendered without a
+/// snippet.
+///
+/// # Arguments
+///
+/// * `file` - The name of the file, as shown to the user
+/// * `source` - The text of the file
+/// * `error` - The error to render
+/// * `color` - Whether to colour the output with ANSI escape codes
+///
+/// # Returns
+///
+/// * `String` - The rendered diagnostic, ending with a newline
+pub fn render(file: &str, source: &str, error: &ParseError, color: bool) -> String {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let location = error.location();
+    let mut out = format!(
+        "{}{}\n",
+        paint(RED, "error"),
+        paint(BOLD, &format!(": {}", error.message()))
+    );
+
+    let line = location
+        .line
+        .checked_sub(1)
+        .and_then(|index| source.lines().nth(index));
+    let Some(line) = line else {
+        out.push_str(&format!("{} {}\n", paint(BLUE, "-->"), file));
+        push_help(&mut out, error, &paint, "");
+        return out;
+    };
+
+    let line_number = location.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+    out.push_str(&format!(
+        "{}{} {}:{}:{}\n",
+        gutter,
+        paint(BLUE, "-->"),
+        file,
+        location.line,
+        location.column
+    ));
+    out.push_str(&format!("{} {}\n", gutter, paint(BLUE, "|")));
+    out.push_str(&format!(
+        "{} {} {}\n",
+        paint(BLUE, &line_number),
+        paint(BLUE, "|"),
+        line
+    ));
+
+    // Columns count bytes; a column past the end points after the last character
+    let start = floor_char_boundary(line, location.column.saturating_sub(1));
+    let padding: String = line[..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = token_width(&line[start..]);
+    out.push_str(&format!(
+        "{} {} {}{}\n",
+        gutter,
+        paint(BLUE, "|"),
+        padding,
+        paint(RED, &"^".repeat(width))
+    ));
+
+    push_help(&mut out, error, &paint, &gutter);
+    out
+}
+
+/// Renders an error of loading files, with source snippets for parse and
+/// resolution errors.
+///
+/// # Arguments
+///
+/// * `error` - The error to render
+/// * `color` - Whether to colour the output with ANSI escape codes
+///
+/// # Returns
+///
+/// * `String` - The rendered diagnostics, separated by blank lines and
+///   ending with a newline
+pub fn render_load_error(error: &LoadError, color: bool) -> String {
+    match error {
+        LoadError::Resolve {
+            file,
+            source,
+            error,
+        } => render(file, source, error, color),
+        LoadError::Syntax {
+            file,
+            source,
+            errors,
+        } => errors
+            .iter()
+            .map(|error| render(file, source, error, color))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        other if color => format!("{}error{}: {}\n", RED, RESET, other),
+        other => format!("error: {}\n", other),
+    }
+}
+
+fn push_help(
+    out: &mut String,
+    error: &ParseError,
+    paint: &dyn Fn(&str, &str) -> String,
+    gutter: &str,
+) {
+    if let Some(help) = error.help() {
+        out.push_str(&format!(
+            "{} {} {}: {}\n",
+            gutter,
+            paint(BLUE, "="),
+            paint(BOLD, "help"),
+            help
+        ));
+    }
+}
+
+/// Returns the width in characters of the token at the start of `text`: a
+/// word, a quoted string, or a single other character. At least 1, so that
+/// the end of a line can be pointed at.
+fn token_width(text: &str) -> usize {
+    let mut chars = text.chars();
+    let width = match chars.next() {
+        None => 0,
+        Some(quote @ ('"' | '\'')) => match chars.position(|c| c == quote) {
+            Some(end) => end + 2,
+            None => text.chars().count(),
+        },
+        Some(c) if is_word_char(c) => text.chars().take_while(|&c| is_word_char(c)).count(),
+        Some(_) => 1,
+    };
+    width.max(1)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::error::Location;
+    use crate::parser::parse_proto_file;
+
+    #[test]
+    fn test_render() {
+        let source = "syntax = \"proto3\";\nmessage A { string a = ; }\n";
+        let error = parse_proto_file(source).unwrap_err();
+
+        assert_eq!(
+            render("broken.proto", source, &error, false),
+            "error: Unexpected token: Expected field number, found Semicolon\n \
+             --> broken.proto:2:24\n  \
+             |\n\
+             2 | message A { string a = ; }\n  \
+             |                        ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_underlines_token_and_help() {
+        let source = "syntax = \"proto3\";\n\tmessage A { Missing.Type b = 1; }\n";
+        let error = ParseError::UnknownType("Missing.Type".to_string(), Location::new(2, 14));
+
+        let rendered = render("a.proto", source, &error, false);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[4], "  | \t            ^^^^^^^^^^^^");
+        assert_eq!(
+            lines[5],
+            "  = help: check the spelling of the type and that the file defining it is imported"
+        );
+    }
+
+    #[test]
+    fn test_render_without_snippet() {
+        let error = ParseError::UnexpectedEndOfInput(Location::new(0, 0));
+        assert_eq!(
+            render("a.proto", "", &error, false),
+            "error: Unexpected end of input\n--> a.proto\n \
+             = help: a closing `}` or `;` may be missing before this point\n"
+        );
+    }
+
+    #[test]
+    fn test_render_load_error() {
+        let error = LoadError::Syntax {
+            file: "a.proto".to_string(),
+            source: "foo\nbar\n".to_string(),
+            errors: vec![
+                ParseError::Other("first".to_string(), Location::new(1, 1)),
+                ParseError::Other("second".to_string(), Location::new(2, 1)),
+            ],
+        };
+        let rendered = render_load_error(&error, false);
+        assert!(rendered.contains("1 | foo\n  | ^^^\n\nerror: Other error: second\n"));
+
+        let error = LoadError::ImportCycle(vec!["a.proto".to_string(), "a.proto".to_string()]);
+        assert_eq!(
+            render_load_error(&error, false),
+            "error: Import cycle: a.proto -> a.proto\n"
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_range() {
+        // Columns of diagnostics count characters, those of errors bytes
+        let source = "// é\n/* é */ foo bar;\n";
+        let error = ParseError::Other("oops".to_string(), Location::new(2, 10));
+        let diagnostic = Diagnostic::from_parse_error("a.proto", source, &error);
+        assert_eq!(diagnostic.code, "E0015");
+        assert_eq!(
+            diagnostic.range,
+            Some((Location::new(2, 9), Location::new(2, 12)))
+        );
+
+        let diagnostic = Diagnostic::from_parse_error("a.proto", "", &error);
+        assert_eq!(
+            diagnostic.range,
+            Some((Location::new(2, 10), Location::new(2, 11)))
+        );
+
+        let error = ParseError::UnexpectedEndOfInput(Location::new(0, 0));
+        let diagnostic = Diagnostic::from_parse_error("a.proto", source, &error);
+        assert_eq!(diagnostic.range, None);
+    }
+
+    #[test]
+    fn test_render_color() {
+        let error = ParseError::Other("oops".to_string(), Location::new(1, 1));
+        let rendered = render("a.proto", "foo", &error, true);
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^^^\x1b[0m"));
+    }
+}
@@ -0,0 +1,172 @@
+//! JSON and SARIF output of diagnostics
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use super::Diagnostic;
+
+/// Version of the SARIF format written by [`to_sarif`]
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Serializes diagnostics as a JSON array with one record per diagnostic.
+///
+/// A record has the fields `file`, `severity` (always `error`), `code`,
+/// `message`, `help` (or `null`) and `range` (or `null`), a range being
+/// `{ "start": { "line", "column" }, "end": { "line", "column" } }` with the
+/// lines and columns of the [`Diagnostic`].
+///
+/// # Arguments
+///
+/// * `diagnostics` - The diagnostics to serialize
+///
+/// # Returns
+///
+/// * `String` - The JSON array
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let records: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "file": diagnostic.file,
+                "severity": "error",
+                "code": diagnostic.code,
+                "message": diagnostic.message,
+                "help": diagnostic.help,
+                "range": diagnostic.range.map(|(start, end)| json!({
+                    "start": { "line": start.line, "column": start.column },
+                    "end": { "line": end.line, "column": end.column },
+                })),
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&records).expect("JSON values always serialize")
+}
+
+/// Serializes diagnostics as a SARIF log, the format GitHub code scanning
+/// accepts.
+///
+/// The log has a single run whose rules are the codes of the diagnostics.
+/// An empty log is valid, so the log of a run without errors can be uploaded
+/// as well.
+///
+/// # Arguments
+///
+/// * `diagnostics` - The diagnostics to serialize
+///
+/// # Returns
+///
+/// * `String` - The SARIF log
+pub fn to_sarif(diagnostics: &[Diagnostic]) -> String {
+    // One rule per code, described by the first help note of that code
+    let mut rules: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        let help = rules.entry(diagnostic.code).or_default();
+        *help = help.or(diagnostic.help);
+    }
+    let rules: Vec<Value> = rules
+        .into_iter()
+        .map(|(code, help)| {
+            let mut rule = json!({ "id": code });
+            if let Some(help) = help {
+                rule["help"] = json!({ "text": help });
+            }
+            rule
+        })
+        .collect();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut location = json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": diagnostic.file },
+                },
+            });
+            if let Some((start, end)) = diagnostic.range {
+                location["physicalLocation"]["region"] = json!({
+                    "startLine": start.line,
+                    "startColumn": start.column,
+                    "endLine": end.line,
+                    "endColumn": end.column,
+                });
+            }
+            json!({
+                "ruleId": diagnostic.code,
+                "level": "error",
+                "message": { "text": diagnostic.message },
+                "locations": [location],
+            })
+        })
+        .collect();
+
+    let log = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&log).expect("JSON values always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_proto_file_recovering;
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        let source = "syntax = \"proto3\";\nmessage A { string a = ; }\n";
+        let (_, errors) = parse_proto_file_recovering(source);
+        errors
+            .iter()
+            .map(|error| Diagnostic::from_parse_error("a.proto", source, error))
+            .collect()
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: Value = serde_json::from_str(&to_json(&diagnostics())).unwrap();
+        assert_eq!(
+            json,
+            json!([{
+                "file": "a.proto",
+                "severity": "error",
+                "code": "E0003",
+                "message": "Unexpected token: Expected field number, found Semicolon",
+                "help": null,
+                "range": {
+                    "start": { "line": 2, "column": 24 },
+                    "end": { "line": 2, "column": 25 },
+                },
+            }])
+        );
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let sarif: Value = serde_json::from_str(&to_sarif(&diagnostics())).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"], json!([{ "id": "E0003" }]));
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "E0003");
+        assert_eq!(result["level"], "error");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "a.proto");
+        assert_eq!(location["region"]["startColumn"], 24);
+
+        let empty: Value = serde_json::from_str(&to_sarif(&[])).unwrap();
+        assert_eq!(empty["runs"][0]["results"], json!([]));
+    }
+}
@@ -0,0 +1,365 @@
+//! Application of `// @zod { ... }` annotations to generated schemas
+
+use super::{is_identifier, quote};
+use crate::parser::ast::{OptionValue, ZodAnnotation, ZodArrayRules};
+use crate::ConversionError;
+
+/// The kind of value a field schema validates, which decides the rules that
+/// can be applied to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueKind {
+    String,
+    Number,
+    /// A number schema that already has `.int()`
+    Integer,
+    BigInt,
+    Other,
+}
+
+/// Appends the value rules (`min`, `max`, `regex`, `email`, ...) to the schema
+/// of a single value.
+pub(crate) fn apply_value_rules(
+    schema: String,
+    kind: ValueKind,
+    annotation: &ZodAnnotation,
+    name: &str,
+) -> Result<String, ConversionError> {
+    let mut schema = schema;
+
+    let string_rules = [
+        ("regex", annotation.regex.is_some()),
+        ("email", annotation.email),
+        ("url", annotation.url),
+        ("uuid", annotation.uuid),
+    ];
+    let number_rules = [
+        ("positive", annotation.positive),
+        ("negative", annotation.negative),
+        ("int", annotation.int),
+    ];
+    for (rule, _) in string_rules.iter().filter(|(_, set)| *set) {
+        if kind != ValueKind::String {
+            return Err(inapplicable(rule, name, "string"));
+        }
+    }
+    for (rule, _) in number_rules.iter().filter(|(_, set)| *set) {
+        if !matches!(
+            kind,
+            ValueKind::Number | ValueKind::Integer | ValueKind::BigInt
+        ) {
+            return Err(inapplicable(rule, name, "numeric"));
+        }
+    }
+
+    for (rule, bound) in [("min", annotation.min), ("max", annotation.max)] {
+        let Some(bound) = bound else { continue };
+        let literal = match kind {
+            ValueKind::String if bound >= 0.0 && bound.fract() == 0.0 => number_literal(bound),
+            ValueKind::String => {
+                return Err(ConversionError::GenerationError(format!(
+                    "@zod {} on string field '{}' must be a non-negative integer length",
+                    rule, name
+                )))
+            }
+            ValueKind::Number | ValueKind::Integer => number_literal(bound),
+            ValueKind::BigInt if bound.fract() == 0.0 => format!("{}n", number_literal(bound)),
+            ValueKind::BigInt => {
+                return Err(ConversionError::GenerationError(format!(
+                    "@zod {} on bigint field '{}' must be an integer",
+                    rule, name
+                )))
+            }
+            ValueKind::Other => return Err(inapplicable(rule, name, "string or numeric")),
+        };
+        schema = format!("{}.{}({})", schema, rule, literal);
+    }
+
+    if let Some(regex) = &annotation.regex {
+        schema = format!("{}.regex({})", schema, regex_literal(regex));
+    }
+    for (rule, set) in string_rules.iter().skip(1).chain(&number_rules) {
+        if *set && !(*rule == "int" && kind != ValueKind::Number) {
+            schema = format!("{}.{}()", schema, rule);
+        }
+    }
+
+    Ok(schema)
+}
+
+/// Appends the length rules of a repeated field to its array schema.
+pub(crate) fn apply_array_rules(schema: String, rules: &ZodArrayRules) -> String {
+    let mut schema = schema;
+    for (rule, value) in [
+        ("min", rules.min),
+        ("max", rules.max),
+        ("length", rules.length),
+    ] {
+        if let Some(value) = value {
+            schema = format!("{}.{}({})", schema, rule, value);
+        }
+    }
+    schema
+}
+
+/// Appends `custom` as a refinement and `nullable`, in that order.
+///
+/// A custom validator is only in scope when it is imported, so `custom` is an
+/// error unless a validators module is configured (`has_validators`).
+pub(crate) fn apply_refinements(
+    schema: String,
+    annotation: &ZodAnnotation,
+    name: &str,
+    has_validators: bool,
+) -> Result<String, ConversionError> {
+    let mut schema = schema;
+    if let Some(custom) = &annotation.custom {
+        if !is_identifier(custom) {
+            return Err(ConversionError::GenerationError(format!(
+                "@zod custom validator '{}' is not a valid identifier",
+                custom
+            )));
+        }
+        if !has_validators {
+            return Err(ConversionError::GenerationError(format!(
+                "@zod custom validator '{}' of '{}' is never imported; pass \
+                 --custom-validators (GeneratorOptions::custom_validators) with the module \
+                 that exports it",
+                custom, name
+            )));
+        }
+        schema = format!("{}.refine({})", schema, custom);
+    }
+    if annotation.nullable {
+        schema.push_str(".nullable()");
+    }
+    Ok(schema)
+}
+
+/// Appends `default` and `description`, which must come last so that they
+/// apply to the final schema.
+pub(crate) fn apply_metadata(schema: String, annotation: &ZodAnnotation) -> String {
+    let mut schema = schema;
+    if let Some(default) = &annotation.default {
+        schema = format!("{}.default({})", schema, js_literal(default));
+    }
+    if let Some(description) = &annotation.description {
+        schema = format!("{}.describe({})", schema, quote(description));
+    }
+    schema
+}
+
+/// Checks that a message or enum annotation only uses the options that make
+/// sense for a whole declaration: `description` and `custom`.
+pub(crate) fn check_declaration_annotation(
+    annotation: &ZodAnnotation,
+    name: &str,
+) -> Result<(), ConversionError> {
+    let unsupported = [
+        ("min", annotation.min.is_some()),
+        ("max", annotation.max.is_some()),
+        ("regex", annotation.regex.is_some()),
+        ("email", annotation.email),
+        ("url", annotation.url),
+        ("uuid", annotation.uuid),
+        ("positive", annotation.positive),
+        ("negative", annotation.negative),
+        ("int", annotation.int),
+        ("default", annotation.default.is_some()),
+        ("optional", annotation.optional),
+        ("nullable", annotation.nullable),
+        ("array", annotation.array.is_some()),
+    ];
+
+    match unsupported.iter().find(|(_, set)| *set) {
+        Some((rule, _)) => Err(ConversionError::GenerationError(format!(
+            "@zod {} is not supported on '{}'; only description and custom apply to messages and enums",
+            rule, name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Renders an annotation value as a JavaScript literal.
+pub(crate) fn js_literal(value: &OptionValue) -> String {
+    match value {
+        OptionValue::String(s) | OptionValue::Identifier(s) => quote(s),
+        OptionValue::Enum(_, value) => quote(value),
+        OptionValue::DecimalInt(n) | OptionValue::Octal(n) | OptionValue::Hex(n) => n.to_string(),
+        OptionValue::Float(f) if f.is_nan() => "NaN".to_string(),
+        OptionValue::Float(f) if f.is_infinite() => {
+            if *f > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+        }
+        OptionValue::Float(f) => number_literal(*f),
+        OptionValue::Bool(b) => b.to_string(),
+        OptionValue::List(values) => {
+            let values: Vec<String> = values.iter().map(js_literal).collect();
+            format!("[{}]", values.join(", "))
+        }
+        OptionValue::Map(entries) => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| format!("[{}]: {}", js_literal(key), js_literal(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        OptionValue::Message(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| format!("{}: {}", quote(key), js_literal(value)))
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+    }
+}
+
+/// Formats a number without a trailing `.0` for whole values.
+fn number_literal(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Renders a pattern as a regular expression literal.
+fn regex_literal(pattern: &str) -> String {
+    let mut literal = String::from("/");
+    let mut escaped = false;
+    for ch in pattern.chars() {
+        match ch {
+            '/' if !escaped => literal.push_str("\\/"),
+            '\n' => literal.push_str("\\n"),
+            c => literal.push(c),
+        }
+        escaped = ch == '\\' && !escaped;
+    }
+    literal.push('/');
+    literal
+}
+
+fn inapplicable(rule: &str, name: &str, expected: &str) -> ConversionError {
+    ConversionError::GenerationError(format!(
+        "@zod {} requires a {} field, but '{}' is not one",
+        rule, expected, name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_value_rules() {
+        let annotation = ZodAnnotation {
+            min: Some(3.0),
+            max: Some(50.0),
+            email: true,
+            regex: Some("^a/b$".to_string()),
+            ..ZodAnnotation::default()
+        };
+        assert_eq!(
+            apply_value_rules(
+                "z.string()".to_string(),
+                ValueKind::String,
+                &annotation,
+                "x"
+            )
+            .unwrap(),
+            r#"z.string().min(3).max(50).regex(/^a\/b$/).email()"#
+        );
+
+        let annotation = ZodAnnotation {
+            min: Some(-1.5),
+            positive: true,
+            int: true,
+            ..ZodAnnotation::default()
+        };
+        assert_eq!(
+            apply_value_rules(
+                "z.number()".to_string(),
+                ValueKind::Number,
+                &annotation,
+                "x"
+            )
+            .unwrap(),
+            "z.number().min(-1.5).positive().int()"
+        );
+        assert_eq!(
+            apply_value_rules(
+                "z.number().int()".to_string(),
+                ValueKind::Integer,
+                &ZodAnnotation {
+                    int: true,
+                    ..ZodAnnotation::default()
+                },
+                "x"
+            )
+            .unwrap(),
+            "z.number().int()"
+        );
+        assert_eq!(
+            apply_value_rules(
+                "z.bigint()".to_string(),
+                ValueKind::BigInt,
+                &ZodAnnotation {
+                    max: Some(10.0),
+                    ..ZodAnnotation::default()
+                },
+                "x"
+            )
+            .unwrap(),
+            "z.bigint().max(10n)"
+        );
+    }
+
+    #[test]
+    fn test_apply_value_rules_errors() {
+        let email = ZodAnnotation {
+            email: true,
+            ..ZodAnnotation::default()
+        };
+        assert!(
+            apply_value_rules("z.number()".to_string(), ValueKind::Number, &email, "x").is_err()
+        );
+
+        let fractional_length = ZodAnnotation {
+            min: Some(1.5),
+            ..ZodAnnotation::default()
+        };
+        assert!(apply_value_rules(
+            "z.string()".to_string(),
+            ValueKind::String,
+            &fractional_length,
+            "x"
+        )
+        .is_err());
+        assert!(check_declaration_annotation(&email, "Message").is_err());
+    }
+
+    #[test]
+    fn test_js_literal() {
+        assert_eq!(
+            js_literal(&OptionValue::String("a\"b".to_string())),
+            r#""a\"b""#
+        );
+        assert_eq!(js_literal(&OptionValue::Float(2.0)), "2");
+        assert_eq!(
+            js_literal(&OptionValue::Float(f64::NEG_INFINITY)),
+            "-Infinity"
+        );
+        assert_eq!(
+            js_literal(&OptionValue::List(vec![
+                OptionValue::DecimalInt(1),
+                OptionValue::Bool(true)
+            ])),
+            "[1, true]"
+        );
+        assert_eq!(
+            js_literal(&OptionValue::Message(vec![(
+                "name".to_string(),
+                OptionValue::String("x".to_string())
+            )])),
+            r#"{ "name": "x" }"#
+        );
+    }
+}
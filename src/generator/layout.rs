@@ -0,0 +1,97 @@
+//! Names of generated files and the module specifiers between them
+
+use std::str::FromStr;
+
+/// How generated files are arranged in the output directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Mirror the import paths, e.g. `foo/bar.proto` becomes `foo/bar.zod.ts`
+    Tree,
+    /// Write every file to the output directory itself, e.g. `bar.zod.ts`
+    Flat,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Layout::Tree),
+            "flat" => Ok(Layout::Flat),
+            _ => Err(format!("Unknown layout '{}', expected 'tree' or 'flat'", s)),
+        }
+    }
+}
+
+/// Returns the path of the file generated for a `.proto` file, relative to
+/// the output directory.
+///
+/// # Arguments
+///
+/// * `proto_name` - The import name of the file, e.g. `foo/bar.proto`
+/// * `layout` - How generated files are arranged
+pub fn output_path(proto_name: &str, layout: Layout) -> String {
+    let name = match layout {
+        Layout::Tree => proto_name,
+        Layout::Flat => proto_name.rsplit('/').next().unwrap_or(proto_name),
+    };
+    format!("{}.zod.ts", name.strip_suffix(".proto").unwrap_or(name))
+}
+
+/// Returns the relative module specifier that the generated file `from`
+/// uses to import the generated file `to`, e.g. `../common/types.zod`.
+pub fn module_specifier(from: &str, to: &str) -> String {
+    let from_dirs: Vec<&str> = from.split('/').collect();
+    let from_dirs = &from_dirs[..from_dirs.len() - 1];
+    let to_parts: Vec<&str> = to.split('/').collect();
+
+    let common = from_dirs
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut specifier = if common == from_dirs.len() {
+        String::from("./")
+    } else {
+        "../".repeat(from_dirs.len() - common)
+    };
+    specifier.push_str(&to_parts[common..].join("/"));
+    if specifier.ends_with(".ts") {
+        specifier.truncate(specifier.len() - ".ts".len());
+    }
+    specifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        assert_eq!(output_path("foo/bar.proto", Layout::Tree), "foo/bar.zod.ts");
+        assert_eq!(output_path("foo/bar.proto", Layout::Flat), "bar.zod.ts");
+        assert_eq!(output_path("stdin", Layout::Tree), "stdin.zod.ts");
+        assert!("nested".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_module_specifier() {
+        assert_eq!(
+            module_specifier("main.zod.ts", "types.zod.ts"),
+            "./types.zod"
+        );
+        assert_eq!(
+            module_specifier("app/main.zod.ts", "common/types.zod.ts"),
+            "../common/types.zod"
+        );
+        assert_eq!(
+            module_specifier("main.zod.ts", "common/types.zod.ts"),
+            "./common/types.zod"
+        );
+        assert_eq!(
+            module_specifier("app/v1/main.zod.ts", "app/types.zod.ts"),
+            "../types.zod"
+        );
+    }
+}
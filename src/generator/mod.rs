@@ -0,0 +1,1738 @@
+//! Zod schema generator
+//!
+//! This module turns a parsed [`ProtoFile`] into TypeScript source code that
+//! declares one Zod schema per message and enum, together with the inferred
+//! TypeScript types.
+
+mod annotations;
+mod layout;
+mod well_known;
+
+pub use layout::{module_specifier, output_path, Layout};
+pub use well_known::TimestampMode;
+
+use crate::loader::CompilationUnit;
+use crate::parser::ast::{
+    Comments, Enum, Field, FieldLabel, FieldType, Message, OneOf, OptionValue, ProtoFile, Syntax,
+    ZodAnnotation,
+};
+use crate::parser::resolver::{ResolvedType, SymbolTable, TypeKind};
+use crate::parser::validator::enum_value_number;
+use crate::ConversionError;
+
+use annotations::ValueKind;
+use log::{debug, warn};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::str::FromStr;
+use well_known::{is_well_known, is_well_known_enum, well_known_schema, well_known_type};
+
+/// How 64-bit integer fields are represented in the generated schemas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Int64Mode {
+    /// Decimal strings, as produced by the protobuf JSON mapping
+    String,
+    /// JavaScript numbers (values above 2^53 lose precision)
+    Number,
+    /// JavaScript `bigint` values
+    BigInt,
+}
+
+impl FromStr for Int64Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(Int64Mode::String),
+            "number" => Ok(Int64Mode::Number),
+            "bigint" => Ok(Int64Mode::BigInt),
+            _ => Err(format!(
+                "Unknown int64 mode '{}', expected 'string', 'number' or 'bigint'",
+                s
+            )),
+        }
+    }
+}
+
+/// How enums are represented in the generated schemas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumStyle {
+    /// Value names, e.g. `z.enum(["UNKNOWN", "ACTIVE"])`
+    Names,
+    /// Numeric values, e.g. `z.union([z.literal(0), z.literal(1)])`
+    Numbers,
+}
+
+impl FromStr for EnumStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "names" => Ok(EnumStyle::Names),
+            "numbers" => Ok(EnumStyle::Numbers),
+            _ => Err(format!(
+                "Unknown enum style '{}', expected 'names' or 'numbers'",
+                s
+            )),
+        }
+    }
+}
+
+/// How `oneof` groups are represented in the generated schemas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OneofStyle {
+    /// A property named after the oneof holding a `z.discriminatedUnion` on
+    /// `oneofKind`, matching the protobuf-ts shape
+    DiscriminatedUnion,
+    /// The member fields flattened into the parent object, with a `z.union`
+    /// that allows at most one of them to be set, matching the protobuf JSON shape
+    Union,
+}
+
+impl FromStr for OneofStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "discriminated-union" => Ok(OneofStyle::DiscriminatedUnion),
+            "union" => Ok(OneofStyle::Union),
+            _ => Err(format!(
+                "Unknown oneof style '{}', expected 'discriminated-union' or 'union'",
+                s
+            )),
+        }
+    }
+}
+
+/// Options that control the shape of the generated TypeScript code
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorOptions {
+    pub int64: Int64Mode,
+    pub enum_style: EnumStyle,
+    pub oneof_style: OneofStyle,
+    pub timestamp: TimestampMode,
+    /// Use lowerCamelCase property names (the protobuf JSON names)
+    /// instead of the field names from the `.proto` file
+    pub json_names: bool,
+    /// Emit `export type X = z.infer<typeof X>` next to every schema
+    pub export_types: bool,
+    /// Emit the comments of messages, enums and fields as JSDoc
+    pub comments: bool,
+    /// Module that exports the functions named by `@zod { custom: ... }`
+    /// annotations. When set, the generated code imports them from it.
+    pub custom_validators: Option<String>,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            int64: Int64Mode::String,
+            enum_style: EnumStyle::Names,
+            oneof_style: OneofStyle::DiscriminatedUnion,
+            timestamp: TimestampMode::String,
+            json_names: false,
+            export_types: true,
+            comments: true,
+            custom_validators: None,
+        }
+    }
+}
+
+/// Generate Zod schemas for every message and enum in a Protobuf file
+///
+/// Nested messages and enums are flattened into top-level declarations named
+/// after their full path (`Outer_Inner`). Enums are emitted first, followed by
+/// messages in dependency order; references that cannot be satisfied by an
+/// earlier declaration (e.g. recursive messages) are wrapped in `z.lazy`, and
+/// the messages of such reference cycles are declared with an explicit type,
+/// since TypeScript cannot infer it.
+///
+/// # Arguments
+///
+/// * `proto_file` - The parsed Protobuf file
+/// * `options` - Options controlling the generated code
+///
+/// # Returns
+///
+/// * `Result<String, ConversionError>` - The generated TypeScript source, or a
+///   `GenerationError` if a construct cannot be expressed in Zod
+pub fn generate_zod(
+    proto_file: &ProtoFile,
+    options: &GeneratorOptions,
+) -> Result<String, ConversionError> {
+    ZodGenerator::new(proto_file, options).generate()
+}
+
+/// A file whose schemas are generated into another module, together with
+/// the module specifier to import them from (e.g. `"./common/types.zod"`)
+#[derive(Debug, Clone, Copy)]
+pub struct ImportedFile<'a> {
+    pub module: &'a str,
+    pub proto_file: &'a ProtoFile,
+}
+
+/// Generate Zod schemas for a Protobuf file that references the messages and
+/// enums of other files
+///
+/// Works like [`generate_zod`], but references to definitions of the
+/// `imports` are satisfied by importing their schemas from the module that
+/// the imported file is generated into.
+///
+/// # Arguments
+///
+/// * `proto_file` - The parsed Protobuf file
+/// * `imports` - The files visible from `proto_file` and their modules
+/// * `options` - Options controlling the generated code
+///
+/// # Returns
+///
+/// * `Result<String, ConversionError>` - The generated TypeScript source, or a
+///   `GenerationError` if a construct cannot be expressed in Zod
+pub fn generate_zod_with_imports(
+    proto_file: &ProtoFile,
+    imports: &[ImportedFile],
+    options: &GeneratorOptions,
+) -> Result<String, ConversionError> {
+    let mut generator = ZodGenerator::new(proto_file, options);
+    for import in imports {
+        generator.add_import(import);
+    }
+    generator.generate()
+}
+
+/// Generate Zod schemas for one file of a compilation unit
+///
+/// The definitions of the files visible from it are imported from the
+/// modules those files are generated into, as arranged by `layout`.
+///
+/// # Arguments
+///
+/// * `unit` - The loaded and resolved files
+/// * `name` - The import name of the file to generate
+/// * `layout` - How generated files are arranged
+/// * `options` - Options controlling the generated code
+///
+/// # Returns
+///
+/// * `Result<String, ConversionError>` - The generated TypeScript source, or a
+///   `GenerationError` naming the file
+pub fn generate_zod_for_unit(
+    unit: &CompilationUnit,
+    name: &str,
+    layout: Layout,
+    options: &GeneratorOptions,
+) -> Result<String, ConversionError> {
+    let file = unit
+        .get(name)
+        .ok_or_else(|| ConversionError::GenerationError(format!("{}: file is not loaded", name)))?;
+    let output = output_path(name, layout);
+
+    let modules: Vec<(String, &ProtoFile)> = unit
+        .visible_files(name)
+        .into_iter()
+        .map(|import| {
+            let module = module_specifier(&output, &output_path(&import.name, layout));
+            (module, &import.proto)
+        })
+        .collect();
+    let imports: Vec<ImportedFile> = modules
+        .iter()
+        .map(|(module, proto_file)| ImportedFile { module, proto_file })
+        .collect();
+
+    generate_zod_with_imports(&file.proto, &imports, options)
+        .map_err(|e| ConversionError::GenerationError(format!("{}: {}", name, e)))
+}
+
+/// A message together with the scope it was declared in
+struct ScopedMessage<'a> {
+    full_name: String,
+    ident: String,
+    message: &'a Message,
+}
+
+struct ZodGenerator<'a> {
+    proto_file: &'a ProtoFile,
+    options: &'a GeneratorOptions,
+    types: SymbolTable,
+    /// The TypeScript identifier of each message and enum, by full name
+    idents: HashMap<String, String>,
+    messages: Vec<ScopedMessage<'a>>,
+    enums: Vec<(String, &'a Enum)>,
+    emitted: HashSet<String>,
+    /// The identifiers of the messages in reference cycles, which are
+    /// declared with an explicit type
+    recursive: HashSet<String>,
+    /// The module and exported identifier of each imported message and enum
+    imported: HashMap<String, (String, String)>,
+    /// The imported identifiers that are referenced, by module
+    used_imports: RefCell<BTreeMap<String, BTreeSet<String>>>,
+    out: String,
+}
+
+impl<'a> ZodGenerator<'a> {
+    fn new(proto_file: &'a ProtoFile, options: &'a GeneratorOptions) -> Self {
+        let mut generator = ZodGenerator {
+            proto_file,
+            options,
+            types: SymbolTable::new(),
+            idents: HashMap::new(),
+            messages: Vec::new(),
+            enums: Vec::new(),
+            emitted: HashSet::new(),
+            recursive: HashSet::new(),
+            imported: HashMap::new(),
+            used_imports: RefCell::new(BTreeMap::new()),
+            out: String::new(),
+        };
+
+        generator.types.add_file(proto_file);
+
+        let package = proto_file.package.clone().unwrap_or_default();
+        for enum_def in &proto_file.enums {
+            generator.collect_enum(&package, "", enum_def);
+        }
+        for message in &proto_file.messages {
+            generator.collect_message(&package, "", message);
+        }
+
+        generator
+    }
+
+    fn collect_message(&mut self, scope: &str, ident_prefix: &str, message: &'a Message) {
+        let full_name = qualify(scope, &message.name);
+        let ident = qualify_ident(ident_prefix, &message.name);
+
+        self.idents.insert(full_name.clone(), ident.clone());
+
+        for enum_def in &message.nested_enums {
+            self.collect_enum(&full_name, &ident, enum_def);
+        }
+        for nested in &message.nested_messages {
+            self.collect_message(&full_name, &ident, nested);
+        }
+
+        self.messages.push(ScopedMessage {
+            full_name,
+            ident,
+            message,
+        });
+    }
+
+    fn add_import(&mut self, import: &ImportedFile) {
+        self.types.add_file(import.proto_file);
+
+        let idents = ZodGenerator::new(import.proto_file, self.options).idents;
+        for (full_name, ident) in idents {
+            self.imported
+                .insert(full_name, (import.module.to_string(), ident));
+        }
+    }
+
+    fn collect_enum(&mut self, scope: &str, ident_prefix: &str, enum_def: &'a Enum) {
+        let ident = qualify_ident(ident_prefix, &enum_def.name);
+        self.idents
+            .insert(qualify(scope, &enum_def.name), ident.clone());
+        self.enums.push((ident, enum_def));
+    }
+
+    fn generate(mut self) -> Result<String, ConversionError> {
+        let mut header = String::from("// Code generated by protobuf_to_zod. DO NOT EDIT.\n\n");
+        header.push_str("import { z } from \"zod\";\n");
+        if let Some(module) = &self.options.custom_validators {
+            let validators = self.custom_validators();
+            if !validators.is_empty() {
+                let _ = writeln!(
+                    header,
+                    "import {{ {} }} from {};",
+                    validators.join(", "),
+                    quote(module)
+                );
+            }
+        }
+
+        for (ident, enum_def) in std::mem::take(&mut self.enums) {
+            self.emit_enum(&ident, enum_def)?;
+        }
+
+        let dependencies = self.dependencies();
+        self.recursive = self.recursive_messages(&dependencies);
+        for index in self.emission_order(&dependencies) {
+            self.emit_message(index)?;
+        }
+
+        // Imports are only known once every reference has been generated
+        for (module, idents) in self.used_imports.take() {
+            let idents: Vec<String> = idents.into_iter().collect();
+            let _ = writeln!(
+                header,
+                "import {{ {} }} from {};",
+                idents.join(", "),
+                quote(&module)
+            );
+        }
+
+        header.push_str(&self.out);
+        Ok(header)
+    }
+
+    /// Returns the sorted names of the custom validators used by annotations.
+    fn custom_validators(&self) -> Vec<&'a str> {
+        let annotations = self.enums.iter().filter_map(|(_, e)| e.zod.as_ref()).chain(
+            self.messages.iter().flat_map(|scoped| {
+                std::iter::once(scoped.message.zod.as_ref())
+                    .chain(all_fields(scoped.message).map(|f| f.zod.as_ref()))
+                    .flatten()
+            }),
+        );
+
+        let mut validators: Vec<&str> = annotations.filter_map(|a| a.custom.as_deref()).collect();
+        validators.sort_unstable();
+        validators.dedup();
+        validators
+    }
+
+    /// Returns the indices of the messages of this file that each message
+    /// references.
+    fn dependencies(&self) -> Vec<Vec<usize>> {
+        let by_name: HashMap<&str, usize> = self
+            .messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (m.full_name.as_str(), i))
+            .collect();
+
+        self.messages
+            .iter()
+            .map(|scoped| {
+                all_fields(scoped.message)
+                    .flat_map(|field| referenced_types(&field.typ))
+                    .filter_map(
+                        |type_name| match self.types.lookup(&scoped.full_name, type_name) {
+                            Some(ResolvedType {
+                                full_name,
+                                kind: TypeKind::Message,
+                            }) => by_name.get(full_name.as_str()).copied(),
+                            _ => None,
+                        },
+                    )
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Orders messages so that every message is emitted after the messages it
+    /// references, as far as reference cycles allow.
+    fn emission_order(&self, dependencies: &[Vec<usize>]) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.messages.len());
+        let mut visited = vec![false; self.messages.len()];
+
+        fn visit(
+            dependencies: &[Vec<usize>],
+            index: usize,
+            visited: &mut Vec<bool>,
+            order: &mut Vec<usize>,
+        ) {
+            if visited[index] {
+                return;
+            }
+            visited[index] = true;
+            for &dep in &dependencies[index] {
+                visit(dependencies, dep, visited, order);
+            }
+            order.push(index);
+        }
+
+        for index in 0..self.messages.len() {
+            visit(dependencies, index, &mut visited, &mut order);
+        }
+
+        order
+    }
+
+    /// Returns the identifiers of the messages that are part of a reference
+    /// cycle, i.e. that reference themselves directly or indirectly.
+    ///
+    /// TypeScript cannot infer the type of a schema that refers to itself, so
+    /// these messages are declared with an explicit type.
+    fn recursive_messages(&self, dependencies: &[Vec<usize>]) -> HashSet<String> {
+        let reaches = |from: usize, to: usize| {
+            let mut visited = vec![false; dependencies.len()];
+            let mut stack = vec![from];
+            while let Some(index) = stack.pop() {
+                for &dep in &dependencies[index] {
+                    if dep == to {
+                        return true;
+                    }
+                    if !visited[dep] {
+                        visited[dep] = true;
+                        stack.push(dep);
+                    }
+                }
+            }
+            false
+        };
+
+        (0..self.messages.len())
+            .filter(|&index| reaches(index, index))
+            .map(|index| self.messages[index].ident.clone())
+            .collect()
+    }
+
+    fn emit_enum(&mut self, ident: &str, enum_def: &Enum) -> Result<(), ConversionError> {
+        debug!("Generating schema for enum {}", ident);
+
+        let schema = match self.options.enum_style {
+            EnumStyle::Names => {
+                let names: Vec<String> = enum_def.values.iter().map(|v| quote(&v.name)).collect();
+                if names.is_empty() {
+                    return Err(ConversionError::GenerationError(format!(
+                        "Enum {} has no values",
+                        enum_def.name
+                    )));
+                }
+                format!("z.enum([{}])", names.join(", "))
+            }
+            EnumStyle::Numbers => {
+                let mut literals = Vec::new();
+                for value in &enum_def.values {
+                    let number = enum_value_number(value)
+                        .map_err(|e| ConversionError::GenerationError(e.message()))?;
+                    let literal = format!("z.literal({})", number);
+                    if !literals.contains(&literal) {
+                        literals.push(literal);
+                    }
+                }
+                match literals.len() {
+                    0 => {
+                        return Err(ConversionError::GenerationError(format!(
+                            "Enum {} has no values",
+                            enum_def.name
+                        )))
+                    }
+                    1 => literals.remove(0),
+                    _ => format!("z.union([{}])", literals.join(", ")),
+                }
+            }
+        };
+
+        let schema =
+            self.apply_declaration_annotation(schema, &enum_def.name, enum_def.zod.as_ref())?;
+        self.emit_declaration(ident, &schema, &enum_def.comments);
+        Ok(())
+    }
+
+    fn emit_message(&mut self, index: usize) -> Result<(), ConversionError> {
+        let scoped = &self.messages[index];
+        let (full_name, ident, message) = (
+            scoped.full_name.clone(),
+            scoped.ident.clone(),
+            scoped.message,
+        );
+        debug!("Generating schema for message {}", full_name);
+
+        let oneofs = message.oneofs.iter().filter(|o| !o.fields.is_empty());
+
+        let mut schema = String::from("z.object({\n");
+        for field in &message.fields {
+            let value = self.field_schema(&full_name, field)?;
+            schema.push_str(&self.jsdoc(&field.comments, "  "));
+            let _ = writeln!(
+                schema,
+                "  {}: {},",
+                property_key(&self.field_name(field)),
+                value
+            );
+        }
+        if self.options.oneof_style == OneofStyle::DiscriminatedUnion {
+            for oneof in oneofs.clone() {
+                let value = self.oneof_discriminated_union(&full_name, oneof)?;
+                let _ = writeln!(
+                    schema,
+                    "  {}: {},",
+                    property_key(&self.property_name(&oneof.name)),
+                    value
+                );
+            }
+        }
+        schema.push_str("})");
+
+        if self.options.oneof_style == OneofStyle::Union {
+            for oneof in oneofs {
+                let _ = write!(schema, ".and({})", self.oneof_union(&full_name, oneof)?);
+            }
+        }
+
+        let schema = self.apply_declaration_annotation(schema, &full_name, message.zod.as_ref())?;
+        if self.recursive.contains(&ident) {
+            let typ = self.message_type(&full_name, message)?;
+            let _ = write!(self.out, "\nexport type {} = {};\n", ident, typ);
+            let doc = self.jsdoc(&message.comments, "");
+            let _ = writeln!(
+                self.out,
+                "{}export const {}: z.ZodType<{}> = {};",
+                doc, ident, ident, schema
+            );
+            self.emitted.insert(ident);
+        } else {
+            self.emit_declaration(&ident, &schema, &message.comments);
+        }
+        Ok(())
+    }
+
+    /// Renders the TypeScript type of the values the schema of a message
+    /// accepts, for messages whose type cannot be inferred.
+    fn message_type(&self, scope: &str, message: &Message) -> Result<String, ConversionError> {
+        let oneofs = message.oneofs.iter().filter(|o| !o.fields.is_empty());
+
+        let mut typ = String::from("{\n");
+        for field in &message.fields {
+            let annotation = field.zod.as_ref();
+            let mut value = self.value_type(scope, &field.typ)?;
+            if field.label == FieldLabel::Repeated {
+                value = format!("Array<{}>", value);
+            }
+            if annotation.is_some_and(|a| a.nullable) {
+                value.push_str(" | null");
+            }
+            let optional = (field.label == FieldLabel::Optional && self.is_optional(scope, field))
+                || annotation.is_some_and(|a| a.optional);
+            // A default fills in absent values
+            let optional = optional && annotation.is_none_or(|a| a.default.is_none());
+            let _ = writeln!(
+                typ,
+                "  {}{}: {};",
+                property_key(&self.field_name(field)),
+                if optional { "?" } else { "" },
+                value
+            );
+        }
+
+        let mut unions = String::new();
+        for oneof in oneofs {
+            let mut variants = Vec::new();
+            match self.options.oneof_style {
+                OneofStyle::DiscriminatedUnion => {
+                    for field in &oneof.fields {
+                        variants.push(format!(
+                            "{{ oneofKind: {}; {}: {} }}",
+                            quote(&self.field_name(field)),
+                            property_key(&self.field_name(field)),
+                            self.value_type(scope, &field.typ)?
+                        ));
+                    }
+                    variants.push("{ oneofKind?: undefined }".to_string());
+                    let _ = writeln!(
+                        typ,
+                        "  {}: {};",
+                        property_key(&self.property_name(&oneof.name)),
+                        variants.join(" | ")
+                    );
+                }
+                OneofStyle::Union => {
+                    let absent = |field: &Field| {
+                        format!("{}?: undefined", property_key(&self.field_name(field)))
+                    };
+                    for field in &oneof.fields {
+                        let mut members = vec![format!(
+                            "{}: {}",
+                            property_key(&self.field_name(field)),
+                            self.value_type(scope, &field.typ)?
+                        )];
+                        members.extend(
+                            oneof
+                                .fields
+                                .iter()
+                                .filter(|f| f.name != field.name)
+                                .map(absent),
+                        );
+                        variants.push(format!("{{ {} }}", members.join("; ")));
+                    }
+                    let members: Vec<String> = oneof.fields.iter().map(absent).collect();
+                    variants.push(format!("{{ {} }}", members.join("; ")));
+                    let _ = write!(unions, " & ({})", variants.join(" | "));
+                }
+            }
+        }
+        typ.push('}');
+        typ.push_str(&unions);
+        Ok(typ)
+    }
+
+    /// Renders the TypeScript type of a single value of a field type.
+    fn value_type(&self, scope: &str, typ: &FieldType) -> Result<String, ConversionError> {
+        let int64 = match self.options.int64 {
+            Int64Mode::String => "string",
+            Int64Mode::Number => "number",
+            Int64Mode::BigInt => "bigint",
+        };
+        Ok(match typ {
+            FieldType::Double
+            | FieldType::Float
+            | FieldType::Int32
+            | FieldType::UInt32
+            | FieldType::SInt32
+            | FieldType::Fixed32
+            | FieldType::SFixed32 => "number".to_string(),
+            FieldType::Int64
+            | FieldType::UInt64
+            | FieldType::SInt64
+            | FieldType::Fixed64
+            | FieldType::SFixed64 => int64.to_string(),
+            FieldType::Bool => "boolean".to_string(),
+            FieldType::String | FieldType::Bytes => "string".to_string(),
+            FieldType::MessageOrEnum(name) | FieldType::Message(name) | FieldType::Enum(name) => {
+                self.reference_type(scope, name)
+            }
+            FieldType::Map(_, value) => {
+                format!("Record<string, {}>", self.value_type(scope, value)?)
+            }
+        })
+    }
+
+    /// Renders the TypeScript type of a referenced message or enum: the
+    /// declared type of a recursive message, or else the type inferred from
+    /// the schema.
+    fn reference_type(&self, scope: &str, type_name: &str) -> String {
+        if let Some(typ) = self
+            .well_known_type(scope, type_name)
+            .and_then(|full_name| well_known_type(&full_name, self.options))
+        {
+            return typ;
+        }
+
+        match self.types.lookup(scope, type_name) {
+            Some(resolved) => match self.idents.get(&resolved.full_name) {
+                Some(ident) if self.recursive.contains(ident) => ident.clone(),
+                Some(ident) => format!("z.infer<typeof {}>", ident),
+                None => format!("z.infer<typeof {}>", self.import_ident(&resolved.full_name)),
+            },
+            None => "unknown".to_string(),
+        }
+    }
+
+    /// Applies the `custom` and `description` options of a message or enum annotation.
+    fn apply_declaration_annotation(
+        &self,
+        schema: String,
+        name: &str,
+        annotation: Option<&ZodAnnotation>,
+    ) -> Result<String, ConversionError> {
+        match annotation {
+            Some(annotation) => {
+                annotations::check_declaration_annotation(annotation, name)?;
+                let schema = annotations::apply_refinements(
+                    schema,
+                    annotation,
+                    name,
+                    self.options.custom_validators.is_some(),
+                )?;
+                Ok(annotations::apply_metadata(schema, annotation))
+            }
+            None => Ok(schema),
+        }
+    }
+
+    fn emit_declaration(&mut self, ident: &str, schema: &str, comments: &Comments) {
+        let doc = self.jsdoc(comments, "");
+        let _ = write!(self.out, "\n{}export const {} = {};\n", doc, ident, schema);
+        if self.options.export_types {
+            let _ = writeln!(self.out, "export type {0} = z.infer<typeof {0}>;", ident);
+        }
+        self.emitted.insert(ident.to_string());
+    }
+
+    /// Renders a oneof as a union discriminated by `oneofKind`, including a
+    /// variant for the case where no member is set.
+    fn oneof_discriminated_union(
+        &self,
+        scope: &str,
+        oneof: &OneOf,
+    ) -> Result<String, ConversionError> {
+        let mut schema = String::from("z.discriminatedUnion(\"oneofKind\", [\n");
+        for field in &oneof.fields {
+            let _ = writeln!(
+                schema,
+                "    z.object({{ oneofKind: z.literal({}), {}: {} }}),",
+                quote(&self.field_name(field)),
+                property_key(&self.field_name(field)),
+                self.value_schema(scope, field)?
+            );
+        }
+        schema.push_str("    z.object({ oneofKind: z.undefined() }),\n  ])");
+        Ok(schema)
+    }
+
+    /// Renders a oneof as a union of objects that each allow a single member
+    /// to be present, plus one variant where none of them are.
+    fn oneof_union(&self, scope: &str, oneof: &OneOf) -> Result<String, ConversionError> {
+        let absent = |field: &Field| {
+            format!(
+                "{}: z.never().optional()",
+                property_key(&self.field_name(field))
+            )
+        };
+
+        let mut schema = String::from("z.union([\n");
+        for field in &oneof.fields {
+            let mut members = vec![format!(
+                "{}: {}",
+                property_key(&self.field_name(field)),
+                self.value_schema(scope, field)?
+            )];
+            members.extend(
+                oneof
+                    .fields
+                    .iter()
+                    .filter(|f| f.name != field.name)
+                    .map(absent),
+            );
+            let _ = writeln!(schema, "  z.object({{ {} }}),", members.join(", "));
+        }
+        let members: Vec<String> = oneof.fields.iter().map(absent).collect();
+        let _ = writeln!(schema, "  z.object({{ {} }}),", members.join(", "));
+        schema.push_str("])");
+        Ok(schema)
+    }
+
+    /// Renders the documentation of a definition as a JSDoc block: its leading
+    /// comment, or else its trailing comment unless that is a `@zod` annotation.
+    fn jsdoc(&self, comments: &Comments, indent: &str) -> String {
+        if !self.options.comments {
+            return String::new();
+        }
+
+        let text = match (&comments.leading, &comments.trailing) {
+            (Some(leading), _) => leading,
+            (None, Some(trailing)) if !trailing.trim_start().starts_with("@zod") => trailing,
+            _ => return String::new(),
+        };
+
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| {
+                line.strip_prefix(' ')
+                    .unwrap_or(line)
+                    .trim_end()
+                    .replace("*/", "*\\/")
+            })
+            .collect();
+        let start = lines.iter().position(|l| !l.is_empty());
+        let end = lines.iter().rposition(|l| !l.is_empty());
+        let lines = match (start, end) {
+            (Some(start), Some(end)) => &lines[start..=end],
+            _ => return String::new(),
+        };
+
+        if let [line] = lines {
+            return format!("{}/** {} */\n", indent, line);
+        }
+        let mut doc = format!("{}/**\n", indent);
+        for line in lines {
+            if line.is_empty() {
+                let _ = writeln!(doc, "{} *", indent);
+            } else {
+                let _ = writeln!(doc, "{} * {}", indent, line);
+            }
+        }
+        let _ = writeln!(doc, "{} */", indent);
+        doc
+    }
+
+    fn property_name(&self, name: &str) -> String {
+        if self.options.json_names {
+            to_lower_camel_case(name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// The JSON name of a field: its `json_name` option if present, otherwise
+    /// the lowerCamelCase form of its name.
+    fn field_name(&self, field: &Field) -> String {
+        if self.options.json_names {
+            let json_name = field.options.iter().find_map(|o| match &o.value {
+                OptionValue::String(s) if o.name.is("json_name") => Some(s.clone()),
+                _ => None,
+            });
+            if let Some(json_name) = json_name {
+                return json_name;
+            }
+        }
+        self.property_name(&field.name)
+    }
+
+    fn field_schema(&self, scope: &str, field: &Field) -> Result<String, ConversionError> {
+        let mut schema = self.value_schema(scope, field)?;
+        let annotation = field.zod.as_ref();
+
+        if field.label == FieldLabel::Repeated {
+            schema = format!("z.array({})", schema);
+            if let Some(rules) = annotation.and_then(|a| a.array.as_ref()) {
+                schema = annotations::apply_array_rules(schema, rules);
+            }
+        } else if annotation.is_some_and(|a| a.array.is_some()) {
+            return Err(ConversionError::GenerationError(format!(
+                "@zod array requires a repeated field, but '{}' is not repeated",
+                field.name
+            )));
+        }
+
+        if let Some(annotation) = annotation {
+            schema = annotations::apply_refinements(
+                schema,
+                annotation,
+                &field.name,
+                self.options.custom_validators.is_some(),
+            )?;
+        }
+
+        let optional = match field.label {
+            FieldLabel::Optional => self.is_optional(scope, field),
+            _ => false,
+        };
+        if optional || annotation.is_some_and(|a| a.optional) {
+            schema.push_str(".optional()");
+        }
+
+        Ok(match annotation {
+            Some(annotation) => annotations::apply_metadata(schema, annotation),
+            None => schema,
+        })
+    }
+
+    /// The schema of a single value of a field, with the value rules of its
+    /// annotation applied.
+    fn value_schema(&self, scope: &str, field: &Field) -> Result<String, ConversionError> {
+        let schema = self.type_schema(scope, &field.typ)?;
+        match &field.zod {
+            Some(annotation) => annotations::apply_value_rules(
+                schema,
+                self.value_kind(&field.typ),
+                annotation,
+                &field.name,
+            ),
+            None => Ok(schema),
+        }
+    }
+
+    fn value_kind(&self, typ: &FieldType) -> ValueKind {
+        match typ {
+            FieldType::Double | FieldType::Float => ValueKind::Number,
+            FieldType::Int32
+            | FieldType::UInt32
+            | FieldType::SInt32
+            | FieldType::Fixed32
+            | FieldType::SFixed32 => ValueKind::Integer,
+            FieldType::Int64
+            | FieldType::UInt64
+            | FieldType::SInt64
+            | FieldType::Fixed64
+            | FieldType::SFixed64 => match self.options.int64 {
+                Int64Mode::String => ValueKind::Other,
+                Int64Mode::Number => ValueKind::Integer,
+                Int64Mode::BigInt => ValueKind::BigInt,
+            },
+            FieldType::String | FieldType::Bytes => ValueKind::String,
+            FieldType::Bool
+            | FieldType::MessageOrEnum(_)
+            | FieldType::Message(_)
+            | FieldType::Enum(_)
+            | FieldType::Map(_, _) => ValueKind::Other,
+        }
+    }
+
+    /// proto2 `optional` fields, proto3 fields written with `optional` and
+    /// singular message fields may be absent.
+    fn is_optional(&self, scope: &str, field: &Field) -> bool {
+        if self.proto_file.syntax == Syntax::Proto2 || field.proto3_optional {
+            return true;
+        }
+        match &field.typ {
+            FieldType::MessageOrEnum(name) => match self.well_known_type(scope, name) {
+                Some(full_name) => !is_well_known_enum(&full_name),
+                None => matches!(
+                    self.types.lookup(scope, name),
+                    Some(ResolvedType {
+                        kind: TypeKind::Message,
+                        ..
+                    })
+                ),
+            },
+            FieldType::Message(_) => true,
+            _ => false,
+        }
+    }
+
+    fn type_schema(&self, scope: &str, typ: &FieldType) -> Result<String, ConversionError> {
+        let schema = match typ {
+            FieldType::Double | FieldType::Float => "z.number()".to_string(),
+            FieldType::Int32
+            | FieldType::UInt32
+            | FieldType::SInt32
+            | FieldType::Fixed32
+            | FieldType::SFixed32 => "z.number().int()".to_string(),
+            FieldType::Int64
+            | FieldType::UInt64
+            | FieldType::SInt64
+            | FieldType::Fixed64
+            | FieldType::SFixed64 => match self.options.int64 {
+                Int64Mode::String => "z.string()".to_string(),
+                Int64Mode::Number => "z.number().int()".to_string(),
+                Int64Mode::BigInt => "z.bigint()".to_string(),
+            },
+            FieldType::Bool => "z.boolean()".to_string(),
+            FieldType::String | FieldType::Bytes => "z.string()".to_string(),
+            FieldType::MessageOrEnum(name) | FieldType::Message(name) | FieldType::Enum(name) => {
+                self.reference_schema(scope, name)
+            }
+            FieldType::Map(key, value) => {
+                if !is_valid_map_key(key) {
+                    return Err(ConversionError::GenerationError(format!(
+                        "Unsupported map key type: {:?}",
+                        key
+                    )));
+                }
+                format!("z.record(z.string(), {})", self.type_schema(scope, value)?)
+            }
+        };
+
+        Ok(schema)
+    }
+
+    /// Returns the full name of a referenced well-known type. Well-known types
+    /// are recognized even when their definitions were not loaded.
+    fn well_known_type(&self, scope: &str, type_name: &str) -> Option<String> {
+        let full_name = match self.types.lookup(scope, type_name) {
+            Some(resolved) => resolved.full_name,
+            None => type_name.trim_start_matches('.').to_string(),
+        };
+        is_well_known(&full_name).then_some(full_name)
+    }
+
+    fn reference_schema(&self, scope: &str, type_name: &str) -> String {
+        if let Some(schema) = self
+            .well_known_type(scope, type_name)
+            .and_then(|full_name| well_known_schema(&full_name, self.options))
+        {
+            return schema;
+        }
+
+        match self.types.lookup(scope, type_name) {
+            Some(resolved) => match self.idents.get(&resolved.full_name) {
+                Some(ident) if self.emitted.contains(ident) => ident.clone(),
+                Some(ident) => format!("z.lazy(() => {})", ident),
+                None => self.import_ident(&resolved.full_name),
+            },
+            None => {
+                warn!(
+                    "Unresolved type '{}' referenced from '{}', using z.unknown()",
+                    type_name, scope
+                );
+                "z.unknown()".to_string()
+            }
+        }
+    }
+
+    /// Records the import of a definition from another file and returns the
+    /// identifier it is imported as. Names that clash with a declaration of
+    /// this file are imported under an alias derived from the full name.
+    fn import_ident(&self, full_name: &str) -> String {
+        let (module, ident) = &self.imported[full_name];
+        let (spec, local) = if self.idents.values().any(|local| local == ident) {
+            let alias = full_name.replace('.', "_");
+            (format!("{} as {}", ident, alias), alias)
+        } else {
+            (ident.clone(), ident.clone())
+        };
+
+        self.used_imports
+            .borrow_mut()
+            .entry(module.clone())
+            .or_default()
+            .insert(spec);
+        local
+    }
+}
+
+/// Returns the regular fields of a message followed by the members of its oneofs.
+fn all_fields(message: &Message) -> impl Iterator<Item = &Field> {
+    message
+        .fields
+        .iter()
+        .chain(message.oneofs.iter().flat_map(|o| o.fields.iter()))
+}
+
+/// Returns the message or enum names referenced by a field type.
+fn referenced_types(typ: &FieldType) -> Vec<&str> {
+    match typ {
+        FieldType::MessageOrEnum(name) | FieldType::Message(name) | FieldType::Enum(name) => {
+            vec![name.as_str()]
+        }
+        FieldType::Map(key, value) => {
+            let mut names = referenced_types(key);
+            names.extend(referenced_types(value));
+            names
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn is_valid_map_key(typ: &FieldType) -> bool {
+    !matches!(
+        typ,
+        FieldType::Double
+            | FieldType::Float
+            | FieldType::Bytes
+            | FieldType::MessageOrEnum(_)
+            | FieldType::Message(_)
+            | FieldType::Map(_, _)
+    )
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn qualify_ident(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}_{}", prefix, name)
+    }
+}
+
+/// Converts a field name into its default JSON name, e.g. `created_at` into
+/// `createdAt`.
+pub(crate) fn to_lower_camel_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut capitalize_next = false;
+    for ch in name.chars() {
+        if ch == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            result.extend(ch.to_uppercase());
+            capitalize_next = false;
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+fn property_key(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Quotes a string as a TypeScript string literal.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_proto_file;
+    use crate::parser::resolver::resolve_types;
+
+    fn generate(input: &str) -> String {
+        let proto_file = parse_proto_file(input).expect("Failed to parse proto file");
+        generate_zod(&proto_file, &GeneratorOptions::default()).expect("Failed to generate")
+    }
+
+    #[test]
+    fn test_generate_message_and_enum() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            package example;
+
+            message Person {
+                string name = 1;
+                int32 age = 2;
+                repeated string hobbies = 3;
+                Gender gender = 4;
+            }
+
+            enum Gender {
+                UNKNOWN = 0;
+                MALE = 1;
+                FEMALE = 2;
+            }
+        "#,
+        );
+
+        assert!(output.starts_with("// Code generated by protobuf_to_zod. DO NOT EDIT.\n"));
+        assert!(output.contains("import { z } from \"zod\";\n"));
+        assert!(output.contains(
+            "export const Gender = z.enum([\"UNKNOWN\", \"MALE\", \"FEMALE\"]);\n\
+             export type Gender = z.infer<typeof Gender>;\n"
+        ));
+        assert!(output.contains(
+            "export const Person = z.object({\n  \
+             name: z.string(),\n  \
+             age: z.number().int(),\n  \
+             hobbies: z.array(z.string()),\n  \
+             gender: Gender,\n\
+             });\n\
+             export type Person = z.infer<typeof Person>;\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_scalar_types() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            message Scalars {
+                double d = 1;
+                int64 big = 2;
+                bool flag = 3;
+                bytes data = 4;
+                map<string, int32> counts = 5;
+            }
+        "#,
+        );
+
+        assert!(output.contains("  d: z.number(),\n"));
+        assert!(output.contains("  big: z.string(),\n"));
+        assert!(output.contains("  flag: z.boolean(),\n"));
+        assert!(output.contains("  data: z.string(),\n"));
+        assert!(output.contains("  counts: z.record(z.string(), z.number().int()),\n"));
+    }
+
+    #[test]
+    fn test_generate_proto3_optional() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            message Order {
+                string id = 1;
+                optional string note = 2;
+                optional int32 count = 3;
+            }
+        "#,
+        );
+
+        assert!(output.contains("  id: z.string(),\n"));
+        assert!(output.contains("  note: z.string().optional(),\n"));
+        assert!(output.contains("  count: z.number().int().optional(),\n"));
+    }
+
+    #[test]
+    fn test_generate_nested_and_ordering() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            message Outer {
+                Inner inner = 1;
+                Later later = 2;
+                message Inner {
+                    Kind kind = 1;
+                    enum Kind {
+                        A = 0;
+                        B = 1;
+                    }
+                }
+            }
+            message Later {
+                string note = 1;
+            }
+        "#,
+        );
+
+        assert!(output.contains("export const Outer_Inner_Kind = z.enum([\"A\", \"B\"]);"));
+        assert!(output.contains("  kind: Outer_Inner_Kind,\n"));
+        assert!(output.contains("  inner: Outer_Inner.optional(),\n"));
+        assert!(output.contains("  later: Later.optional(),\n"));
+
+        let inner = output.find("export const Outer_Inner =").unwrap();
+        let later = output.find("export const Later =").unwrap();
+        let outer = output.find("export const Outer =").unwrap();
+        assert!(inner < later && later < outer);
+    }
+
+    #[test]
+    fn test_generate_recursive_message_uses_lazy() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            message Node {
+                repeated Node children = 1;
+            }
+        "#,
+        );
+
+        assert!(output.contains(
+            "export type Node = {\n  \
+             children: Array<Node>;\n\
+             };\n\
+             export const Node: z.ZodType<Node> = z.object({\n  \
+             children: z.array(z.lazy(() => Node)),\n\
+             });\n"
+        ));
+        assert!(!output.contains("z.infer<typeof Node>"));
+    }
+
+    #[test]
+    fn test_generate_mutually_recursive_messages() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            message Tree {
+                Node root = 1;
+                Leaf leaf = 2;
+            }
+            message Node {
+                optional string name = 1;
+                map<string, Tree> forest = 2;
+                oneof pick {
+                    Tree tree = 3;
+                    int64 id = 4;
+                }
+            }
+            message Leaf {
+                string value = 1;
+            }
+        "#,
+        );
+
+        // Both messages of the cycle are declared with their type, the
+        // messages outside of it keep the inferred type
+        assert!(output.contains(
+            "export type Node = {\n  \
+             name?: string;\n  \
+             forest: Record<string, Tree>;\n  \
+             pick: { oneofKind: \"tree\"; tree: Tree } | { oneofKind: \"id\"; id: string } \
+             | { oneofKind?: undefined };\n\
+             };\n\
+             export const Node: z.ZodType<Node> = z.object({\n"
+        ));
+        assert!(output.contains(
+            "export type Tree = {\n  \
+             root?: Node;\n  \
+             leaf?: z.infer<typeof Leaf>;\n\
+             };\n\
+             export const Tree: z.ZodType<Tree> = z.object({\n  \
+             root: Node.optional(),\n"
+        ));
+        assert!(output.contains("  forest: z.record(z.string(), z.lazy(() => Tree)),\n"));
+        assert!(output.contains("export type Leaf = z.infer<typeof Leaf>;\n"));
+    }
+
+    #[test]
+    fn test_generate_with_options() {
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            message Event {
+                int64 created_at = 1;
+                Level level = 2;
+                string user_id = 3 [json_name = "user-id"];
+            }
+            enum Level {
+                LOW = 0;
+                HIGH = 1;
+            }
+        "#,
+        )
+        .unwrap();
+
+        let options = GeneratorOptions {
+            int64: Int64Mode::BigInt,
+            enum_style: EnumStyle::Numbers,
+            oneof_style: OneofStyle::DiscriminatedUnion,
+            timestamp: TimestampMode::String,
+            json_names: true,
+            export_types: false,
+            comments: true,
+            custom_validators: None,
+        };
+        let output = generate_zod(&proto_file, &options).unwrap();
+
+        assert!(output.contains("export const Level = z.union([z.literal(0), z.literal(1)]);"));
+        assert!(output.contains("  createdAt: z.bigint(),\n"));
+        assert!(output.contains("  \"user-id\": z.string(),\n"));
+        assert!(!output.contains("export type"));
+    }
+
+    const ONEOF_PROTO: &str = r#"
+        syntax = "proto3";
+        message Result {
+            string id = 1;
+            oneof outcome {
+                string error_message = 2;
+                Payload payload = 3;
+            }
+        }
+        message Payload {
+            bytes data = 1;
+        }
+    "#;
+
+    #[test]
+    fn test_generate_oneof_discriminated_union() {
+        let output = generate(ONEOF_PROTO);
+
+        assert!(output.contains(
+            "export const Result = z.object({\n  \
+             id: z.string(),\n  \
+             outcome: z.discriminatedUnion(\"oneofKind\", [\n    \
+             z.object({ oneofKind: z.literal(\"error_message\"), error_message: z.string() }),\n    \
+             z.object({ oneofKind: z.literal(\"payload\"), payload: Payload }),\n    \
+             z.object({ oneofKind: z.undefined() }),\n  \
+             ]),\n\
+             });\n"
+        ));
+
+        let payload = output.find("export const Payload =").unwrap();
+        let result = output.find("export const Result =").unwrap();
+        assert!(payload < result);
+    }
+
+    #[test]
+    fn test_generate_oneof_union() {
+        let proto_file = parse_proto_file(ONEOF_PROTO).unwrap();
+        let options = GeneratorOptions {
+            oneof_style: OneofStyle::Union,
+            json_names: true,
+            ..GeneratorOptions::default()
+        };
+        let output = generate_zod(&proto_file, &options).unwrap();
+
+        assert!(output.contains(
+            "export const Result = z.object({\n  \
+             id: z.string(),\n\
+             }).and(z.union([\n  \
+             z.object({ errorMessage: z.string(), payload: z.never().optional() }),\n  \
+             z.object({ payload: Payload, errorMessage: z.never().optional() }),\n  \
+             z.object({ errorMessage: z.never().optional(), payload: z.never().optional() }),\n\
+             ]));\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_well_known_types() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            import "google/protobuf/timestamp.proto";
+            import "google/protobuf/wrappers.proto";
+            import "google/protobuf/struct.proto";
+
+            message Document {
+                google.protobuf.Timestamp created_at = 1;
+                .google.protobuf.Duration ttl = 2;
+                google.protobuf.StringValue title = 3;
+                google.protobuf.Struct metadata = 4;
+                repeated google.protobuf.Any attachments = 5;
+                google.protobuf.NullValue nothing = 6;
+                map<string, google.protobuf.Value> values = 7;
+                google.protobuf.FieldMask mask = 8;
+            }
+        "#,
+        );
+
+        assert!(output.contains("  created_at: z.string().datetime().optional(),\n"));
+        assert!(output.contains("  ttl: z.string().regex(/^-?\\d+(\\.\\d{1,9})?s$/).optional(),\n"));
+        assert!(output.contains("  title: z.string().nullable().optional(),\n"));
+        assert!(output.contains("  metadata: z.record(z.string(), z.unknown()).optional(),\n"));
+        assert!(output.contains(
+            "  attachments: z.array(z.object({ \"@type\": z.string() }).passthrough()),\n"
+        ));
+        assert!(output.contains("  nothing: z.null(),\n"));
+        assert!(output.contains("  values: z.record(z.string(), z.unknown()),\n"));
+        assert!(output.contains("  mask: z.string().optional(),\n"));
+    }
+
+    #[test]
+    fn test_generate_timestamp_as_date() {
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            message Event {
+                google.protobuf.Timestamp at = 1;
+            }
+        "#,
+        )
+        .unwrap();
+        let options = GeneratorOptions {
+            timestamp: TimestampMode::Date,
+            ..GeneratorOptions::default()
+        };
+        let output = generate_zod(&proto_file, &options).unwrap();
+
+        assert!(output.contains("  at: z.coerce.date().optional(),\n"));
+    }
+
+    #[test]
+    fn test_generate_zod_annotations() {
+        let proto_file =
+            parse_proto_file(include_str!("../../files/with-zod-comments.proto")).unwrap();
+        let output = generate_zod(&proto_file, &GeneratorOptions::default()).unwrap();
+
+        assert!(output.contains(
+            "export const UserType = z.enum([\"STANDARD\", \"ADMIN\", \"MODERATOR\"]).describe(\"Types of users in the system\");\n"
+        ));
+        assert!(output
+            .contains("  username: z.string().min(3).max(50).describe(\"User's username\"),\n"));
+        assert!(
+            output.contains("  email: z.string().email().describe(\"User's email address\"),\n")
+        );
+        assert!(output.contains(
+            "  age: z.number().int().min(0).max(120).describe(\"User's age in years\"),\n"
+        ));
+        assert!(output.contains(
+            "  password: z.string().regex(/^(?=.*[A-Za-z])(?=.*\\d)[A-Za-z\\d]{8,}$/).describe(\"User's password\"),\n"
+        ));
+        assert!(output
+            .contains("  tags: z.array(z.string()).min(1).max(10).describe(\"User's tags\"),\n"));
+        assert!(
+            output.contains("  type: UserType.default(\"STANDARD\").describe(\"User type\"),\n")
+        );
+        assert!(output
+            .contains("  website: z.string().url().optional().describe(\"User's website\"),\n"));
+        assert!(output.contains("}).describe(\"Represents a user in the system\");\n"));
+    }
+
+    #[test]
+    fn test_generate_custom_validators() {
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            message Account { // @zod { custom: "checkAccount" }
+                string id = 1; // @zod { uuid: true, custom: "checkId", nullable: true }
+                repeated double scores = 2; // @zod { min: 0.5, array: { length: 3 } }
+            }
+        "#,
+        )
+        .unwrap();
+        let options = GeneratorOptions {
+            custom_validators: Some("./validators".to_string()),
+            ..GeneratorOptions::default()
+        };
+        let output = generate_zod(&proto_file, &options).unwrap();
+
+        assert!(output.contains("import { checkAccount, checkId } from \"./validators\";\n"));
+        assert!(output.contains("  id: z.string().uuid().refine(checkId).nullable(),\n"));
+        assert!(output.contains("  scores: z.array(z.number().min(0.5)).length(3),\n"));
+        assert!(output.contains("}).refine(checkAccount);\n"));
+    }
+
+    #[test]
+    fn test_generate_custom_validator_without_module() {
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            message Account {
+                string id = 1; // @zod { custom: "isFoo" }
+            }
+        "#,
+        )
+        .unwrap();
+
+        match generate_zod(&proto_file, &GeneratorOptions::default()) {
+            Err(ConversionError::GenerationError(message)) => {
+                assert!(message.contains("'isFoo' of 'id'"), "{}", message);
+                assert!(message.contains("--custom-validators"), "{}", message);
+            }
+            other => panic!("expected a generation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_generate_inapplicable_annotation() {
+        for input in [
+            "message M { int32 n = 1; // @zod { email: true }\n}",
+            "message M { string s = 1; // @zod { array: { min: 1 } }\n}",
+            "message M { // @zod { min: 1 }\n string s = 1; }",
+        ] {
+            let proto_file = parse_proto_file(&format!("syntax = \"proto3\";\n{}", input)).unwrap();
+            assert!(
+                matches!(
+                    generate_zod(&proto_file, &GeneratorOptions::default()),
+                    Err(ConversionError::GenerationError(_))
+                ),
+                "expected a generation error for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_jsdoc() {
+        let input = r#"
+            syntax = "proto3";
+
+            // A person
+            // with a name
+            message Person {
+                string name = 1; // The name
+                int32 age = 2; // @zod { min: 0 }
+            }
+
+            /* Kinds of */ enum Kind {
+                A = 0;
+            }
+        "#;
+        let output = generate(input);
+
+        assert!(output.contains(
+            "\n/**\n * A person\n * with a name\n */\nexport const Person = z.object({\n"
+        ));
+        assert!(output.contains("  /** The name */\n  name: z.string(),\n"));
+        assert!(output.contains(
+            "{\n  /** The name */\n  name: z.string(),\n  age: z.number().int().min(0),\n"
+        ));
+        assert!(output.contains("\n/** Kinds of */\nexport const Kind = "));
+
+        let proto_file = parse_proto_file(input).unwrap();
+        let options = GeneratorOptions {
+            comments: false,
+            ..GeneratorOptions::default()
+        };
+        assert!(!generate_zod(&proto_file, &options).unwrap().contains("/**"));
+    }
+
+    #[test]
+    fn test_generate_invalid_map_key() {
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            message Bad {
+                map<double, string> values = 1;
+            }
+        "#,
+        )
+        .unwrap();
+
+        let result = generate_zod(&proto_file, &GeneratorOptions::default());
+        assert!(matches!(result, Err(ConversionError::GenerationError(_))));
+    }
+
+    #[test]
+    fn test_generate_resolved_types() {
+        let input = r#"
+            syntax = "proto3";
+            package app;
+            message User {
+                enum Role { ROLE_UNKNOWN = 0; }
+                Role role = 1;
+                map<string, Profile> profiles = 2;
+                google.protobuf.Timestamp created_at = 3;
+            }
+            message Profile { string bio = 1; }
+        "#;
+
+        let mut resolved = parse_proto_file(input).unwrap();
+        let timestamp =
+            parse_proto_file("syntax = \"proto3\"; package google.protobuf; message Timestamp {}")
+                .unwrap();
+        resolve_types(&mut resolved, &[&timestamp]).unwrap();
+        assert_eq!(
+            resolved.messages[0].fields[0].typ,
+            FieldType::Enum(".app.User.Role".to_string())
+        );
+
+        assert_eq!(
+            generate_zod(&resolved, &GeneratorOptions::default()).unwrap(),
+            generate(input)
+        );
+    }
+
+    #[test]
+    fn test_generate_with_imports() {
+        let common = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            package common;
+            message Id { string value = 1; }
+            message User { message Ref { Id id = 1; } }
+        "#,
+        )
+        .unwrap();
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            package app;
+            message User {
+                common.Id id = 1;
+                common.User.Ref ref = 2;
+            }
+        "#,
+        )
+        .unwrap();
+
+        let imports = [ImportedFile {
+            module: "./common.zod",
+            proto_file: &common,
+        }];
+        let output =
+            generate_zod_with_imports(&proto_file, &imports, &GeneratorOptions::default()).unwrap();
+
+        assert!(output.contains(
+            "import { z } from \"zod\";\nimport { Id, User_Ref } from \"./common.zod\";\n"
+        ));
+        assert!(output.contains("  id: Id.optional(),\n"));
+        assert!(output.contains("  ref: User_Ref.optional(),\n"));
+
+        // `User` clashes with the local declaration and is imported under an alias
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            package app;
+            message User { common.User owner = 1; }
+        "#,
+        )
+        .unwrap();
+        let output =
+            generate_zod_with_imports(&proto_file, &imports, &GeneratorOptions::default()).unwrap();
+        assert!(output.contains("import { User as common_User } from \"./common.zod\";\n"));
+        assert!(output.contains("  owner: common_User.optional(),\n"));
+    }
+
+    #[test]
+    fn test_unresolved_type_falls_back_to_unknown() {
+        let output = generate(
+            r#"
+            syntax = "proto3";
+            message Wrapper {
+                Missing value = 1;
+            }
+        "#,
+        );
+
+        assert!(output.contains("  value: z.unknown(),\n"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Zod schemas for the `google.protobuf` well-known types
+//!
+//! The well-known types have a special representation in the protobuf JSON
+//! mapping (e.g. `Timestamp` is an RFC 3339 string rather than an object with
+//! `seconds` and `nanos`), so they are mapped directly instead of being
+//! generated from their message definitions.
+
+use super::{GeneratorOptions, Int64Mode};
+use std::str::FromStr;
+
+/// How `google.protobuf.Timestamp` fields are represented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampMode {
+    /// RFC 3339 strings, validated with `z.string().datetime()`
+    String,
+    /// `Date` objects, coerced from strings with `z.coerce.date()`
+    Date,
+}
+
+impl FromStr for TimestampMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(TimestampMode::String),
+            "date" => Ok(TimestampMode::Date),
+            _ => Err(format!(
+                "Unknown timestamp mode '{}', expected 'string' or 'date'",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns true if the fully-qualified name (without a leading dot) is a
+/// well-known type with a dedicated JSON representation.
+pub(crate) fn is_well_known(full_name: &str) -> bool {
+    full_name
+        .strip_prefix("google.protobuf.")
+        .is_some_and(|name| WELL_KNOWN_TYPES.contains(&name))
+}
+
+/// Returns true if the well-known type is an enum rather than a message.
+pub(crate) fn is_well_known_enum(full_name: &str) -> bool {
+    full_name == "google.protobuf.NullValue"
+}
+
+const WELL_KNOWN_TYPES: &[&str] = &[
+    "Any",
+    "Timestamp",
+    "Duration",
+    "Empty",
+    "FieldMask",
+    "Struct",
+    "Value",
+    "ListValue",
+    "NullValue",
+    "DoubleValue",
+    "FloatValue",
+    "Int64Value",
+    "UInt64Value",
+    "Int32Value",
+    "UInt32Value",
+    "BoolValue",
+    "StringValue",
+    "BytesValue",
+];
+
+/// Returns the Zod schema for a well-known type, following the protobuf JSON
+/// mapping, or `None` if the name is not a well-known type.
+pub(crate) fn well_known_schema(full_name: &str, options: &GeneratorOptions) -> Option<String> {
+    let int64 = match options.int64 {
+        Int64Mode::String => "z.string()",
+        Int64Mode::Number => "z.number().int()",
+        Int64Mode::BigInt => "z.bigint()",
+    };
+
+    let schema = match full_name.strip_prefix("google.protobuf.")? {
+        "Any" => "z.object({ \"@type\": z.string() }).passthrough()".to_string(),
+        "Timestamp" => match options.timestamp {
+            TimestampMode::String => "z.string().datetime()".to_string(),
+            TimestampMode::Date => "z.coerce.date()".to_string(),
+        },
+        "Duration" => r"z.string().regex(/^-?\d+(\.\d{1,9})?s$/)".to_string(),
+        "Empty" => "z.object({})".to_string(),
+        "FieldMask" => "z.string()".to_string(),
+        "Struct" => "z.record(z.string(), z.unknown())".to_string(),
+        "Value" => "z.unknown()".to_string(),
+        "ListValue" => "z.array(z.unknown())".to_string(),
+        "NullValue" => "z.null()".to_string(),
+        "DoubleValue" | "FloatValue" => "z.number().nullable()".to_string(),
+        "Int32Value" | "UInt32Value" => "z.number().int().nullable()".to_string(),
+        "Int64Value" | "UInt64Value" => format!("{}.nullable()", int64),
+        "BoolValue" => "z.boolean().nullable()".to_string(),
+        "StringValue" | "BytesValue" => "z.string().nullable()".to_string(),
+        _ => return None,
+    };
+
+    Some(schema)
+}
+
+/// Returns the TypeScript type of the values [`well_known_schema`] accepts,
+/// or `None` if the name is not a well-known type.
+pub(crate) fn well_known_type(full_name: &str, options: &GeneratorOptions) -> Option<String> {
+    let int64 = match options.int64 {
+        Int64Mode::String => "string",
+        Int64Mode::Number => "number",
+        Int64Mode::BigInt => "bigint",
+    };
+
+    let typ = match full_name.strip_prefix("google.protobuf.")? {
+        "Any" => "{ \"@type\": string; [key: string]: unknown }".to_string(),
+        "Timestamp" => match options.timestamp {
+            TimestampMode::String => "string".to_string(),
+            TimestampMode::Date => "Date".to_string(),
+        },
+        "Duration" | "FieldMask" => "string".to_string(),
+        "Empty" => "{}".to_string(),
+        "Struct" => "Record<string, unknown>".to_string(),
+        "Value" => "unknown".to_string(),
+        "ListValue" => "Array<unknown>".to_string(),
+        "NullValue" => "null".to_string(),
+        "DoubleValue" | "FloatValue" | "Int32Value" | "UInt32Value" => "number | null".to_string(),
+        "Int64Value" | "UInt64Value" => format!("{} | null", int64),
+        "BoolValue" => "boolean | null".to_string(),
+        "StringValue" | "BytesValue" => "string | null".to_string(),
+        _ => return None,
+    };
+
+    Some(typ)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_well_known_schema() {
+        let options = GeneratorOptions::default();
+
+        assert_eq!(
+            well_known_schema("google.protobuf.Timestamp", &options).as_deref(),
+            Some("z.string().datetime()")
+        );
+        assert_eq!(
+            well_known_schema("google.protobuf.Int64Value", &options).as_deref(),
+            Some("z.string().nullable()")
+        );
+        assert_eq!(
+            well_known_schema("google.protobuf.Struct", &options).as_deref(),
+            Some("z.record(z.string(), z.unknown())")
+        );
+        assert_eq!(well_known_schema("google.protobuf.Other", &options), None);
+        assert_eq!(well_known_schema("example.Timestamp", &options), None);
+    }
+
+    #[test]
+    fn test_well_known_schema_with_options() {
+        let options = GeneratorOptions {
+            timestamp: TimestampMode::Date,
+            int64: Int64Mode::BigInt,
+            ..GeneratorOptions::default()
+        };
+
+        assert_eq!(
+            well_known_schema("google.protobuf.Timestamp", &options).as_deref(),
+            Some("z.coerce.date()")
+        );
+        assert_eq!(
+            well_known_schema("google.protobuf.UInt64Value", &options).as_deref(),
+            Some("z.bigint().nullable()")
+        );
+    }
+
+    #[test]
+    fn test_is_well_known() {
+        assert!(is_well_known("google.protobuf.Any"));
+        assert!(is_well_known("google.protobuf.NullValue"));
+        assert!(!is_well_known("google.protobuf.FileDescriptorProto"));
+        assert!(is_well_known_enum("google.protobuf.NullValue"));
+        assert!(!is_well_known_enum("google.protobuf.Value"));
+    }
+}
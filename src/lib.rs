@@ -7,7 +7,12 @@
 use std::error::Error;
 use std::fmt;
 
+pub mod descriptor;
+pub mod diagnostics;
+pub mod generator;
+pub mod loader;
 pub mod parser;
+pub mod plugin;
 pub mod visitor;
 
 /// Errors that can occur during the conversion process
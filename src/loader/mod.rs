@@ -0,0 +1,645 @@
+//! Loading of `.proto` files together with their imports
+//!
+//! Imports are looked up in a list of include paths, like `protoc -I`. Every
+//! file of the transitive import graph is parsed once, and the result is a
+//! [`CompilationUnit`] that holds the files in dependency order.
+//!
+//! The well-known types (`google/protobuf/*.proto`) are built in, so they do
+//! not need to be on an include path.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use log::{debug, warn};
+
+use crate::parser::ast::{ImportKind, ProtoFile};
+use crate::parser::error::Location;
+use crate::parser::resolver::resolve_types;
+use crate::parser::validator::validate_file;
+use crate::parser::{parse_proto_file_recovering, ParseError};
+use crate::ConversionError;
+
+/// Errors that can occur while loading files and their imports
+#[derive(Debug)]
+pub enum LoadError {
+    /// An import was not found in any of the include paths
+    NotFound {
+        name: String,
+        imported_from: String,
+        location: Location,
+    },
+    /// A file could not be read
+    Io { path: PathBuf, error: io::Error },
+    /// A type reference of a file could not be resolved
+    Resolve {
+        file: String,
+        /// The text of the file, for rendering the error
+        source: String,
+        error: ParseError,
+    },
+    /// A file could not be parsed or is invalid; holds every syntax error of
+    /// the file, or if there are none every error found by the validator
+    Syntax {
+        file: String,
+        /// The text of the file, for rendering the errors
+        source: String,
+        errors: Vec<ParseError>,
+    },
+    /// Files import each other; the first and last names are the same file
+    ImportCycle(Vec<String>),
+    /// A compiled descriptor set could not be decoded or converted
+    InvalidDescriptor { path: PathBuf, message: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotFound {
+                name,
+                imported_from,
+                location,
+            } => write!(
+                f,
+                "Import '{}' not found in include paths (imported from {} at {})",
+                name, imported_from, location
+            ),
+            LoadError::Io { path, error } => {
+                write!(f, "Failed to read '{}': {}", path.display(), error)
+            }
+            LoadError::Resolve { file, error, .. } => write!(f, "{}: {}", file, error),
+            LoadError::Syntax { file, errors, .. } => {
+                let errors: Vec<String> = errors
+                    .iter()
+                    .map(|error| format!("{}: {}", file, error))
+                    .collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            LoadError::ImportCycle(names) => write!(f, "Import cycle: {}", names.join(" -> ")),
+            LoadError::InvalidDescriptor { path, message } => {
+                write!(
+                    f,
+                    "Invalid descriptor set '{}': {}",
+                    path.display(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl LoadError {
+    /// Returns the stable code of the error's kind, like
+    /// [`ParseError::code`]. Resolve and syntax errors have the code of the
+    /// parse error, or of the first one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoadError::NotFound { .. } => "E0101",
+            LoadError::Io { .. } => "E0102",
+            LoadError::ImportCycle(_) => "E0103",
+            LoadError::InvalidDescriptor { .. } => "E0104",
+            LoadError::Resolve { error, .. } => error.code(),
+            LoadError::Syntax { errors, .. } => errors.first().map_or("E0005", ParseError::code),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadError> for ConversionError {
+    fn from(err: LoadError) -> Self {
+        match err {
+            LoadError::Io { error, .. } => ConversionError::FileReadError(error),
+            other => ConversionError::ParseError(other.to_string()),
+        }
+    }
+}
+
+/// A parsed `.proto` file
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// The name used to import the file, relative to its include path,
+    /// e.g. `google/protobuf/timestamp.proto`
+    pub name: String,
+    /// The path the file was read from; `None` for built-in and in-memory files
+    pub path: Option<PathBuf>,
+    pub source: String,
+    pub proto: ProtoFile,
+}
+
+/// A set of files together with everything they import
+///
+/// Files are stored in dependency order: every file comes after the files it
+/// imports.
+#[derive(Debug, Clone, Default)]
+pub struct CompilationUnit {
+    files: Vec<SourceFile>,
+    roots: Vec<String>,
+}
+
+impl CompilationUnit {
+    /// Creates a unit from files that were parsed or converted elsewhere,
+    /// e.g. from the descriptors sent to a protoc plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The files, imports before the files that import them
+    /// * `roots` - The names of the requested files
+    pub fn from_files(files: Vec<SourceFile>, roots: Vec<String>) -> Self {
+        CompilationUnit { files, roots }
+    }
+
+    /// Returns all files, imports before the files that import them.
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Returns the files that were requested, rather than loaded as imports.
+    pub fn roots(&self) -> impl Iterator<Item = &SourceFile> {
+        self.roots.iter().filter_map(|name| self.get(name))
+    }
+
+    /// Returns the file with the given import name.
+    pub fn get(&self, name: &str) -> Option<&SourceFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Returns the files whose definitions are visible from the named file:
+    /// its direct imports and, transitively, their public imports.
+    pub fn visible_files(&self, name: &str) -> Vec<&SourceFile> {
+        let mut visible = Vec::new();
+        let mut seen = HashSet::new();
+        if let Some(file) = self.get(name) {
+            for import in &file.proto.imports {
+                self.collect_public(&import.path, &mut seen, &mut visible);
+            }
+        }
+        visible
+    }
+
+    fn collect_public<'a>(
+        &'a self,
+        name: &str,
+        seen: &mut HashSet<String>,
+        visible: &mut Vec<&'a SourceFile>,
+    ) {
+        // Missing weak imports are skipped
+        let Some(file) = self.get(name) else { return };
+        if !seen.insert(name.to_string()) {
+            return;
+        }
+        visible.push(file);
+        for import in &file.proto.imports {
+            if import.kind == ImportKind::Public {
+                self.collect_public(&import.path, seen, visible);
+            }
+        }
+    }
+
+    /// Resolves the type references of every file against its visible files.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), LoadError>` - A `Resolve` error wrapping `UnknownType` for
+    ///   the first reference that cannot be resolved.
+    pub fn resolve(&mut self) -> Result<(), LoadError> {
+        for index in 0..self.files.len() {
+            let name = self.files[index].name.clone();
+            let visible: Vec<String> = self
+                .visible_files(&name)
+                .into_iter()
+                .map(|file| file.name.clone())
+                .collect();
+
+            // Imports always come first, so they are all in `done`
+            let (done, rest) = self.files.split_at_mut(index);
+            let imports: Vec<&ProtoFile> = done
+                .iter()
+                .filter(|file| visible.contains(&file.name))
+                .map(|file| &file.proto)
+                .collect();
+            let file = &mut rest[0];
+            resolve_types(&mut file.proto, &imports).map_err(|error| LoadError::Resolve {
+                file: name,
+                source: file.source.clone(),
+                error,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Loads files and their imports from a list of include paths
+#[derive(Debug, Clone)]
+pub struct Loader {
+    include_paths: Vec<PathBuf>,
+}
+
+impl Loader {
+    /// Creates a loader that searches the include paths in order. Without any
+    /// include paths, imports are relative to the current directory.
+    pub fn new<P: Into<PathBuf>>(include_paths: impl IntoIterator<Item = P>) -> Self {
+        let mut include_paths: Vec<PathBuf> = include_paths.into_iter().map(Into::into).collect();
+        if include_paths.is_empty() {
+            include_paths.push(PathBuf::from("."));
+        }
+        Loader { include_paths }
+    }
+
+    /// Loads files from disk together with their transitive imports.
+    ///
+    /// A file is named after its path relative to the first include path that
+    /// contains it, so that it is not loaded twice when it is also imported.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths of the files to load.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<CompilationUnit, LoadError>` - The loaded files, or the first
+    ///   error encountered.
+    pub fn load<P: AsRef<Path>>(&self, paths: &[P]) -> Result<CompilationUnit, LoadError> {
+        let mut unit = CompilationUnit::default();
+        for path in paths {
+            let path = path.as_ref();
+            let name = self.import_name(path);
+            if unit.get(&name).is_none() {
+                let source = read(path)?;
+                self.add_file(&mut unit, &mut Vec::new(), name.clone(), Some(path), source)?;
+            }
+            if !unit.roots.contains(&name) {
+                unit.roots.push(name);
+            }
+        }
+        Ok(unit)
+    }
+
+    /// Loads a file that is not on disk, e.g. one read from stdin, together
+    /// with its imports.
+    pub fn load_source(&self, name: &str, source: String) -> Result<CompilationUnit, LoadError> {
+        let mut unit = CompilationUnit::default();
+        self.add_file(&mut unit, &mut Vec::new(), name.to_string(), None, source)?;
+        unit.roots.push(name.to_string());
+        Ok(unit)
+    }
+
+    /// Parses a file and loads its imports depth-first, so that the file is
+    /// added after everything it imports. `stack` holds the names of the files
+    /// currently being loaded, which is how cycles are detected.
+    fn add_file(
+        &self,
+        unit: &mut CompilationUnit,
+        stack: &mut Vec<String>,
+        name: String,
+        path: Option<&Path>,
+        source: String,
+    ) -> Result<(), LoadError> {
+        debug!("Loading {}", name);
+
+        let (proto, mut errors) = parse_proto_file_recovering(&source);
+        if errors.is_empty() {
+            errors = validate_file(&proto);
+        }
+        if !errors.is_empty() {
+            return Err(LoadError::Syntax {
+                file: name,
+                source,
+                errors,
+            });
+        }
+
+        stack.push(name.clone());
+        for import in &proto.imports {
+            if let Some(start) = stack.iter().position(|n| *n == import.path) {
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(import.path.clone());
+                return Err(LoadError::ImportCycle(cycle));
+            }
+            if unit.get(&import.path).is_some() {
+                continue;
+            }
+
+            match self.find_import(&import.path)? {
+                Some((path, source)) => {
+                    self.add_file(unit, stack, import.path.clone(), path.as_deref(), source)?
+                }
+                None if import.kind == ImportKind::Weak => {
+                    warn!(
+                        "Weak import '{}' of {} not found, skipping it",
+                        import.path, name
+                    );
+                }
+                None => {
+                    return Err(LoadError::NotFound {
+                        name: import.path.clone(),
+                        imported_from: name,
+                        location: import.span.location(),
+                    })
+                }
+            }
+        }
+        stack.pop();
+
+        unit.files.push(SourceFile {
+            name,
+            path: path.map(Path::to_path_buf),
+            source,
+            proto,
+        });
+        Ok(())
+    }
+
+    /// Looks an import up in the include paths, then in the built-in files.
+    fn find_import(&self, name: &str) -> Result<Option<(Option<PathBuf>, String)>, LoadError> {
+        for include_path in &self.include_paths {
+            let path = include_path.join(name);
+            if path.is_file() {
+                return Ok(Some((Some(path.clone()), read(&path)?)));
+            }
+        }
+        Ok(built_in_file(name).map(|source| (None, source.to_string())))
+    }
+
+    /// Returns the import name of a file: its path relative to the first
+    /// include path that contains it, else relative to the current directory,
+    /// else the path itself.
+    ///
+    /// Paths are compared as written first and then canonicalized, so that
+    /// e.g. `./protos/a.proto` and an absolute path are found under `-I protos`.
+    /// Only a file outside every include path and the current directory keeps
+    /// an absolute name or one starting with `..`.
+    fn import_name(&self, path: &Path) -> String {
+        let below = |path: &Path, dir: &Path| {
+            path.strip_prefix(dir)
+                .ok()
+                .filter(|relative| {
+                    relative
+                        .components()
+                        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+                })
+                .map(Path::to_path_buf)
+        };
+        let canonical = |path: &Path| fs::canonicalize(path).ok();
+
+        let relative = self
+            .include_paths
+            .iter()
+            .find_map(|include_path| below(path, include_path))
+            .or_else(|| {
+                let path = canonical(path)?;
+                self.include_paths
+                    .iter()
+                    .filter_map(|include_path| canonical(include_path))
+                    .chain(std::env::current_dir().ok().and_then(|dir| canonical(&dir)))
+                    .find_map(|dir| below(&path, &dir))
+            })
+            .unwrap_or_else(|| path.to_path_buf());
+
+        // The root of an absolute path becomes the leading separator
+        let name = relative
+            .components()
+            .filter(|c| !matches!(c, Component::RootDir | Component::CurDir))
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if relative.has_root() {
+            format!("/{}", name)
+        } else {
+            name
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, LoadError> {
+    fs::read_to_string(path).map_err(|error| LoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Returns the source of a built-in `google/protobuf` file.
+fn built_in_file(name: &str) -> Option<&'static str> {
+    let source = match name {
+        "google/protobuf/any.proto" => include_str!("google/protobuf/any.proto"),
+        "google/protobuf/api.proto" => include_str!("google/protobuf/api.proto"),
+        "google/protobuf/descriptor.proto" => include_str!("google/protobuf/descriptor.proto"),
+        "google/protobuf/duration.proto" => include_str!("google/protobuf/duration.proto"),
+        "google/protobuf/empty.proto" => include_str!("google/protobuf/empty.proto"),
+        "google/protobuf/field_mask.proto" => include_str!("google/protobuf/field_mask.proto"),
+        "google/protobuf/source_context.proto" => {
+            include_str!("google/protobuf/source_context.proto")
+        }
+        "google/protobuf/struct.proto" => include_str!("google/protobuf/struct.proto"),
+        "google/protobuf/timestamp.proto" => include_str!("google/protobuf/timestamp.proto"),
+        "google/protobuf/type.proto" => include_str!("google/protobuf/type.proto"),
+        "google/protobuf/wrappers.proto" => include_str!("google/protobuf/wrappers.proto"),
+        _ => return None,
+    };
+    Some(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::FieldType;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("files/imports")
+    }
+
+    #[test]
+    fn test_load_imports() {
+        let loader = Loader::new([fixtures()]);
+        let mut unit = loader.load(&[fixtures().join("main.proto")]).unwrap();
+
+        let names: Vec<&str> = unit.files().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "common/status.proto",
+                "common/types.proto",
+                "google/protobuf/timestamp.proto",
+                "main.proto"
+            ]
+        );
+        let roots: Vec<&str> = unit.roots().map(|f| f.name.as_str()).collect();
+        assert_eq!(roots, vec!["main.proto"]);
+        assert!(unit
+            .get("google/protobuf/timestamp.proto")
+            .unwrap()
+            .path
+            .is_none());
+
+        // The public import of common/types.proto makes common/status.proto visible
+        let visible: Vec<&str> = unit
+            .visible_files("main.proto")
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(
+            visible,
+            vec![
+                "common/types.proto",
+                "common/status.proto",
+                "google/protobuf/timestamp.proto"
+            ]
+        );
+
+        unit.resolve().unwrap();
+        let user = &unit.get("main.proto").unwrap().proto.messages[0];
+        assert_eq!(
+            user.fields[1].typ,
+            FieldType::Enum(".common.Status".to_string())
+        );
+        assert_eq!(
+            user.fields[2].typ,
+            FieldType::Message(".google.protobuf.Timestamp".to_string())
+        );
+    }
+
+    #[test]
+    fn test_import_names() {
+        // Tests run in the package directory
+        let common = fixtures().join("common");
+        let loader = Loader::new([common.clone()]);
+        for path in [
+            common.join("status.proto"),
+            PathBuf::from("files/imports/common/status.proto"),
+            PathBuf::from("./files/imports/../imports/common/status.proto"),
+        ] {
+            assert_eq!(loader.import_name(&path), "status.proto", "{:?}", path);
+        }
+
+        // Outside every include path, names are relative to the current directory
+        let loader = Loader::new([common.join("missing")]);
+        assert_eq!(
+            loader.import_name(&common.join("status.proto")),
+            "files/imports/common/status.proto"
+        );
+    }
+
+    #[test]
+    fn test_load_source_without_public_import() {
+        let loader = Loader::new([fixtures()]);
+        let source = r#"
+            syntax = "proto3";
+            import "main.proto";
+            message Wrapper { app.User user = 1; }
+        "#;
+        let mut unit = loader.load_source("<stdin>", source.to_string()).unwrap();
+        unit.resolve().unwrap();
+
+        // main.proto imports common/types.proto, but not publicly
+        let source = r#"
+            syntax = "proto3";
+            import "main.proto";
+            message Wrapper { common.Id id = 1; }
+        "#;
+        let mut unit = loader.load_source("<stdin>", source.to_string()).unwrap();
+        assert!(unit.get("common/types.proto").is_some());
+        assert!(matches!(
+            unit.resolve(),
+            Err(LoadError::Resolve {
+                error: ParseError::UnknownType(..),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_load_built_in_files() {
+        let loader = Loader::new(Vec::<PathBuf>::new());
+        let source = r#"
+            syntax = "proto3";
+            import "google/protobuf/any.proto";
+            import "google/protobuf/api.proto";
+            import "google/protobuf/descriptor.proto";
+            import "google/protobuf/duration.proto";
+            import "google/protobuf/empty.proto";
+            import "google/protobuf/field_mask.proto";
+            import "google/protobuf/source_context.proto";
+            import "google/protobuf/struct.proto";
+            import "google/protobuf/timestamp.proto";
+            import "google/protobuf/type.proto";
+            import "google/protobuf/wrappers.proto";
+            message Schema { google.protobuf.FileDescriptorProto file = 1; }
+        "#;
+        let mut unit = loader.load_source("<stdin>", source.to_string()).unwrap();
+        unit.resolve().unwrap();
+        assert_eq!(unit.files().len(), 12);
+
+        let descriptor = &unit.get("google/protobuf/descriptor.proto").unwrap().proto;
+        let options: Vec<String> = descriptor
+            .options
+            .iter()
+            .map(|option| option.name.to_string())
+            .collect();
+        assert!(options.contains(&"java_outer_classname".to_string()));
+        let schema = &unit.get("<stdin>").unwrap().proto.messages[0];
+        assert_eq!(
+            schema.fields[0].typ,
+            FieldType::Message(".google.protobuf.FileDescriptorProto".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let loader = Loader::new([fixtures()]);
+
+        match loader.load(&[fixtures().join("cycle_a.proto")]) {
+            Err(LoadError::ImportCycle(names)) => assert_eq!(
+                names,
+                vec!["cycle_a.proto", "cycle_b.proto", "cycle_a.proto"]
+            ),
+            other => panic!("Expected an import cycle, got {:?}", other),
+        }
+
+        match loader.load(&[fixtures().join("missing_import.proto")]) {
+            Err(LoadError::NotFound {
+                name,
+                imported_from,
+                location,
+            }) => {
+                assert_eq!(name, "does/not/exist.proto");
+                assert_eq!(imported_from, "missing_import.proto");
+                assert_eq!(location, Location::new(3, 1));
+            }
+            other => panic!("Expected a missing import, got {:?}", other),
+        }
+
+        assert!(matches!(
+            loader.load(&[fixtures().join("nonexistent.proto")]),
+            Err(LoadError::Io { .. })
+        ));
+
+        let source = "syntax = \"proto3\";\nmessage A { string a = ; }\nmessage B { int32 = 1; }\n";
+        match loader.load_source("broken.proto", source.to_string()) {
+            Err(error @ LoadError::Syntax { .. }) => {
+                let lines: Vec<String> = error.to_string().lines().map(String::from).collect();
+                assert_eq!(lines.len(), 2);
+                assert!(lines[0].starts_with("broken.proto: "));
+                assert!(lines[1].contains("line 3"));
+            }
+            other => panic!("Expected syntax errors, got {:?}", other),
+        }
+
+        let source = "syntax = \"proto3\";\nmessage A { string a = 1; string b = 1; }\n";
+        match loader.load_source("invalid.proto", source.to_string()) {
+            Err(LoadError::Syntax { errors, .. }) => {
+                assert!(matches!(errors[..], [ParseError::DuplicateDefinition(..)]));
+            }
+            other => panic!("Expected validation errors, got {:?}", other),
+        }
+    }
+}
@@ -1,36 +1,282 @@
-use log::{error, info};
-use protobuf_to_zod::parser::parse_proto_file;
+use clap::Parser;
+use log::info;
+use prost::Message;
+use protobuf_to_zod::descriptor::{load_descriptor_set, to_file_descriptor_set};
+use protobuf_to_zod::diagnostics::{
+    render_load_error, to_json, to_sarif, use_color, Diagnostic, ErrorFormat,
+};
+use protobuf_to_zod::generator::{
+    generate_zod_for_unit, output_path, EnumStyle, GeneratorOptions, Int64Mode, Layout, OneofStyle,
+    TimestampMode,
+};
+use protobuf_to_zod::loader::{CompilationUnit, LoadError, Loader};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// Name of the file read from stdin, used for its messages and output file
+const STDIN_NAME: &str = "stdin.proto";
+
+/// Generate Zod schemas from Protocol Buffer files
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Directory to search for imports, like protoc's -I; may be repeated
+    #[arg(short = 'I', long = "proto-path", value_name = "DIR")]
+    include_paths: Vec<PathBuf>,
+
+    /// The .proto files to generate, or glob patterns matching them; `-`
+    /// reads a single file from stdin. With --descriptor-set-in, the names of
+    /// files in the set.
+    #[arg(required_unless_present = "descriptor_set_in", value_name = "FILES")]
+    files: Vec<String>,
+
+    /// Read compiled files from a binary FileDescriptorSet, e.g. one written
+    /// by `buf build -o`, instead of parsing .proto files. Without FILES,
+    /// every file in the set is generated.
+    #[arg(long, value_name = "FILE", conflicts_with = "include_paths")]
+    descriptor_set_in: Option<PathBuf>,
+
+    /// Write the files as a binary FileDescriptorSet, like protoc's
+    /// --descriptor_set_out. Without --out, no schemas are generated.
+    #[arg(long, value_name = "FILE")]
+    descriptor_set_out: Option<PathBuf>,
+
+    /// Include the imported files in --descriptor-set-out
+    #[arg(long, requires = "descriptor_set_out")]
+    include_imports: bool,
+
+    /// Include source locations and comments in --descriptor-set-out
+    #[arg(long, requires = "descriptor_set_out")]
+    include_source_info: bool,
+
+    /// Directory to write the generated `.zod.ts` files to. Without it, the
+    /// schemas of a single file are printed to stdout.
+    #[arg(short, long, value_name = "DIR")]
+    out: Option<PathBuf>,
+
+    /// Arrangement of the generated files: `tree` mirrors the import paths,
+    /// `flat` writes every file to the output directory itself
+    #[arg(long, default_value = "tree", value_name = "LAYOUT")]
+    layout: Layout,
+
+    /// Representation of 64-bit integers: `string`, `number` or `bigint`
+    #[arg(long, default_value = "string", value_name = "MODE")]
+    int64: Int64Mode,
+
+    /// Representation of enums: `names` or `numbers`
+    #[arg(long, default_value = "names", value_name = "STYLE")]
+    enum_style: EnumStyle,
+
+    /// Representation of oneofs: `discriminated-union` or `union`
+    #[arg(long, default_value = "discriminated-union", value_name = "STYLE")]
+    oneof_style: OneofStyle,
+
+    /// Representation of google.protobuf.Timestamp: `string` or `date`
+    #[arg(long, default_value = "string", value_name = "MODE")]
+    timestamp: TimestampMode,
+
+    /// Use lowerCamelCase JSON names for properties
+    #[arg(long)]
+    json_names: bool,
+
+    /// Do not emit `export type` declarations next to the schemas
+    #[arg(long)]
+    no_export_types: bool,
+
+    /// Do not emit comments as JSDoc
+    #[arg(long)]
+    no_comments: bool,
+
+    /// Module to import the `@zod { custom: ... }` validators from
+    #[arg(long, value_name = "MODULE")]
+    custom_validators: Option<String>,
+
+    /// Format of the errors written to stderr: `human`, or `json` or `sarif`
+    /// for tools. The machine-readable formats are also written when there
+    /// are no errors.
+    #[arg(long, default_value = "human", value_name = "FORMAT")]
+    error_format: ErrorFormat,
+}
+
+impl Cli {
+    fn generator_options(&self) -> GeneratorOptions {
+        GeneratorOptions {
+            int64: self.int64,
+            enum_style: self.enum_style,
+            oneof_style: self.oneof_style,
+            timestamp: self.timestamp,
+            json_names: self.json_names,
+            export_types: !self.no_export_types,
+            comments: !self.no_comments,
+            custom_validators: self.custom_validators.clone(),
+        }
+    }
+}
+
+fn main() -> ExitCode {
     env_logger::init();
 
-    let mut proto_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    proto_path.push("files");
-    proto_path.push("logdservice.proto");
+    let cli = Cli::parse();
+    let error_format = cli.error_format;
+    let result = run(cli);
+
+    let load_error = result
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<LoadError>());
+    match error_format {
+        ErrorFormat::Human => {}
+        ErrorFormat::Json | ErrorFormat::Sarif => {
+            let diagnostics = load_error
+                .map(Diagnostic::from_load_error)
+                .unwrap_or_default();
+            if error_format == ErrorFormat::Json {
+                eprintln!("{}", to_json(&diagnostics));
+            } else {
+                eprintln!("{}", to_sarif(&diagnostics));
+            }
+        }
+    }
+
+    match &result {
+        Ok(()) => ExitCode::SUCCESS,
+        // Errors that are not about a file are always reported as text
+        Err(e) => {
+            match load_error {
+                Some(_) if error_format != ErrorFormat::Human => {}
+                Some(error) => eprint!("{}", render_load_error(error, use_color())),
+                None => eprintln!("error: {}", e),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let unit = match &cli.descriptor_set_in {
+        Some(path) => select_roots(load_descriptor_set(path)?, &cli.files)?,
+        None => load_sources(&cli)?,
+    };
 
-    info!("Reading Protobuf file from: {}", proto_path.display());
+    if let Some(path) = &cli.descriptor_set_out {
+        info!("Writing {}", path.display());
+        let set = to_file_descriptor_set(&unit, cli.include_imports, cli.include_source_info)?;
+        fs::write(path, set.encode_to_vec())
+            .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+        if cli.out.is_none() {
+            return Ok(());
+        }
+    }
 
-    let proto_content = fs::read_to_string(&proto_path).map_err(|e| {
-        error!("Failed to read the proto file: {}", e);
-        format!(
-            "Failed to read the proto file '{}': {}",
-            proto_path.display(),
-            e
-        )
-    })?;
+    let options = cli.generator_options();
+    let roots: Vec<&str> = unit.roots().map(|file| file.name.as_str()).collect();
 
-    info!("Parsing Protobuf file content");
+    let Some(out_dir) = &cli.out else {
+        let name = match roots[..] {
+            [name] => name,
+            // Only a descriptor set can hold no files
+            [] => return Err("The descriptor set contains no files".into()),
+            _ => return Err("--out is required when generating more than one file".into()),
+        };
+        print!(
+            "{}",
+            generate_zod_for_unit(&unit, name, cli.layout, &options)?
+        );
+        return Ok(());
+    };
 
-    let proto_file = parse_proto_file(&proto_content).map_err(|e| {
-        error!("Failed to parse Protobuf file: {}", e);
-        format!("Failed to parse Protobuf file: {}", e)
-    })?;
+    let mut written = HashSet::new();
+    for name in roots {
+        // Names outside every include path and the current directory are
+        // absolute or start with `..`, and would escape the output directory
+        let relative = output_path(name, cli.layout);
+        if !Path::new(&relative)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(format!(
+                "The schemas of '{}' would be written outside '{}'; pass a file below an \
+                 include path (-I) or the current directory",
+                name,
+                out_dir.display()
+            )
+            .into());
+        }
+        let path = out_dir.join(relative);
+        if !written.insert(path.clone()) {
+            return Err(format!(
+                "More than one file would be written to '{}'",
+                path.display()
+            )
+            .into());
+        }
+        info!("Writing {}", path.display());
 
-    info!("Successfully parsed Protobuf file");
-    info!("Parsed content: {:#?}", proto_file);
+        let schema = generate_zod_for_unit(&unit, name, cli.layout, &options)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, schema)
+            .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+    }
 
     Ok(())
 }
+
+/// Loads and resolves the .proto files named on the command line, or the
+/// file read from stdin.
+fn load_sources(cli: &Cli) -> Result<CompilationUnit, Box<dyn Error>> {
+    let loader = Loader::new(cli.include_paths.clone());
+
+    let mut unit = if cli.files == ["-"] {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        loader.load_source(STDIN_NAME, source)?
+    } else {
+        if cli.files.iter().any(|file| file == "-") {
+            return Err("`-` cannot be combined with other files".into());
+        }
+        loader.load(&expand_globs(&cli.files)?)?
+    };
+    unit.resolve()?;
+    Ok(unit)
+}
+
+/// Restricts the files generated from a descriptor set to the named ones.
+fn select_roots(
+    unit: CompilationUnit,
+    names: &[String],
+) -> Result<CompilationUnit, Box<dyn Error>> {
+    if names.is_empty() {
+        return Ok(unit);
+    }
+    if let Some(missing) = names.iter().find(|name| unit.get(name).is_none()) {
+        return Err(format!("'{}' is not in the descriptor set", missing).into());
+    }
+    Ok(CompilationUnit::from_files(
+        unit.files().to_vec(),
+        names.to_vec(),
+    ))
+}
+
+/// Expands the arguments that contain glob patterns into the matching paths.
+fn expand_globs(patterns: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(pattern));
+            continue;
+        }
+
+        let matches = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            return Err(format!("No files match '{}'", pattern).into());
+        }
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
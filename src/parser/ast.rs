@@ -1,11 +1,25 @@
+use super::error::Span;
+use std::fmt;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Syntax {
     Proto2,
     Proto3,
 }
 
+/// The largest field number allowed by protobuf, written as `max` in ranges
+pub const MAX_FIELD_NUMBER: i32 = 536_870_911;
+
+/// An entry of a `reserved` statement, e.g. `2`, `9 to 11` or `"foo"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reserved {
+    pub kind: ReservedKind,
+    pub span: Span,
+}
+
+/// What a `reserved` entry reserves; both ends of a range are inclusive
 #[derive(Debug, Clone, PartialEq)]
-pub enum Reserved {
+pub enum ReservedKind {
     Number(i32),
     Range(i32, i32),
     FieldName(String),
@@ -41,10 +55,28 @@ pub enum OptionValue {
     Message(Vec<(String, OptionValue)>), // For nested message options
 }
 
+/// A single component of an option name.
+///
+/// `(validate.rules).string.min_len` consists of the extension part
+/// `validate.rules` followed by the field parts `string` and `min_len`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionNamePart {
+    /// A plain field name, e.g. `deprecated`
+    Field(String),
+    /// A parenthesized extension name, e.g. `(google.api.http)`
+    Extension(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionName {
+    pub parts: Vec<OptionNamePart>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProtoOption {
-    pub name: String,
+    pub name: OptionName,
     pub value: OptionValue,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -56,12 +88,14 @@ pub struct ProtoFile {
     pub messages: Vec<Message>,
     pub enums: Vec<Enum>,
     pub services: Vec<Service>,
+    pub extends: Vec<Extend>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Import {
     pub path: String,
     pub kind: ImportKind,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -80,6 +114,31 @@ pub struct Message {
     pub nested_enums: Vec<Enum>,
     pub options: Vec<ProtoOption>,
     pub reserved: Vec<Reserved>,
+    pub extensions: Vec<ExtensionRange>,
+    pub extends: Vec<Extend>,
+    pub zod: Option<ZodAnnotation>,
+    pub comments: Comments,
+    pub span: Span,
+}
+
+/// A range of field numbers reserved for extensions, e.g. `extensions 100 to max;`
+///
+/// Both ends are inclusive; `max` is stored as [`MAX_FIELD_NUMBER`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionRange {
+    pub start: i32,
+    pub end: i32,
+    pub options: Vec<ProtoOption>,
+    pub span: Span,
+}
+
+/// An `extend` block adding fields to another message,
+/// e.g. `extend google.protobuf.FieldOptions { ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extend {
+    pub extendee: String,
+    pub fields: Vec<Field>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -94,9 +153,18 @@ pub enum NumberValue {
 pub struct Field {
     pub name: String,
     pub number: NumberValue,
+    /// The span of the number literal, where errors about the number are
+    /// reported
+    pub number_span: Span,
     pub label: FieldLabel,
+    /// Whether the field of a proto3 file is written with `optional`, which
+    /// gives it explicit presence like a proto2 optional field
+    pub proto3_optional: bool,
     pub typ: FieldType,
     pub options: Vec<ProtoOption>,
+    pub zod: Option<ZodAnnotation>,
+    pub comments: Comments,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -123,7 +191,12 @@ pub enum FieldType {
     Bool,
     String,
     Bytes,
+    /// A message or enum name as written in the source, before resolution
     MessageOrEnum(String),
+    /// A resolved message reference, by fully-qualified name with a leading dot
+    Message(String),
+    /// A resolved enum reference, by fully-qualified name with a leading dot
+    Enum(String),
     Map(Box<FieldType>, Box<FieldType>),
 }
 
@@ -131,6 +204,8 @@ pub enum FieldType {
 pub struct OneOf {
     pub name: String,
     pub fields: Vec<Field>,
+    pub options: Vec<ProtoOption>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -138,6 +213,9 @@ pub struct Enum {
     pub name: String,
     pub values: Vec<EnumValue>,
     pub options: Vec<EnumValueOption>,
+    pub zod: Option<ZodAnnotation>,
+    pub comments: Comments,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -145,36 +223,21 @@ pub struct EnumValue {
     pub name: String,
     pub number: NumberValue,
     pub options: Vec<EnumValueOption>,
+    pub comments: Comments,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct EnumValueOption {
-    pub name: String,
-    pub value: EnumValueOptionValue,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum EnumValueOptionValue {
-    String(String),
-    DecimalInt(i64),
-    Octal(i64),
-    Hex(i64),
-    Float(f64),
-    Bool(bool),
-    Identifier(String), // For referencing other enum values or custom identifiers
-}
-
-impl EnumValueOption {
-    pub fn new(name: String, value: EnumValueOptionValue) -> Self {
-        EnumValueOption { name, value }
-    }
-}
+/// An option of an enum or enum value, which takes the same values as any
+/// other option
+pub type EnumValueOption = ProtoOption;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Service {
     pub name: String,
     pub methods: Vec<Method>,
     pub options: Vec<ProtoOption>,
+    pub comments: Comments,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -185,6 +248,58 @@ pub struct Method {
     pub client_streaming: bool,
     pub server_streaming: bool,
     pub options: Vec<ProtoOption>,
+    pub comments: Comments,
+    pub span: Span,
+}
+
+/// Comments attached to a definition, like protoc's `SourceCodeInfo`
+///
+/// The comment markers are stripped, but the text is otherwise kept as
+/// written. Consecutive line comments form a single comment with one line
+/// (terminated by `\n`) per source line.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Comments {
+    /// The comment directly above the definition
+    pub leading: Option<String>,
+    /// The comment following the definition on the same line; for messages,
+    /// enums and services, the comment after the opening brace
+    pub trailing: Option<String>,
+    /// Comments above the definition that are separated from it by a blank line
+    pub leading_detached: Vec<String>,
+}
+
+/// Validation rules from a trailing `// @zod { ... }` comment
+///
+/// See `docs/zod_commets_format.md` for the annotation format. Boolean rules
+/// that were not given (or given as `false`) are `false`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ZodAnnotation {
+    /// Minimum value for numbers, minimum length for strings
+    pub min: Option<f64>,
+    /// Maximum value for numbers, maximum length for strings
+    pub max: Option<f64>,
+    pub regex: Option<String>,
+    pub email: bool,
+    pub url: bool,
+    pub uuid: bool,
+    pub positive: bool,
+    pub negative: bool,
+    pub int: bool,
+    pub description: Option<String>,
+    pub default: Option<OptionValue>,
+    pub optional: bool,
+    pub nullable: bool,
+    pub array: Option<ZodArrayRules>,
+    /// Name of a custom validation function
+    pub custom: Option<String>,
+}
+
+/// Length rules for repeated fields, e.g. `array: { min: 1, max: 10 }`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ZodArrayRules {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub length: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -212,10 +327,17 @@ impl ProtoFile {
             messages: Vec::new(),
             enums: Vec::new(),
             services: Vec::new(),
+            extends: Vec::new(),
         }
     }
 }
 
+impl Default for ProtoFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Message {
     pub fn new(name: String) -> Self {
         Message {
@@ -226,6 +348,32 @@ impl Message {
             nested_enums: Vec::new(),
             options: Vec::new(),
             reserved: Vec::new(),
+            extensions: Vec::new(),
+            extends: Vec::new(),
+            zod: None,
+            comments: Comments::default(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl Extend {
+    pub fn new(extendee: String) -> Self {
+        Extend {
+            extendee,
+            fields: Vec::new(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl OneOf {
+    pub fn new(name: String) -> Self {
+        OneOf {
+            name,
+            fields: Vec::new(),
+            options: Vec::new(),
+            span: Span::default(),
         }
     }
 }
@@ -236,6 +384,9 @@ impl Enum {
             name,
             values: Vec::new(),
             options: Vec::new(),
+            zod: None,
+            comments: Comments::default(),
+            span: Span::default(),
         }
     }
 }
@@ -246,27 +397,190 @@ impl Service {
             name,
             methods: Vec::new(),
             options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl Reserved {
+    pub fn new(kind: ReservedKind) -> Self {
+        Reserved {
+            kind,
+            span: Span::default(),
         }
     }
 }
 
 impl ProtoOption {
-    pub fn new(name: String, value: OptionValue) -> Self {
-        ProtoOption { name, value }
+    pub fn new(name: OptionName, value: OptionValue) -> Self {
+        ProtoOption {
+            name,
+            value,
+            span: Span::default(),
+        }
     }
 }
 
-// TODO: Add support for 'extend' keyword
-// TODO: Add support for 'Any' type
-// TODO: Add support for 'Timestamp' type
-// TODO: Add support for 'Duration' type
-// TODO: Add support for 'Empty' type
-// TODO: Add support for Well-Known Types (e.g., DoubleValue, FloatValue, Int64Value, etc.)
+impl OptionName {
+    pub fn new(parts: Vec<OptionNamePart>) -> Self {
+        OptionName { parts }
+    }
+
+    /// Creates a name consisting of a single plain field, e.g. `deprecated`
+    pub fn simple(name: &str) -> Self {
+        OptionName {
+            parts: vec![OptionNamePart::Field(name.to_string())],
+        }
+    }
+
+    /// Returns true if this is the plain (non-extension) option `name`
+    pub fn is(&self, name: &str) -> bool {
+        matches!(&self.parts[..], [OptionNamePart::Field(field)] if field == name)
+    }
+
+    /// Returns the extension name if the option starts with one,
+    /// e.g. `google.api.http` for `(google.api.http).get`
+    pub fn extension(&self) -> Option<&str> {
+        match self.parts.first() {
+            Some(OptionNamePart::Extension(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the field path that follows the first component,
+    /// e.g. `["string", "min_len"]` for `(validate.rules).string.min_len`
+    pub fn path(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .skip(1)
+            .map(|part| match part {
+                OptionNamePart::Field(name) | OptionNamePart::Extension(name) => name.as_str(),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for OptionName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            match part {
+                OptionNamePart::Field(name) => write!(f, "{}", name)?,
+                OptionNamePart::Extension(name) => write!(f, "({})", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the number as a literal of its kind, e.g. `12`, `0x1f` or `2.5`.
+impl fmt::Display for NumberValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberValue::DecimalInt(n) => write!(f, "{}", n),
+            NumberValue::Octal(n) if *n < 0 => write!(f, "-0{:o}", n.unsigned_abs()),
+            NumberValue::Octal(n) => write!(f, "0{:o}", n),
+            NumberValue::Hex(n) if *n < 0 => write!(f, "-0x{:x}", n.unsigned_abs()),
+            NumberValue::Hex(n) => write!(f, "0x{:x}", n),
+            NumberValue::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+/// Writes the value in the text format of option values, e.g. `"a"`, `0x1f`
+/// or `{ min: 1, max: 10 }`. Strings are written as they were in the source,
+/// since they keep their escape sequences.
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionValue::Identifier(s) => write!(f, "{}", s),
+            OptionValue::String(s) => write!(f, "\"{}\"", s),
+            OptionValue::DecimalInt(n) => write!(f, "{}", n),
+            OptionValue::Octal(n) if *n < 0 => write!(f, "-0{:o}", n.unsigned_abs()),
+            OptionValue::Octal(n) => write!(f, "0{:o}", n),
+            OptionValue::Hex(n) if *n < 0 => write!(f, "-0x{:x}", n.unsigned_abs()),
+            OptionValue::Hex(n) => write!(f, "0x{:x}", n),
+            OptionValue::Float(x) if x.is_nan() => write!(f, "nan"),
+            OptionValue::Float(x) if x.is_infinite() => {
+                write!(f, "{}inf", if *x < 0.0 { "-" } else { "" })
+            }
+            OptionValue::Float(x) => write!(f, "{:?}", x),
+            OptionValue::Bool(b) => write!(f, "{}", b),
+            OptionValue::Enum(_, value) => write!(f, "{}", value),
+            OptionValue::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            OptionValue::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    write!(f, "{} {}: {}", if i > 0 { "," } else { "" }, key, value)?;
+                }
+                write!(f, " }}")
+            }
+            OptionValue::Message(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    write!(f, "{} {}: {}", if i > 0 { "," } else { "" }, name, value)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_option_value_display() {
+        let value = OptionValue::Message(vec![
+            ("min".to_string(), OptionValue::DecimalInt(-1)),
+            ("mask".to_string(), OptionValue::Hex(0x1f)),
+            ("ratio".to_string(), OptionValue::Float(1.0)),
+            (
+                "tags".to_string(),
+                OptionValue::List(vec![
+                    OptionValue::String("a".to_string()),
+                    OptionValue::Identifier("B".to_string()),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{ min: -1, mask: 0x1f, ratio: 1.0, tags: ["a", B] }"#
+        );
+        assert_eq!(OptionValue::Octal(8).to_string(), "010");
+        assert_eq!(OptionValue::Float(f64::NEG_INFINITY).to_string(), "-inf");
+        assert_eq!(OptionValue::Message(Vec::new()).to_string(), "{ }");
+    }
+
+    #[test]
+    fn test_option_name() {
+        let name = OptionName::new(vec![
+            OptionNamePart::Extension("validate.rules".to_string()),
+            OptionNamePart::Field("string".to_string()),
+            OptionNamePart::Field("min_len".to_string()),
+        ]);
+
+        assert_eq!(name.to_string(), "(validate.rules).string.min_len");
+        assert_eq!(name.extension(), Some("validate.rules"));
+        assert_eq!(name.path(), vec!["string", "min_len"]);
+        assert!(!name.is("validate.rules"));
+        assert!(OptionName::simple("deprecated").is("deprecated"));
+        assert_eq!(OptionName::simple("deprecated").extension(), None);
+    }
+
     #[test]
     fn test_create_proto_file() {
         let mut proto_file = ProtoFile::new();
@@ -276,6 +590,7 @@ mod tests {
         let import = Import {
             path: "google/protobuf/any.proto".to_string(),
             kind: ImportKind::Default,
+            span: Span::default(),
         };
         proto_file.imports.push(import);
 
@@ -283,17 +598,27 @@ mod tests {
         message.fields.push(Field {
             name: "name".to_string(),
             number: NumberValue::DecimalInt(1),
+            number_span: Span::default(),
             label: FieldLabel::Optional,
+            proto3_optional: false,
             typ: FieldType::MessageOrEnum(String::new()),
             options: Vec::new(),
+            zod: None,
+            comments: Comments::default(),
+            span: Span::default(),
         });
 
         message.fields.push(Field {
             name: "age".to_string(),
             number: NumberValue::DecimalInt(2),
+            number_span: Span::default(),
             label: FieldLabel::Optional,
+            proto3_optional: false,
             typ: FieldType::String,
             options: Vec::new(),
+            zod: None,
+            comments: Comments::default(),
+            span: Span::default(),
         });
         proto_file.messages.push(message);
 
@@ -302,16 +627,22 @@ mod tests {
             name: "UNKNOWN".to_string(),
             number: NumberValue::DecimalInt(0),
             options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::default(),
         });
         enum_def.values.push(EnumValue {
             name: "MALE".to_string(),
             number: NumberValue::DecimalInt(1),
             options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::default(),
         });
         enum_def.values.push(EnumValue {
             name: "FEMALE".to_string(),
             number: NumberValue::DecimalInt(2),
             options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::default(),
         });
         proto_file.enums.push(enum_def);
 
@@ -11,6 +11,26 @@ impl Location {
     pub fn new(line: usize, column: usize) -> Self {
         Location { line, column }
     }
+
+    /// Computes the location of a byte offset into `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source text
+    /// * `offset` - A byte offset into `source`, at most its length
+    ///
+    /// # Returns
+    ///
+    /// * `Location` - The line and byte column of the offset
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let before = &source[..offset];
+        match before.rfind('\n') {
+            Some(last_newline) => {
+                Location::new(before.matches('\n').count() + 1, offset - last_newline)
+            }
+            None => Location::new(1, offset + 1),
+        }
+    }
 }
 
 impl fmt::Display for Location {
@@ -19,6 +39,63 @@ impl fmt::Display for Location {
     }
 }
 
+/// A position in the source text
+///
+/// Lines and columns are 1-based; columns count bytes, like `offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    /// Byte offset from the start of the input
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Position {
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+impl From<Position> for Location {
+    fn from(position: Position) -> Self {
+        Location::new(position.line, position.column)
+    }
+}
+
+/// A range of source text, from `start` up to but not including `end`
+///
+/// Nodes built by hand rather than parsed have an empty default span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    /// Returns the span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+
+    /// Returns the location of the start of the span.
+    pub fn location(&self) -> Location {
+        self.start.into()
+    }
+
+    /// Returns the source text covered by the span.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start.offset..self.end.offset]
+    }
+}
+
 /// Represents all possible errors that can occur during parsing
 #[derive(Debug)]
 pub enum ParseError {
@@ -48,6 +125,10 @@ pub enum ParseError {
     InvalidRange(i32, i32, Location),
     /// Invalid field number
     InvalidFieldNumber(String, Location),
+    /// Invalid enum value number
+    InvalidEnumValue(String, Location),
+    /// Field number that its message reserves
+    ReservedFieldNumber(String, Location),
     /// Tokenization error
     NomError(String, Location),
     /// Generic error for other cases
@@ -70,6 +151,8 @@ impl ParseError {
             ParseError::MissingIdentifier(_, loc) => *loc,
             ParseError::InvalidRange(_, _, loc) => *loc,
             ParseError::InvalidFieldNumber(_, loc) => *loc,
+            ParseError::InvalidEnumValue(_, loc) => *loc,
+            ParseError::ReservedFieldNumber(_, loc) => *loc,
             ParseError::NomError(_, loc) => *loc,
             ParseError::Other(_, loc) => *loc,
         }
@@ -82,11 +165,9 @@ impl ParseError {
             ParseError::UnexpectedCharacter(char, _) => format!("Unexpected token: {}", char),
             ParseError::ExpectedToken(token, _) => format!("Expected token: {}", token),
             ParseError::InvalidSyntax(msg, _) => format!("Invalid syntax: {}", msg),
-            ParseError::UnterminatedStringLiteral(loc) => {
-                format!("Unterminated string literal at {}", loc)
-            }
+            ParseError::UnterminatedStringLiteral(_) => "Unterminated string literal".to_string(),
             ParseError::UnexpectedEndOfInput(_) => "Unexpected end of input".to_string(),
-            ParseError::IncompleteParser(_, loc) => format!("Incomplete parser: {}", loc),
+            ParseError::IncompleteParser(msg, _) => format!("Incomplete parser: {}", msg),
             ParseError::DuplicateDefinition(name, _) => format!("Duplicate definition: {}", name),
             ParseError::UnknownType(type_name, _) => format!("Unknown type: {}", type_name),
             ParseError::MissingIdentifier(msg, _) => format!("Missing identifier: {}", msg),
@@ -94,10 +175,69 @@ impl ParseError {
                 format!("Invalid range: {} to {}", start, end)
             }
             ParseError::InvalidFieldNumber(msg, _) => format!("Invalid field number: {}", msg),
+            ParseError::InvalidEnumValue(msg, _) => format!("Invalid enum value: {}", msg),
+            ParseError::ReservedFieldNumber(msg, _) => format!("Reserved field number: {}", msg),
             ParseError::NomError(msg, _) => format!("Nom error: {}", msg),
             ParseError::Other(msg, _) => format!("Other error: {}", msg),
         }
     }
+
+    /// Returns the stable code of the error's kind, e.g. `E0003` for an
+    /// unexpected token.
+    ///
+    /// Codes identify kinds of errors in machine-readable diagnostics, so a
+    /// code is never changed or reused for another kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::LexerError(..) => "E0001",
+            ParseError::UnexpectedCharacter(..) => "E0002",
+            ParseError::UnexpectedToken(..) => "E0003",
+            ParseError::ExpectedToken(..) => "E0004",
+            ParseError::InvalidSyntax(..) => "E0005",
+            ParseError::UnexpectedEndOfInput(..) => "E0006",
+            ParseError::UnterminatedStringLiteral(..) => "E0007",
+            ParseError::IncompleteParser(..) => "E0008",
+            ParseError::DuplicateDefinition(..) => "E0009",
+            ParseError::UnknownType(..) => "E0010",
+            ParseError::MissingIdentifier(..) => "E0011",
+            ParseError::InvalidRange(..) => "E0012",
+            ParseError::InvalidFieldNumber(..) => "E0013",
+            ParseError::NomError(..) => "E0014",
+            ParseError::Other(..) => "E0015",
+            ParseError::InvalidEnumValue(..) => "E0016",
+            ParseError::ReservedFieldNumber(..) => "E0017",
+        }
+    }
+
+    /// Returns a hint on how to fix the error, if there is a general one.
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            ParseError::UnexpectedEndOfInput(_) => {
+                Some("a closing `}` or `;` may be missing before this point")
+            }
+            ParseError::UnterminatedStringLiteral(_) => {
+                Some("add the closing quote; strings cannot span lines")
+            }
+            ParseError::DuplicateDefinition(..) => Some("rename or remove one of the definitions"),
+            ParseError::UnknownType(..) => {
+                Some("check the spelling of the type and that the file defining it is imported")
+            }
+            ParseError::InvalidRange(..) => {
+                Some("the start of a range must not be greater than its end")
+            }
+            ParseError::InvalidFieldNumber(..) => {
+                Some("field numbers must be between 1 and 536870911, excluding 19000 to 19999")
+            }
+            ParseError::InvalidEnumValue(..) => {
+                Some("enum values must be integers between -2147483648 and 2147483647")
+            }
+            ParseError::ReservedFieldNumber(..) => {
+                Some("use a number that is not reserved, or remove it from `reserved`")
+            }
+            ParseError::MissingIdentifier(..) => Some("add a name before this point"),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -144,6 +284,12 @@ impl fmt::Display for ParseError {
             ParseError::InvalidFieldNumber(msg, loc) => {
                 write!(f, "Invalid field number: {} at {}", msg, loc)
             }
+            ParseError::InvalidEnumValue(msg, loc) => {
+                write!(f, "Invalid enum value: {} at {}", msg, loc)
+            }
+            ParseError::ReservedFieldNumber(msg, loc) => {
+                write!(f, "Reserved field number: {} at {}", msg, loc)
+            }
             ParseError::NomError(msg, loc) => {
                 write!(f, "Nom error: {} at {}", msg, loc)
             }
@@ -154,16 +300,34 @@ impl fmt::Display for ParseError {
     }
 }
 
-impl From<nom::Err<nom::error::Error<&str>>> for ParseError {
-    fn from(error: nom::Err<nom::error::Error<&str>>) -> Self {
+impl ParseError {
+    /// Converts the error of a nom parser run on `input` into a ParseError.
+    ///
+    /// nom errors carry the remaining input rather than a position, so the
+    /// location is computed from how much of `input` was consumed. Incomplete
+    /// input is reported at the end of `input`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input the nom parser was run on
+    /// * `error` - The error returned by the parser
+    ///
+    /// # Returns
+    ///
+    /// * `ParseError` - An IncompleteParser or NomError at the failing position
+    pub fn from_nom(input: &str, error: nom::Err<nom::error::Error<&str>>) -> Self {
         match error {
-            nom::Err::Incomplete(_) => {
-                ParseError::IncompleteParser("Incomplete input".to_string(), Location::new(0, 0))
-            }
-            nom::Err::Error(e) | nom::Err::Failure(e) => ParseError::NomError(
-                format!("Failed to parse token: {:?}", e.code),
-                Location::new(0, 0),
+            nom::Err::Incomplete(_) => ParseError::IncompleteParser(
+                "Incomplete input".to_string(),
+                Location::from_offset(input, input.len()),
             ),
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                let offset = input.len().saturating_sub(e.input.len());
+                ParseError::NomError(
+                    format!("Failed to parse token: {:?}", e.code),
+                    Location::from_offset(input, offset),
+                )
+            }
         }
     }
 }
@@ -265,6 +429,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_error_codes() {
+        let location = Location::new(1, 1);
+        assert_eq!(
+            ParseError::LexerError(String::new(), location).code(),
+            "E0001"
+        );
+        assert_eq!(
+            ParseError::UnknownType(String::new(), location).code(),
+            "E0010"
+        );
+        assert_eq!(ParseError::Other(String::new(), location).code(), "E0015");
+        assert_eq!(
+            ParseError::ReservedFieldNumber(String::new(), location).code(),
+            "E0017"
+        );
+    }
+
+    #[test]
+    fn test_location_from_offset() {
+        let source = "ab\ncd\n";
+        assert_eq!(Location::from_offset(source, 0), Location::new(1, 1));
+        assert_eq!(Location::from_offset(source, 2), Location::new(1, 3));
+        assert_eq!(Location::from_offset(source, 3), Location::new(2, 1));
+        assert_eq!(Location::from_offset(source, 6), Location::new(3, 1));
+    }
+
+    #[test]
+    fn test_from_nom() {
+        let input = "syntax\n  123";
+        let error = nom::Err::Error(nom::error::Error::new(
+            &input[9..],
+            nom::error::ErrorKind::Tag,
+        ));
+        let error = ParseError::from_nom(input, error);
+        assert_eq!(error.message(), "Nom error: Failed to parse token: Tag");
+        assert_eq!(error.location(), Location::new(2, 3));
+
+        let incomplete = nom::Err::Incomplete(nom::Needed::Unknown);
+        assert_eq!(
+            ParseError::from_nom(input, incomplete).location(),
+            Location::new(2, 6)
+        );
+    }
+
     #[test]
     fn test_unexpected_end_of_input() {
         let error = ParseError::UnexpectedEndOfInput(Location {
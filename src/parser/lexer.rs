@@ -1,4 +1,8 @@
-use super::{error::Location, ParseError};
+use super::{
+    error::{Location, Position, Span},
+    ParseError,
+};
+use std::fmt;
 
 use nom::{
     branch::alt,
@@ -32,6 +36,7 @@ pub enum Token<'a> {
     Stream,
     Public,
     Extensions,
+    Extend,
     Optional,
 
     // Identifiers and Literals
@@ -61,6 +66,7 @@ pub enum Token<'a> {
     CloseBracket,
     LessThan,
     GreaterThan,
+    Minus,
 
     // Special
     Comment(&'a str),
@@ -71,9 +77,9 @@ pub enum Token<'a> {
     Required,
 }
 
-impl<'a> ToString for Token<'a> {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
             Token::Syntax => "syntax".to_string(),
             Token::Proto2 => "proto2".to_string(),
             Token::Proto3 => "proto3".to_string(),
@@ -94,6 +100,7 @@ impl<'a> ToString for Token<'a> {
             Token::Weak => "weak".to_string(),
             Token::Public => "public".to_string(),
             Token::Extensions => "extensions".to_string(),
+            Token::Extend => "extend".to_string(),
             Token::Identifier(s) => s.to_string(),
             Token::StringLiteral(s) => format!("\"{}\"", s),
             Token::BoolLiteral(b) => format!("{}", b),
@@ -116,11 +123,13 @@ impl<'a> ToString for Token<'a> {
             Token::CloseBracket => "]".to_string(),
             Token::LessThan => "<".to_string(),
             Token::GreaterThan => ">".to_string(),
+            Token::Minus => "-".to_string(),
             Token::Required => "required".to_string(),
             Token::Optional => "optional".to_string(),
             Token::Comment(s) => format!("Comment({})", s),
             Token::Unknown(s) => s.to_string(),
-        }
+        };
+        write!(f, "{}", s)
     }
 }
 
@@ -128,6 +137,8 @@ impl<'a> ToString for Token<'a> {
 pub struct TokenWithLocation<'a> {
     pub token: Token<'a>,
     pub location: Location,
+    /// The source range of the token; `location` is its start
+    pub span: Span,
 }
 
 impl<'a> TokenWithLocation<'a> {
@@ -138,10 +149,7 @@ impl<'a> TokenWithLocation<'a> {
                 self.location,
             ))
         } else {
-            Ok(TokenWithLocation {
-                token: self.token.clone(),
-                location: self.location,
-            })
+            Ok(self.clone())
         }
     }
 }
@@ -153,7 +161,7 @@ impl<'a> PartialEq for TokenWithLocation<'a> {
 }
 
 #[allow(dead_code)]
-fn parse_syntax_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_syntax_keywords(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("syntax"), |_| Token::Syntax),
         map(tag("proto2"), |_| Token::Proto2),
@@ -162,7 +170,7 @@ fn parse_syntax_keywords(input: &str) -> IResult<&str, Token> {
 }
 
 #[allow(dead_code)]
-fn parse_import_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_import_keywords(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("import"), |_| Token::Import),
         map(tag("weak"), |_| Token::Weak),
@@ -171,7 +179,7 @@ fn parse_import_keywords(input: &str) -> IResult<&str, Token> {
 }
 
 #[allow(dead_code)]
-fn parse_message_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_message_keywords(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("message"), |_| Token::Message),
         map(tag("enum"), |_| Token::Enum),
@@ -181,7 +189,7 @@ fn parse_message_keywords(input: &str) -> IResult<&str, Token> {
 }
 
 #[allow(dead_code)]
-fn parse_field_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_field_keywords(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("repeated"), |_| Token::Repeated),
         map(tag("optional"), |_| Token::Optional),
@@ -191,7 +199,7 @@ fn parse_field_keywords(input: &str) -> IResult<&str, Token> {
 }
 
 #[allow(dead_code)]
-fn parse_service_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_service_keywords(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("service"), |_| Token::Service),
         map(tag("rpc"), |_| Token::Rpc),
@@ -200,12 +208,12 @@ fn parse_service_keywords(input: &str) -> IResult<&str, Token> {
 }
 
 #[allow(dead_code)]
-fn parse_option_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_option_keywords(input: &str) -> IResult<&str, Token<'_>> {
     map(tag("option"), |_| Token::Option)(input)
 }
 
 #[allow(dead_code)]
-fn parse_misc_keywords(input: &str) -> IResult<&str, Token> {
+fn parse_misc_keywords(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("package"), |_| Token::Package),
         map(tag("reserved"), |_| Token::Reserved),
@@ -215,7 +223,7 @@ fn parse_misc_keywords(input: &str) -> IResult<&str, Token> {
 }
 
 #[allow(dead_code)]
-fn parse_identifier(input: &str) -> IResult<&str, Token> {
+fn parse_identifier(input: &str) -> IResult<&str, Token<'_>> {
     map(
         recognize(pair(
             alt((alpha1, tag("_"))),
@@ -241,6 +249,7 @@ fn parse_identifier(input: &str) -> IResult<&str, Token> {
             "weak" => Token::Weak,
             "public" => Token::Public,
             "extensions" => Token::Extensions,
+            "extend" => Token::Extend,
             "required" => Token::Required,
             "optional" => Token::Optional,
             "string" => Token::StringType,
@@ -249,7 +258,7 @@ fn parse_identifier(input: &str) -> IResult<&str, Token> {
     )(input)
 }
 
-pub fn tokenize(input: &str) -> Result<Vec<TokenWithLocation>, ParseError> {
+pub fn tokenize(input: &str) -> Result<Vec<TokenWithLocation<'_>>, ParseError> {
     let mut tokens = Vec::new();
     let mut pos = 0;
     let mut line = 1;
@@ -257,113 +266,76 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithLocation>, ParseError> {
 
     while pos < input.len() {
         let current_char = input[pos..].chars().next().unwrap();
-        let start_column = column;
-
-        // // Try to parse fully qualified identifier first
-        // if let Ok((remaining, token)) = parse_fully_qualified_identifier(&input[pos..]) {
-        //     let token_len = input[pos..].len() - remaining.len();
-        //     tokens.push(TokenWithLocation {
-        //         token,
-        //         location: Location {
-        //             line,
-        //             column: start_column,
-        //         },
-        //     });
-        //     pos += token_len;
-        //     column += token_len;
-        //     continue;
-        // }
-
-        // If not a fully qualified identifier, proceed with other token types
-        match current_char {
+        let start = Position::new(pos, line, column);
+
+        let token = match current_char {
             ' ' | '\t' | '\r' => {
                 pos += 1;
                 column += 1;
+                None
             }
             '\n' => {
                 pos += 1;
                 line += 1;
                 column = 1;
+                None
             }
             '/' => {
                 if input[pos..].starts_with("//") {
+                    // The newline is left for the whitespace branch to count
                     let end = pos + input[pos..].find('\n').unwrap_or(input.len() - pos);
                     let comment = &input[pos..end];
-                    tokens.push(TokenWithLocation {
-                        token: Token::Comment(comment),
-                        location: Location {
-                            line,
-                            column: start_column,
-                        },
-                    });
                     pos = end;
-                    line += 1;
-                    column = 1;
+                    column += comment.len();
+                    Some(Token::Comment(comment))
                 } else if input[pos..].starts_with("/*") {
                     let end = pos + input[pos..].find("*/").map_or(input.len() - pos, |i| i + 2);
                     let comment = &input[pos..end];
-                    let newlines = comment.chars().filter(|&c| c == '\n').count();
-                    tokens.push(TokenWithLocation {
-                        token: Token::Comment(comment),
-                        location: Location {
-                            line,
-                            column: start_column,
-                        },
-                    });
                     pos = end;
-                    line += newlines;
-                    if newlines > 0 {
-                        column = comment.chars().rev().take_while(|&c| c != '\n').count() + 1;
-                    } else {
-                        column += comment.len();
+                    match comment.rfind('\n') {
+                        Some(last_newline) => {
+                            line += comment.matches('\n').count();
+                            column = comment.len() - last_newline;
+                        }
+                        None => column += comment.len(),
                     }
+                    Some(Token::Comment(comment))
                 } else {
-                    return Err(ParseError::UnexpectedCharacter(
-                        '/',
-                        Location { line, column },
-                    ));
+                    // Separates the host from the type name in `Any` type URLs
+                    pos += 1;
+                    column += 1;
+                    Some(Token::Slash)
                 }
             }
             '"' => {
-                let (token, len) = tokenize_string_literal(&input[pos..])?;
-                tokens.push(TokenWithLocation {
-                    token,
-                    location: Location {
-                        line,
-                        column: start_column,
-                    },
-                });
+                let (token, len) = tokenize_string_literal(&input[pos..], start.into())?;
                 pos += len;
                 column += len;
+                Some(token)
+            }
+            '-' if !input[pos + 1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                // A sign that isn't part of a number literal, e.g. in `-inf`
+                pos += 1;
+                column += 1;
+                Some(Token::Minus)
             }
             '0'..='9' | '-' | '+' => {
                 let (token, len) = tokenize_number(&input[pos..]);
-                tokens.push(TokenWithLocation {
-                    token,
-                    location: Location {
-                        line,
-                        column: start_column,
-                    },
-                });
                 pos += len;
                 column += len;
+                Some(token)
             }
             'a'..='z' | 'A'..='Z' | '_' => {
                 let (token, len) = tokenize_identifier(&input[pos..]);
-                tokens.push(TokenWithLocation {
-                    token,
-                    location: Location {
-                        line,
-                        column: start_column,
-                    },
-                });
                 pos += len;
                 column += len;
+                Some(token)
             }
-            '=' | ';' | '{' | '}' | '(' | ')' | '[' | ']' | '<' | '>' | ',' | '.' => {
+            '=' | ';' | ':' | '{' | '}' | '(' | ')' | '[' | ']' | '<' | '>' | ',' | '.' => {
                 let token = match current_char {
                     '=' => Token::Equals,
                     ';' => Token::Semicolon,
+                    ':' => Token::Colon,
                     '{' => Token::OpenBrace,
                     '}' => Token::CloseBrace,
                     '(' => Token::OpenParen,
@@ -376,15 +348,9 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithLocation>, ParseError> {
                     '.' => Token::Dot,
                     _ => unreachable!(),
                 };
-                tokens.push(TokenWithLocation {
-                    token,
-                    location: Location {
-                        line,
-                        column: start_column,
-                    },
-                });
                 pos += 1;
                 column += 1;
+                Some(token)
             }
             c => {
                 return Err(ParseError::UnexpectedCharacter(
@@ -392,31 +358,49 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithLocation>, ParseError> {
                     Location { line, column },
                 ));
             }
+        };
+
+        if let Some(token) = token {
+            tokens.push(TokenWithLocation {
+                token,
+                location: start.into(),
+                span: Span::new(start, Position::new(pos, line, column)),
+            });
         }
     }
 
     Ok(tokens)
 }
 
-fn tokenize_string_literal(input: &str) -> Result<(Token, usize), ParseError> {
-    let mut end = 1;
+/// Tokenizes the string literal at the start of `input`, which begins with
+/// its opening quote.
+///
+/// A string that reaches a newline or the end of the input before its closing
+/// quote is unterminated; the error is reported at the opening quote, given by
+/// `location`.
+fn tokenize_string_literal(
+    input: &str,
+    location: Location,
+) -> Result<(Token<'_>, usize), ParseError> {
     let mut escaped = false;
 
-    for ch in input[1..].chars() {
-        if escaped {
+    for (i, ch) in input[1..].char_indices() {
+        let end = i + 1;
+        if ch == '\n' {
+            break;
+        } else if escaped {
             escaped = false;
         } else if ch == '\\' {
             escaped = true;
         } else if ch == '"' {
             return Ok((Token::StringLiteral(&input[1..end]), end + 1));
         }
-        end += 1;
     }
 
-    Err(ParseError::UnterminatedStringLiteral(Location::new(0, 0))) // You might want to pass proper location here
+    Err(ParseError::UnterminatedStringLiteral(location))
 }
 
-fn tokenize_number(input: &str) -> (Token, usize) {
+fn tokenize_number(input: &str) -> (Token<'_>, usize) {
     let mut end = 0;
     let mut is_float = false;
     let mut is_hex = false;
@@ -437,7 +421,7 @@ fn tokenize_number(input: &str) -> (Token, usize) {
         && input[end + 1..]
             .chars()
             .next()
-            .map_or(false, |c| c.is_digit(8))
+            .is_some_and(|c| c.is_digit(8))
     {
         is_octal = true;
         end += 1;
@@ -472,18 +456,20 @@ fn tokenize_number(input: &str) -> (Token, usize) {
     }
 
     let number_str = &input[..end];
+    let sign_len = usize::from(input.starts_with('-') || input.starts_with('+'));
+    let sign = &number_str[..sign_len];
     if is_float {
         match number_str.parse::<f64>() {
             Ok(value) => (Token::FloatLiteral(value), end),
             Err(_) => (Token::Unknown(number_str.to_string()), end),
         }
     } else if is_hex {
-        match i64::from_str_radix(&number_str[2..], 16) {
+        match i64::from_str_radix(&format!("{}{}", sign, &number_str[sign_len + 2..]), 16) {
             Ok(value) => (Token::HexIntLiteral(value), end),
             Err(_) => (Token::Unknown(number_str.to_string()), end),
         }
     } else if is_octal {
-        match i64::from_str_radix(&number_str[1..], 8) {
+        match i64::from_str_radix(&format!("{}{}", sign, &number_str[sign_len + 1..]), 8) {
             Ok(value) => (Token::OctalIntLiteral(value), end),
             Err(_) => (Token::Unknown(number_str.to_string()), end),
         }
@@ -495,7 +481,7 @@ fn tokenize_number(input: &str) -> (Token, usize) {
     }
 }
 
-fn tokenize_identifier(input: &str) -> (Token, usize) {
+fn tokenize_identifier(input: &str) -> (Token<'_>, usize) {
     let mut end = 0;
     for (i, ch) in input.char_indices() {
         if ch.is_alphanumeric() || ch == '_' {
@@ -525,7 +511,10 @@ fn tokenize_identifier(input: &str) -> (Token, usize) {
         "weak" => (Token::Weak, end),
         "public" => (Token::Public, end),
         "extensions" => (Token::Extensions, end),
+        "extend" => (Token::Extend, end),
         "stream" => (Token::Stream, end),
+        "optional" => (Token::Optional, end),
+        "required" => (Token::Required, end),
         "string" => (Token::StringType, end),
         "int32" => (Token::Identifier("int32"), end),
         "int64" => (Token::Identifier("int64"), end),
@@ -575,161 +564,184 @@ mod tests {
                     location: Location {
                         line: 2,
                         column: 17
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Equals,
                     location: Location {
                         line: 2,
                         column: 24
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::StringLiteral("proto3"),
                     location: Location {
                         line: 2,
                         column: 26
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Semicolon,
                     location: Location {
                         line: 2,
                         column: 34
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Message,
                     location: Location {
                         line: 4,
                         column: 17
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Identifier("Person"),
                     location: Location {
                         line: 4,
                         column: 25
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::OpenBrace,
                     location: Location {
                         line: 4,
                         column: 32
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::StringType,
                     location: Location {
                         line: 5,
                         column: 21
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Identifier("name"),
                     location: Location {
                         line: 5,
                         column: 28
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Equals,
                     location: Location {
                         line: 5,
                         column: 33
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::DecimalIntLiteral(1),
                     location: Location {
                         line: 5,
                         column: 35
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Semicolon,
                     location: Location {
                         line: 5,
                         column: 36
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Identifier("int32"),
                     location: Location {
                         line: 6,
                         column: 21
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Identifier("age"),
                     location: Location {
                         line: 6,
                         column: 27
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Equals,
                     location: Location {
                         line: 6,
                         column: 31
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::DecimalIntLiteral(2),
                     location: Location {
                         line: 6,
                         column: 33
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Semicolon,
                     location: Location {
                         line: 6,
                         column: 34
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Identifier("float"),
                     location: Location {
                         line: 7,
                         column: 21
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Identifier("height"),
                     location: Location {
                         line: 7,
                         column: 27
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Equals,
                     location: Location {
                         line: 7,
                         column: 34
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::DecimalIntLiteral(3),
                     location: Location {
                         line: 7,
                         column: 36
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::Semicolon,
                     location: Location {
                         line: 7,
                         column: 37
-                    }
+                    },
+                    span: Span::default(),
                 },
                 TokenWithLocation {
                     token: Token::CloseBrace,
                     location: Location {
                         line: 8,
                         column: 17
-                    }
+                    },
+                    span: Span::default(),
                 },
             ]
         );
@@ -737,7 +749,7 @@ mod tests {
 
     #[test]
     fn test_keywords() {
-        let input = "syntax proto2 proto3 import package message enum service rpc returns option repeated oneof map reserved to weak public extensions";
+        let input = "syntax proto2 proto3 import package message enum service rpc returns option repeated oneof map reserved to weak public extensions extend optional required";
         let tokens = tokenize(input).unwrap();
 
         assert_eq!(
@@ -762,6 +774,9 @@ mod tests {
                 &Token::Weak,
                 &Token::Public,
                 &Token::Extensions,
+                &Token::Extend,
+                &Token::Optional,
+                &Token::Required,
             ]
         );
     }
@@ -807,6 +822,18 @@ mod tests {
     }
 
     #[test]
+    fn test_unterminated_string_literal() {
+        // Reported at the opening quote, whether the input or the line ends first
+        for input in ["a = \"abc", "a = \"abc\n\"", "a = \"abc\\\"\n"] {
+            assert!(matches!(
+                tokenize(input),
+                Err(ParseError::UnterminatedStringLiteral(loc)) if loc == Location::new(1, 5)
+            ));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_number_literals() {
         // let input = "0 123 -456 3.14 -2.718 .5"; <--- FIXME support .5 in lexer
         let input = "0 123 -456 3.14 -2.718";
@@ -824,9 +851,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_signed_number_literals() {
+        let input = "-0x1F 0x1F -017 -inf";
+        let tokens = tokenize(input).unwrap();
+
+        assert_eq!(
+            tokens.iter().map(|t| &t.token).collect::<Vec<_>>(),
+            vec![
+                &Token::HexIntLiteral(-31),
+                &Token::HexIntLiteral(31),
+                &Token::OctalIntLiteral(-15),
+                &Token::Minus,
+                &Token::Identifier("inf"),
+            ]
+        );
+    }
+
     #[test]
     fn test_symbols() {
-        let input = "= ; , . { } ( ) [ ] < >";
+        let input = "= ; : , . { } ( ) [ ] < > - /";
         let tokens = tokenize(input).unwrap();
 
         assert_eq!(
@@ -834,6 +878,7 @@ mod tests {
             vec![
                 &Token::Equals,
                 &Token::Semicolon,
+                &Token::Colon,
                 &Token::Comma,
                 &Token::Dot,
                 &Token::OpenBrace,
@@ -844,6 +889,8 @@ mod tests {
                 &Token::CloseBracket,
                 &Token::LessThan,
                 &Token::GreaterThan,
+                &Token::Minus,
+                &Token::Slash,
             ]
         );
     }
@@ -901,6 +948,11 @@ mod tests {
                 }
             }
         }
+
+        // Line comments do not count their terminating newline twice
+        assert_eq!(tokens[1].location, Location::new(3, 13));
+        assert_eq!(tokens[10].location, Location::new(5, 34));
+        assert_eq!(tokens[11].location, Location::new(6, 13));
     }
 
     fn normalize_comment(comment: &str) -> String {
@@ -1040,6 +1092,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_token_spans() {
+        let input = "syntax = \"proto3\";\n/* a\n b */ message Person {}";
+
+        let tokens = tokenize(input).unwrap();
+        let texts: Vec<&str> = tokens.iter().map(|t| t.span.text(input)).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "syntax",
+                "=",
+                "\"proto3\"",
+                ";",
+                "/* a\n b */",
+                "message",
+                "Person",
+                "{",
+                "}"
+            ]
+        );
+
+        // The block comment ends on line 3, so `message` starts after it
+        let message = &tokens[5];
+        assert_eq!(message.span.start, Position::new(30, 3, 7));
+        assert_eq!(message.span.end, Position::new(37, 3, 14));
+        assert_eq!(message.location, Location::new(3, 7));
+    }
+
     #[test]
     fn test_float_in_field_number() {
         let input = "message Person { int32 age = 2.5; }";
@@ -6,14 +6,18 @@
 pub mod ast;
 pub mod error;
 mod lexer;
+pub mod resolver;
+pub mod validator;
+pub(crate) mod zod;
 
 use crate::parser::ast::{
-    Enum, EnumValue, Field, FieldLabel, Import, ImportKind, Message, Method, OptionValue,
-    ProtoFile, ProtoOption, Service, Syntax,
+    Enum, EnumValue, Extend, ExtensionRange, Field, FieldLabel, Import, ImportKind, Message,
+    Method, OneOf, OptionName, OptionNamePart, OptionValue, ProtoFile, ProtoOption, Reserved,
+    ReservedKind, Service, Syntax, MAX_FIELD_NUMBER,
 };
 
-use ast::{EnumValueOption, EnumValueOptionValue, FieldType, NumberValue};
-use error::Location;
+use ast::{Comments, FieldType, NumberValue, ZodAnnotation};
+use error::{Location, Span};
 pub use error::{ParseError, ParseResult};
 pub use lexer::{tokenize, Token, TokenWithLocation};
 
@@ -46,14 +50,14 @@ pub fn parse_proto_file(input: &str) -> Result<ProtoFile, ParseError> {
         );
     }
 
-    let mut token_iter = tokens.into_iter().peekable();
-
-    parse_tokenized_input(&mut token_iter)
+    let end = end_of_input(&tokens);
+    parse_tokenized_input(tokens.into_iter(), &mut Recovery::default())
+        .map_err(|e| locate_end_of_input(e, end))
 }
 
-fn parse_tokenized_input<'a, I>(tokens: I) -> Result<ProtoFile, ParseError>
+fn parse_tokenized_input<'a, I>(tokens: I, recovery: &mut Recovery) -> Result<ProtoFile, ParseError>
 where
-    I: Iterator<Item = TokenWithLocation<'a>>,
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
 {
     let mut tokens = tokens.peekable();
     let mut proto_file = ProtoFile::new();
@@ -62,50 +66,240 @@ where
     skip_comments_and_whitespace(&mut tokens);
 
     // required
-    parse_syntax(&mut tokens, &mut proto_file)?;
+    recovery.statement(&mut tokens, |tokens, _| {
+        parse_syntax(tokens, &mut proto_file)
+    })?;
 
-    // Parse options that might follow syntax
-    while let Some(token) = tokens.peek() {
-        match &token.token {
-            Token::Option => parse_option(&mut tokens, &mut proto_file.options)?,
-            _ => break,
+    loop {
+        let comments = parse_leading_comments(&mut tokens);
+        if tokens.peek().is_none() {
+            break;
         }
-    }
 
-    while let Some(current_token) = tokens.peek() {
-        match &current_token.token {
-            Token::Syntax => parse_syntax(&mut tokens, &mut proto_file)?,
-            Token::Package => parse_package(&mut tokens, &mut proto_file)?,
-            Token::Import => parse_import(&mut tokens, &mut proto_file)?,
-            Token::Message => {
-                let message = parse_message(&mut tokens)?;
-                proto_file.messages.push(message);
-            }
-            Token::Enum => {
-                let enum_def = parse_enum(&mut tokens)?;
-                proto_file.enums.push(enum_def);
-            }
-            Token::Service => {
-                let service = parse_service(&mut tokens)?;
-                proto_file.services.push(service);
+        recovery.statement(&mut tokens, |tokens, recovery| {
+            let syntax = proto_file.syntax.clone();
+            let current_token = tokens.peek().expect("checked above");
+            match &current_token.token {
+                Token::Syntax => parse_syntax(tokens, &mut proto_file)?,
+                Token::Package => parse_package(tokens, &mut proto_file)?,
+                Token::Import => parse_import(tokens, &mut proto_file)?,
+                Token::Option => parse_option(tokens, &mut proto_file.options)?,
+                Token::Message => {
+                    let mut message = parse_message(tokens, recovery, &syntax)?;
+                    attach_leading_comments(&mut message.comments, comments);
+                    proto_file.messages.push(message);
+                }
+                Token::Enum => {
+                    let mut enum_def = parse_enum(tokens, recovery)?;
+                    attach_leading_comments(&mut enum_def.comments, comments);
+                    proto_file.enums.push(enum_def);
+                }
+                Token::Service => {
+                    let mut service = parse_service(tokens, recovery)?;
+                    attach_leading_comments(&mut service.comments, comments);
+                    proto_file.services.push(service);
+                }
+                Token::Extend => {
+                    let extend = parse_extend(tokens, recovery, &syntax)?;
+                    proto_file.extends.push(extend);
+                }
+                _ => {
+                    return Err(ParseError::UnexpectedToken(
+                        format!("{:?}", current_token.token),
+                        current_token.location,
+                    ));
+                }
             }
-            Token::Comment(_) => {
-                tokens.next(); // Skip comments
+            Ok(())
+        })?;
+    }
+
+    Ok(proto_file)
+}
+
+/// Parse a Protobuf file, collecting every error instead of stopping at the
+/// first one
+///
+/// After an error, the parser skips to the end of the statement it was in,
+/// i.e. past the next `;` or the `}` of a block started in the statement,
+/// and carries on with the next statement. A `}` that ends the enclosing
+/// block still closes it, even if the statement failed on it. The definitions that could be
+/// parsed are returned alongside the errors; a definition whose body is not
+/// closed is dropped. Lexer errors are not recovered from, so a file that
+/// cannot be tokenized yields an empty `ProtoFile` and the lexer error.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the Protobuf file content
+///
+/// # Returns
+///
+/// * `(ProtoFile, Vec<ParseError>)` - The parsed definitions, and the errors
+///   in the order they were found, empty if the file is valid
+pub fn parse_proto_file_recovering(input: &str) -> (ProtoFile, Vec<ParseError>) {
+    let tokens = match tokenize(input) {
+        Ok(tokens) => tokens,
+        Err(e) => return (ProtoFile::new(), vec![e]),
+    };
+
+    let mut recovery = Recovery {
+        enabled: true,
+        errors: Vec::new(),
+    };
+    let end = end_of_input(&tokens);
+    let result = parse_tokenized_input(tokens.iter().cloned(), &mut recovery);
+
+    // Only errors outside of statements end a recovering parse
+    let proto_file = result.unwrap_or_else(|e| {
+        recovery.errors.push(e);
+        ProtoFile::new()
+    });
+    let errors = recovery
+        .errors
+        .into_iter()
+        .map(|e| locate_end_of_input(e, end))
+        .collect();
+    (proto_file, errors)
+}
+
+/// Returns the error for input that ends in the middle of a definition.
+///
+/// The parsing functions only see the tokens, not where the input ends, so
+/// the error is located by [`locate_end_of_input`] once parsing has stopped.
+fn unexpected_end() -> ParseError {
+    ParseError::UnexpectedEndOfInput(Location::new(0, 0))
+}
+
+/// Returns the location right after the last token, where an unexpected end
+/// of input is reported. Trailing whitespace is left out, so that the
+/// location is on a line with source text.
+fn end_of_input(tokens: &[TokenWithLocation]) -> Location {
+    tokens
+        .last()
+        .map_or(Location::new(1, 1), |token| token.span.end.into())
+}
+
+/// Gives an unexpected end of input error without a location, as returned by
+/// [`unexpected_end`], the location of the end of the input; other errors,
+/// such as the end of a `@zod` comment, are returned unchanged.
+fn locate_end_of_input(error: ParseError, end: Location) -> ParseError {
+    match error {
+        ParseError::UnexpectedEndOfInput(location) if location == Location::new(0, 0) => {
+            ParseError::UnexpectedEndOfInput(end)
+        }
+        other => other,
+    }
+}
+
+/// How statement errors are handled
+///
+/// Without recovery, the error of a statement ends parsing. With it, the
+/// error is collected and the statement skipped.
+#[derive(Default)]
+struct Recovery {
+    enabled: bool,
+    errors: Vec<ParseError>,
+}
+
+impl Recovery {
+    /// Parses a statement, returning `None` if it failed and was skipped.
+    ///
+    /// The tokens are cloned before the statement, so that a failed one can
+    /// be skipped from its start, whatever the parser consumed before failing.
+    fn statement<'a, I, T>(
+        &mut self,
+        tokens: &mut Peekable<I>,
+        parse: impl FnOnce(&mut Peekable<I>, &mut Self) -> Result<T, ParseError>,
+    ) -> Result<Option<T>, ParseError>
+    where
+        I: Iterator<Item = TokenWithLocation<'a>> + Clone,
+    {
+        let start = self.enabled.then(|| tokens.clone());
+        match parse(tokens, self) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                let Some(start) = start else {
+                    return Err(e);
+                };
+                debug!("Recovering from {}", e);
+                if matches!(e, ParseError::UnexpectedEndOfInput(loc) if loc == Location::new(0, 0))
+                {
+                    // There is no token to skip to; whatever is left belongs
+                    // to the statement
+                    skip_statement(tokens, 0);
+                } else {
+                    *tokens = start;
+                    skip_failed_statement(tokens, e.location());
+                }
+                self.errors.push(e);
+                Ok(None)
             }
-            _ => {
-                let loc = current_token.location;
-                return Err(ParseError::UnexpectedToken(
-                    format!("{:?}", current_token.token),
-                    loc,
-                ));
+        }
+    }
+}
+
+/// Skips a statement that failed at `failed_on`, from its first token.
+///
+/// The tokens before `failed_on` are skipped, and if the statement has not
+/// ended there, the rest of it from the token it failed on. A `}` it failed
+/// on is left to close the enclosing block, and a `{` opens a block that is
+/// skipped with the statement.
+fn skip_failed_statement<'a, I>(tokens: &mut Peekable<I>, failed_on: Location)
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let failed_on = (failed_on.line, failed_on.column);
+    let mut depth = 0;
+    let mut skipped = false;
+    let mut ended = false;
+    while let Some(token) = tokens.next_if(|t| (t.location.line, t.location.column) < failed_on) {
+        skipped = true;
+        match token.token {
+            Token::OpenBrace => depth += 1,
+            Token::CloseBrace => {
+                depth -= 1;
+                ended = depth == 0;
             }
+            Token::Semicolon if depth == 0 => ended = true,
+            _ => {}
         }
+    }
+    if ended {
+        return;
+    }
 
-        // Skip comments between top-level elements
-        skip_comments_and_whitespace(&mut tokens);
+    if !skipped {
+        // A statement that failed on its first token must not be retried,
+        // or the parser would never get past it
+        match tokens.next().map(|t| t.token) {
+            Some(Token::OpenBrace) => depth = 1,
+            Some(Token::Semicolon | Token::CloseBrace) | None => return,
+            Some(_) => {}
+        }
     }
+    skip_statement(tokens, depth);
+}
 
-    Ok(proto_file)
+/// Skips the rest of a statement that is `depth` blocks deep: up to and
+/// including the next `;` or the `}` that closes a block opened in the
+/// statement, but not past the `}` of the enclosing block.
+fn skip_statement<'a, I>(tokens: &mut Peekable<I>, mut depth: usize)
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    while let Some(token) = tokens.next_if(|t| !(depth == 0 && t.token == Token::CloseBrace)) {
+        match token.token {
+            Token::OpenBrace => depth += 1,
+            Token::CloseBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+            Token::Semicolon if depth == 0 => return,
+            _ => {}
+        }
+    }
 }
 
 fn skip_comments_and_whitespace<'a, I>(tokens: &mut Peekable<I>)
@@ -121,6 +315,149 @@ where
         }
     }
 }
+
+/// Consumes the comments between a definition's name and its opening brace,
+/// returning the `@zod` annotation among them, if any.
+fn parse_leading_annotation<'a, I>(
+    tokens: &mut Peekable<I>,
+) -> Result<Option<ZodAnnotation>, ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let mut annotation = None;
+    while let Some(TokenWithLocation {
+        token: Token::Comment(comment),
+        location,
+        ..
+    }) = tokens.peek()
+    {
+        if let Some(parsed) = zod::parse_zod_comment(comment, *location)? {
+            annotation = Some(parsed);
+        }
+        tokens.next(); // Consume the comment
+    }
+    Ok(annotation)
+}
+
+/// Consumes the next token if it is a comment starting on the given line,
+/// i.e. a comment trailing the statement that ended on that line.
+fn take_trailing_comment<'a, I>(
+    tokens: &mut Peekable<I>,
+    line: usize,
+) -> Option<(&'a str, Location)>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    match tokens.peek() {
+        Some(TokenWithLocation {
+            token: Token::Comment(comment),
+            location,
+            ..
+        }) if location.line == line => {
+            let trailing = (*comment, *location);
+            tokens.next(); // Consume the comment
+            Some(trailing)
+        }
+        _ => None,
+    }
+}
+
+/// Consumes a trailing comment on the given line, returning its text together
+/// with the `@zod` annotation it contains, if any.
+fn parse_trailing_comment<'a, I>(
+    tokens: &mut Peekable<I>,
+    line: usize,
+) -> Result<(Option<String>, Option<ZodAnnotation>), ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    match take_trailing_comment(tokens, line) {
+        Some((comment, location)) => Ok((
+            Some(comment_text(comment)),
+            zod::parse_zod_comment(comment, location)?,
+        )),
+        None => Ok((None, None)),
+    }
+}
+
+/// Consumes the comments before a definition and sorts them into its leading
+/// comment and the detached comments above it.
+///
+/// Consecutive line comments are merged into one comment. The last comment is
+/// the leading comment if it ends on the line before the definition (or on
+/// the same line); any comment separated from what follows by a blank line is
+/// detached.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+///
+/// # Returns
+///
+/// * `Comments` - The leading and detached comments; `trailing` is left empty.
+fn parse_leading_comments<'a, I>(tokens: &mut Peekable<I>) -> Comments
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    // (text, last line, is a line comment)
+    let mut blocks: Vec<(String, usize, bool)> = Vec::new();
+
+    while let Some(TokenWithLocation {
+        token: Token::Comment(comment),
+        location,
+        ..
+    }) = tokens.peek()
+    {
+        let is_line_comment = comment.starts_with("//");
+        let end_line = location.line + comment.matches('\n').count();
+        match blocks.last_mut() {
+            Some((text, last_line, true)) if is_line_comment && location.line == *last_line + 1 => {
+                text.push_str(&comment_text(comment));
+                *last_line = end_line;
+            }
+            _ => blocks.push((comment_text(comment), end_line, is_line_comment)),
+        }
+        tokens.next(); // Consume the comment
+    }
+
+    let next_line = tokens.peek().map(|t| t.location.line);
+    let mut comments = Comments::default();
+    if let Some((_, last_line, _)) = blocks.last() {
+        if next_line.is_some_and(|line| line == *last_line || line == *last_line + 1) {
+            comments.leading = blocks.pop().map(|(text, _, _)| text);
+        }
+    }
+    comments.leading_detached = blocks.into_iter().map(|(text, _, _)| text).collect();
+    comments
+}
+
+/// Moves the leading and detached comments collected before a definition into
+/// its comments, keeping the trailing comment found while parsing it.
+fn attach_leading_comments(comments: &mut Comments, leading: Comments) {
+    comments.leading = leading.leading;
+    comments.leading_detached = leading.leading_detached;
+}
+
+/// Strips the comment markers from a comment, like protoc does: line comments
+/// lose the `//` and gain a trailing newline, block comments lose `/*`, `*/`
+/// and the leading whitespace and `*` of every line after the first.
+fn comment_text(comment: &str) -> String {
+    if let Some(text) = comment.strip_prefix("//") {
+        return format!("{}\n", text.trim_end_matches('\r'));
+    }
+
+    let text = comment.strip_prefix("/*").unwrap_or(comment);
+    let text = text.strip_suffix("*/").unwrap_or(text);
+    let mut lines = text.split('\n');
+    let mut result = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        let line = line.trim_start();
+        result.push('\n');
+        result.push_str(line.strip_prefix('*').unwrap_or(line));
+    }
+    result
+}
+
 /// Parses the syntax declaration of a Protobuf file.
 ///
 /// This function expects to find a syntax declaration at the beginning of the file,
@@ -142,11 +479,9 @@ where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
     // Consume 'syntax' token
-    let syntax_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
+    let syntax_token = tokens.next().ok_or_else(unexpected_end)?;
     match syntax_token.token {
-        Token::Identifier(s) if s == "syntax" => {
+        Token::Identifier("syntax") => {
             debug!("Found 'syntax' identifier");
         }
         Token::Syntax => {
@@ -162,9 +497,7 @@ where
     }
 
     // Expect '=' token
-    let equals_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(syntax_token.location))?;
+    let equals_token = tokens.next().ok_or_else(unexpected_end)?;
     if equals_token.token != Token::Equals {
         return Err(ParseError::UnexpectedToken(
             format!("Expected '=', found {:?}", equals_token.token),
@@ -173,9 +506,7 @@ where
     }
 
     // Parse syntax version
-    let version_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(equals_token.location))?;
+    let version_token = tokens.next().ok_or_else(unexpected_end)?;
     debug!("Parsing syntax version: {:?}", version_token);
     match version_token.token {
         Token::StringLiteral("proto2") => proto_file.syntax = Syntax::Proto2,
@@ -198,10 +529,12 @@ where
                 token.location,
             ));
         }
+        let line = token.location.line;
         tokens.next(); // Consume the semicolon
+        take_trailing_comment(tokens, line);
         debug!("Consumed semicolon");
     } else {
-        return Err(ParseError::UnexpectedEndOfInput(version_token.location));
+        return Err(unexpected_end());
     }
 
     Ok(())
@@ -231,7 +564,7 @@ where
     // Consume 'package' token
     let package_token = tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Package)?;
 
     let package_name = match tokens.next() {
@@ -277,14 +610,15 @@ where
                 t.location,
             ))
         }
-        None => return Err(ParseError::UnexpectedEndOfInput(package_token.location)),
+        None => return Err(unexpected_end()),
     };
 
     // Expect semicolon
-    tokens
+    let semicolon_token = tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(package_token.location))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Semicolon)?;
+    take_trailing_comment(tokens, semicolon_token.location.line);
 
     proto_file.package = Some(package_name);
     Ok(())
@@ -312,9 +646,7 @@ fn parse_import<'a, I>(
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
-    let import_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
+    let import_token = tokens.next().ok_or_else(unexpected_end)?;
     if import_token.token != Token::Import {
         return Err(ParseError::UnexpectedToken(
             format!("Expected 'import', found {:?}", import_token.token),
@@ -323,9 +655,7 @@ where
     }
 
     let mut kind = ImportKind::Default;
-    let next_token = tokens
-        .peek()
-        .ok_or(ParseError::UnexpectedEndOfInput(import_token.location))?;
+    let next_token = tokens.peek().ok_or_else(unexpected_end)?;
 
     match next_token.token {
         Token::Public => {
@@ -340,9 +670,7 @@ where
     }
 
     // Parse import path
-    let path_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(import_token.location))?;
+    let path_token = tokens.next().ok_or_else(unexpected_end)?;
     let path = match path_token.token {
         Token::StringLiteral(path) => path.to_string(),
         // Token::Identifier(path) | Token::FullyQualifiedIdentifier(path) => path.to_string(),
@@ -358,18 +686,21 @@ where
     };
 
     // Expect semicolon
-    let semicolon_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(path_token.location))?;
+    let semicolon_token = tokens.next().ok_or_else(unexpected_end)?;
     if semicolon_token.token != Token::Semicolon {
         return Err(ParseError::UnexpectedToken(
             format!("Expected ';', found {:?}", semicolon_token.token),
             semicolon_token.location,
         ));
     }
+    take_trailing_comment(tokens, semicolon_token.location.line);
 
     // Add the import to the proto file
-    proto_file.imports.push(Import { path, kind });
+    proto_file.imports.push(Import {
+        path,
+        kind,
+        span: import_token.span.to(semicolon_token.span),
+    });
 
     Ok(())
 }
@@ -382,19 +713,22 @@ where
 /// # Arguments
 ///
 /// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+/// * `syntax` - The syntax of the file, passed on to the fields.
 ///
 /// # Returns
 ///
 /// * `Result<Message, ParseError>` - A Result containing the parsed Message on success,
 ///   or a ParseError on failure.
-fn parse_message<'a, I>(tokens: &mut Peekable<I>) -> Result<Message, ParseError>
+fn parse_message<'a, I>(
+    tokens: &mut Peekable<I>,
+    recovery: &mut Recovery,
+    syntax: &Syntax,
+) -> Result<Message, ParseError>
 where
-    I: Iterator<Item = TokenWithLocation<'a>>,
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
 {
     // Expect 'message' keyword
-    let message_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
+    let message_token = tokens.next().ok_or_else(unexpected_end)?;
 
     match &message_token.token {
         Token::Message => {}
@@ -407,9 +741,7 @@ where
     }
 
     // Expect message name
-    let name_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(message_token.location))?;
+    let name_token = tokens.next().ok_or_else(unexpected_end)?;
     let name = match &name_token.token {
         Token::Identifier(s) => s.to_string(),
         _ => {
@@ -420,10 +752,10 @@ where
         }
     };
 
+    let zod = parse_leading_annotation(tokens)?;
+
     // Expect opening brace
-    let open_brace_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(name_token.location))?;
+    let open_brace_token = tokens.next().ok_or_else(unexpected_end)?;
     if open_brace_token.token != Token::OpenBrace {
         return Err(ParseError::UnexpectedToken(
             format!("Expected '{{', found {:?}", open_brace_token.token),
@@ -431,35 +763,29 @@ where
         ));
     }
 
+    let (trailing, trailing_zod) = parse_trailing_comment(tokens, open_brace_token.location.line)?;
+
     let mut message = Message::new(name);
+    message.zod = zod.or(trailing_zod);
+    message.comments.trailing = trailing;
 
     // Parse message body
     while let Some(_token_with_location) = tokens.peek() {
-        skip_comments_and_whitespace(tokens);
+        let comments = parse_leading_comments(tokens);
 
         if let Some(token_with_location) = tokens.peek() {
             match &token_with_location.token {
                 Token::CloseBrace => {
+                    let line = token_with_location.location.line;
+                    message.span = message_token.span.to(token_with_location.span);
                     tokens.next(); // Consume closing brace
+                    take_trailing_comment(tokens, line);
                     return Ok(message);
                 }
-                Token::Message => {
-                    let nested_message = parse_message(tokens)?;
-                    message.nested_messages.push(nested_message);
-                }
-                Token::Enum => {
-                    let nested_enum = parse_enum(tokens)?;
-                    message.nested_enums.push(nested_enum);
-                }
-                Token::Option => {
-                    parse_option(tokens, &mut message.options)?;
-                }
-                Token::Reserved => {
-                    parse_reserved(tokens, &mut message.reserved)?;
-                }
                 _ => {
-                    let field = parse_field(tokens)?;
-                    message.fields.push(field);
+                    recovery.statement(tokens, |tokens, recovery| {
+                        parse_message_statement(tokens, recovery, syntax, &mut message, comments)
+                    })?;
                 }
             }
         } else {
@@ -467,345 +793,464 @@ where
         }
     }
 
-    Err(ParseError::UnexpectedEndOfInput(open_brace_token.location))
+    Err(unexpected_end())
 }
 
-/// Parses a message definition from the token stream.
+/// Parses a statement of a message body and adds what it defines to `message`.
+fn parse_message_statement<'a, I>(
+    tokens: &mut Peekable<I>,
+    recovery: &mut Recovery,
+    syntax: &Syntax,
+    message: &mut Message,
+    comments: Comments,
+) -> Result<(), ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
+{
+    let Some(token_with_location) = tokens.peek() else {
+        return Ok(());
+    };
+    match &token_with_location.token {
+        Token::Message => {
+            let mut nested_message = parse_message(tokens, recovery, syntax)?;
+            attach_leading_comments(&mut nested_message.comments, comments);
+            message.nested_messages.push(nested_message);
+        }
+        Token::Enum => {
+            let mut nested_enum = parse_enum(tokens, recovery)?;
+            attach_leading_comments(&mut nested_enum.comments, comments);
+            message.nested_enums.push(nested_enum);
+        }
+        Token::Option => {
+            parse_option(tokens, &mut message.options)?;
+        }
+        Token::Reserved => {
+            parse_reserved(tokens, &mut message.reserved)?;
+        }
+        Token::Oneof => {
+            let oneof = parse_oneof(tokens, recovery, syntax)?;
+            message.oneofs.push(oneof);
+        }
+        Token::Extensions => {
+            parse_extensions(tokens, &mut message.extensions)?;
+        }
+        Token::Extend => {
+            let extend = parse_extend(tokens, recovery, syntax)?;
+            message.extends.push(extend);
+        }
+        _ => {
+            let mut field = parse_field(tokens, syntax)?;
+            attach_leading_comments(&mut field.comments, comments);
+            message.fields.push(field);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a oneof definition from the token stream.
 ///
-/// This function expects the 'message' keyword to have already been consumed.
-/// It parses the message name, opening brace, message body (including nested messages,
-/// enums, fields, options, and reserved statements), and closing brace.
+/// It parses the oneof name, opening brace, oneof body (fields and options)
+/// and closing brace. Comments inside the body are skipped.
 ///
 /// # Arguments
 ///
 /// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+/// * `syntax` - The syntax of the file, passed on to the fields.
 ///
 /// # Returns
 ///
-/// * `Result<Message, ParseError>` - A Result containing the parsed Message on success,
+/// * `Result<OneOf, ParseError>` - A Result containing the parsed OneOf on success,
 ///   or a ParseError on failure.
-fn parse_field<'a, I>(tokens: &mut Peekable<I>) -> Result<Field, ParseError>
+fn parse_oneof<'a, I>(
+    tokens: &mut Peekable<I>,
+    recovery: &mut Recovery,
+    syntax: &Syntax,
+) -> Result<OneOf, ParseError>
 where
-    I: Iterator<Item = TokenWithLocation<'a>>,
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
 {
-    let start_location = tokens
-        .peek()
-        .map(|t| t.location)
-        .unwrap_or(Location::new(0, 0));
-
-    skip_comments_and_whitespace(tokens);
-
-    // Parse field label (optional, repeated, required)
-    let label = match tokens.peek() {
-        Some(TokenWithLocation {
-            token: Token::Repeated,
-            ..
-        }) => {
-            tokens.next(); // Consume 'repeated'
-            FieldLabel::Repeated
-        }
-        Some(TokenWithLocation {
-            token: Token::Required,
-            ..
-        }) => {
-            tokens.next(); // Consume 'required'
-            FieldLabel::Required
-        }
-        _ => FieldLabel::Optional,
-    };
-
-    skip_comments_and_whitespace(tokens);
-
-    let (typ, name) = if let Some(TokenWithLocation {
-        token: Token::Map, ..
-    }) = tokens.peek()
-    {
-        tokens.next(); // Consume 'map'
-        parse_map_field(tokens)?
-    } else {
-        // Parse field type
-        let type_token = tokens
-            .next()
-            .ok_or(ParseError::UnexpectedEndOfInput(start_location))?;
-
-        debug!("Parsing field type: {:?}", type_token);
-
-        let typ = parse_field_type(&type_token)?;
-
-        // Parse field name
-        let name = parse_field_name(tokens)?;
-
-        (typ, name)
-    };
-
-    // Expect '=' token
-    tokens
+    // Expect 'oneof' keyword
+    let oneof_token = tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(start_location))?
-        .expect(Token::Equals)?;
+        .ok_or_else(unexpected_end)?
+        .expect(Token::Oneof)?;
 
-    // Parse field number
-    let number_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(start_location))?;
-    let number = match number_token.token {
-        Token::DecimalIntLiteral(num) => NumberValue::DecimalInt(num),
-        Token::FloatLiteral(num) => NumberValue::Float(num),
-        Token::HexIntLiteral(num) => NumberValue::Hex(num),
-        Token::OctalIntLiteral(num) => NumberValue::Octal(num),
+    // Expect oneof name
+    let name_token = tokens.next().ok_or_else(unexpected_end)?;
+    let name = match &name_token.token {
+        Token::Identifier(s) => s.to_string(),
         _ => {
             return Err(ParseError::UnexpectedToken(
-                format!("Expected field number, found {:?}", number_token.token),
-                number_token.location,
+                format!("Expected oneof name, found {:?}", name_token.token),
+                name_token.location,
             ));
         }
     };
 
-    // Expect semicolon
+    // Expect opening brace
     tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(start_location))?
-        .expect(Token::Semicolon)?;
+        .ok_or_else(unexpected_end)?
+        .expect(Token::OpenBrace)?;
 
-    Ok(Field {
-        name,
-        label,
-        typ,
-        number,
-        options: Vec::new(), // Add options parsing if needed
-    })
+    let mut oneof = OneOf::new(name);
+
+    loop {
+        let comments = parse_leading_comments(tokens);
+
+        match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::CloseBrace,
+                location,
+                span,
+            }) => {
+                let line = location.line;
+                oneof.span = oneof_token.span.to(*span);
+                tokens.next(); // Consume closing brace
+                take_trailing_comment(tokens, line);
+                return Ok(oneof);
+            }
+            Some(_) => {
+                recovery.statement(tokens, |tokens, _| {
+                    let token_with_location = tokens.peek().expect("peeked above");
+                    match &token_with_location.token {
+                        Token::Option => parse_option(tokens, &mut oneof.options),
+                        Token::Repeated | Token::Required | Token::Optional => {
+                            Err(ParseError::InvalidSyntax(
+                                "Fields in oneof must not have labels".to_string(),
+                                token_with_location.location,
+                            ))
+                        }
+                        _ => {
+                            let mut field = parse_field(tokens, syntax)?;
+                            attach_leading_comments(&mut field.comments, comments);
+                            oneof.fields.push(field);
+                            Ok(())
+                        }
+                    }
+                })?;
+            }
+            None => return Err(unexpected_end()),
+        }
+    }
 }
 
-/// Parses a field name from the token stream.
+/// Parses an `extend` block from the token stream.
 ///
-/// This function iterates through tokens, building up the field name.
-/// It handles multi-part names (e.g., "message_field") and special cases like "message".
-/// The parsing stops when it encounters an '=' token, which signifies the end of the field name.
+/// It parses the extended message name, opening brace, the extension fields
+/// and closing brace. Comments inside the body are skipped.
 ///
 /// # Arguments
 ///
 /// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+/// * `syntax` - The syntax of the file, passed on to the fields.
 ///
 /// # Returns
 ///
-/// * `Result<String, ParseError>` - The parsed field name on success, or a ParseError on failure.
-///
-/// # Errors
-///
-/// Returns a ParseError if:
-/// - Unexpected end of input is encountered
-/// - An unexpected token is found (neither identifier, 'message', nor '=')
-fn parse_field_name<'a, I>(tokens: &mut Peekable<I>) -> Result<String, ParseError>
+/// * `Result<Extend, ParseError>` - A Result containing the parsed Extend on success,
+///   or a ParseError on failure.
+fn parse_extend<'a, I>(
+    tokens: &mut Peekable<I>,
+    recovery: &mut Recovery,
+    syntax: &Syntax,
+) -> Result<Extend, ParseError>
 where
-    I: Iterator<Item = TokenWithLocation<'a>>,
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
 {
-    let mut name_parts = Vec::new();
-    let mut location = Location::new(0, 0);
+    // Expect 'extend' keyword
+    let extend_token = tokens
+        .next()
+        .ok_or_else(unexpected_end)?
+        .expect(Token::Extend)?;
 
-    while let Some(token_with_location) = tokens.peek() {
-        location = token_with_location.location;
-        debug!(
-            "Parsing field name, current token: {:?}",
-            token_with_location.token
-        );
-        match &token_with_location.token {
-            Token::Identifier(part) => {
-                debug!("Adding identifier part: {}", part);
-                // Split the identifier by underscores and add each part separately
-                for (i, subpart) in part.split('_').enumerate() {
-                    if i > 0 || !name_parts.is_empty() {
-                        name_parts.push("_".to_string());
-                    }
-                    name_parts.push(subpart.to_string());
-                }
-                tokens.next(); // Consume the token
-            }
-            Token::Message if name_parts.is_empty() => {
-                debug!("Found Message token");
-                name_parts.push("message".to_string());
-                tokens.next(); // Consume the token
+    // Parse the (possibly fully-qualified) name of the extended message
+    let mut extendee = String::new();
+    loop {
+        match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::Dot, ..
+            }) => {
+                extendee.push('.');
+                tokens.next(); // Consume the dot
             }
-            Token::Map if name_parts.is_empty() => {
-                debug!("Found Map token");
-                name_parts.push("map".to_string());
-                tokens.next(); // Consume the token
+            Some(TokenWithLocation {
+                token: Token::OpenBrace,
+                ..
+            }) => break,
+            Some(t) => match name_part(&t.token) {
+                Some(part) => {
+                    extendee.push_str(&part);
+                    tokens.next(); // Consume the name part
+                }
+                None => {
+                    return Err(ParseError::UnexpectedToken(
+                        format!("Expected extended message name, found {:?}", t.token),
+                        t.location,
+                    ))
+                }
+            },
+            None => return Err(unexpected_end()),
+        }
+    }
+
+    if extendee.is_empty() {
+        return Err(ParseError::MissingIdentifier(
+            "Expected extended message name".to_string(),
+            extend_token.location,
+        ));
+    }
+
+    // Expect opening brace
+    tokens
+        .next()
+        .ok_or_else(unexpected_end)?
+        .expect(Token::OpenBrace)?;
+
+    let mut extend = Extend::new(extendee);
+
+    loop {
+        let comments = parse_leading_comments(tokens);
+
+        match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::CloseBrace,
+                location,
+                span,
+            }) => {
+                let line = location.line;
+                extend.span = extend_token.span.to(*span);
+                tokens.next(); // Consume closing brace
+                take_trailing_comment(tokens, line);
+                return Ok(extend);
             }
-            Token::Repeated if name_parts.is_empty() => {
-                debug!("Found Repeated token");
-                name_parts.push("repeated".to_string());
-                tokens.next(); // Consume the token
+            Some(_) => {
+                recovery.statement(tokens, |tokens, _| {
+                    let mut field = parse_field(tokens, syntax)?;
+                    attach_leading_comments(&mut field.comments, comments);
+                    extend.fields.push(field);
+                    Ok(())
+                })?;
             }
-            Token::Equals => {
-                debug!("Found Equals token, ending field name parsing");
-                break;
+            None => return Err(unexpected_end()),
+        }
+    }
+}
+
+/// Parses an `extensions` statement, e.g. `extensions 100 to 199, 500 to max;`
+///
+/// Options in square brackets after the ranges apply to every range of the statement.
+fn parse_extensions<'a, I>(
+    tokens: &mut Peekable<I>,
+    extensions: &mut Vec<ExtensionRange>,
+) -> Result<(), ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    // Consume 'extensions' token
+    tokens
+        .next()
+        .ok_or_else(unexpected_end)?
+        .expect(Token::Extensions)?;
+
+    let mut ranges = Vec::new();
+
+    loop {
+        let start_token = tokens.next().ok_or_else(unexpected_end)?;
+        let start = range_bound(&start_token, false)?;
+
+        let (end, end_span) = match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::To, ..
+            }) => {
+                tokens.next(); // Consume 'to' token
+                let end_token = tokens.next().ok_or_else(unexpected_end)?;
+                (range_bound(&end_token, true)?, end_token.span)
             }
-            _ => {
-                debug!(
-                    "Found unexpected token: {:?}, ending field name parsing",
-                    token_with_location.token
-                );
-                break;
+            _ => (start, start_token.span),
+        };
+
+        if start > end {
+            return Err(ParseError::InvalidRange(start, end, start_token.location));
+        }
+        ranges.push((start, end, start_token.span.to(end_span)));
+
+        match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::Comma,
+                ..
+            }) => {
+                tokens.next(); // Consume comma
             }
+            _ => break,
         }
     }
 
-    if name_parts.is_empty() {
-        return Err(ParseError::MissingIdentifier(
-            "Expected field name".to_string(),
-            location,
-        ));
-    }
+    let options = parse_field_options(tokens)?;
+
+    let semicolon_token = tokens
+        .next()
+        .ok_or_else(unexpected_end)?
+        .expect(Token::Semicolon)?;
+    take_trailing_comment(tokens, semicolon_token.location.line);
+
+    extensions.extend(ranges.into_iter().map(|(start, end, span)| ExtensionRange {
+        start,
+        end,
+        options: options.clone(),
+        span,
+    }));
 
-    let name = name_parts.join("");
-    debug!("Final field name: {}", name);
-    Ok(name)
+    Ok(())
 }
 
-/// Parses an enum definition from the token stream.
+/// Reads one end of a field number range; `max` is only accepted as the upper bound.
+fn range_bound(token: &TokenWithLocation, allow_max: bool) -> Result<i32, ParseError> {
+    match token.token {
+        Token::DecimalIntLiteral(n) | Token::HexIntLiteral(n) | Token::OctalIntLiteral(n) => {
+            i32::try_from(n).map_err(|_| {
+                ParseError::InvalidFieldNumber(format!("{} is out of range", n), token.location)
+            })
+        }
+        Token::Identifier("max") if allow_max => Ok(MAX_FIELD_NUMBER),
+        _ => Err(ParseError::UnexpectedToken(
+            format!("Expected field number, found {:?}", token.token),
+            token.location,
+        )),
+    }
+}
+
+/// Parses a message definition from the token stream.
 ///
-/// It parses the enum name, opening brace, enum body (including enum values
-/// and options), and closing brace.
+/// This function expects the 'message' keyword to have already been consumed.
+/// It parses the message name, opening brace, message body (including nested messages,
+/// enums, fields, options, and reserved statements), and closing brace.
 ///
 /// # Arguments
 ///
 /// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+/// * `syntax` - The syntax of the file; `optional` only adds presence in proto3.
 ///
 /// # Returns
 ///
-/// * `Result<Enum, ParseError>` - A Result containing the parsed Enum on success,
+/// * `Result<Message, ParseError>` - A Result containing the parsed Message on success,
 ///   or a ParseError on failure.
-fn parse_enum<'a, I>(tokens: &mut Peekable<I>) -> Result<Enum, ParseError>
+fn parse_field<'a, I>(tokens: &mut Peekable<I>, syntax: &Syntax) -> Result<Field, ParseError>
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
-    // Expect 'enum' keyword
-    let enum_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
-    if enum_token.token != Token::Enum {
-        return Err(ParseError::UnexpectedToken(
-            format!("Expected 'enum', found {:?}", enum_token.token),
-            enum_token.location,
-        ));
-    }
+    skip_comments_and_whitespace(tokens);
 
-    // Parse enum name
-    let name_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(enum_token.location))?;
-    let name = match &name_token.token {
-        Token::Identifier(name) => name.to_string(),
-        _ => {
-            return Err(ParseError::UnexpectedToken(
-                format!("Expected enum name, found {:?}", name_token.token),
-                name_token.location,
-            ))
+    let start_span = tokens.peek().map(|t| t.span).unwrap_or_default();
+
+    // Parse field label (optional, repeated, required). In proto2, `optional`
+    // is the default label and does not add presence.
+    let proto3_optional = *syntax == Syntax::Proto3
+        && matches!(
+            tokens.peek(),
+            Some(TokenWithLocation {
+                token: Token::Optional,
+                ..
+            })
+        );
+    let label = match tokens.peek() {
+        Some(TokenWithLocation {
+            token: Token::Repeated,
+            ..
+        }) => {
+            tokens.next(); // Consume 'repeated'
+            FieldLabel::Repeated
+        }
+        Some(TokenWithLocation {
+            token: Token::Required,
+            ..
+        }) => {
+            tokens.next(); // Consume 'required'
+            FieldLabel::Required
         }
+        Some(TokenWithLocation {
+            token: Token::Optional,
+            ..
+        }) => {
+            tokens.next(); // Consume 'optional'
+            FieldLabel::Optional
+        }
+        _ => FieldLabel::Optional,
     };
 
-    // Expect opening brace
-    let open_brace_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(name_token.location))?;
-    if open_brace_token.token != Token::OpenBrace {
-        return Err(ParseError::UnexpectedToken(
-            format!("Expected '{{', found {:?}", open_brace_token.token),
-            open_brace_token.location,
-        ));
-    }
-
-    let mut enum_def = Enum::new(name);
+    skip_comments_and_whitespace(tokens);
 
-    while let Some(token_with_location) = tokens.peek() {
-        match &token_with_location.token {
-            Token::CloseBrace => {
-                tokens.next(); // Consume closing brace
-                return Ok(enum_def);
-            }
-            Token::Identifier(_) => {
-                // Parse enum value
-                let value = parse_enum_value(tokens)?;
-                enum_def.values.push(value);
-            }
-            Token::Option => {
-                let option = parse_enum_option(tokens)?;
-                enum_def.options.push(option);
-            }
-            _ => {
-                return Err(ParseError::UnexpectedToken(
-                    format!(
-                        "Unexpected token in enum body: {:?}",
-                        token_with_location.token
-                    ),
-                    token_with_location.location,
-                ));
-            }
-        }
-    }
+    let (typ, name) = if let Some(TokenWithLocation {
+        token: Token::Map, ..
+    }) = tokens.peek()
+    {
+        tokens.next(); // Consume 'map'
+        parse_map_field(tokens)?
+    } else {
+        // Parse field type
+        let typ = parse_qualified_field_type(tokens)?;
 
-    Err(ParseError::UnexpectedEndOfInput(open_brace_token.location))
-}
+        debug!("Parsed field type: {:?}", typ);
 
-fn parse_enum_option<'a, I>(tokens: &mut Peekable<I>) -> Result<EnumValueOption, ParseError>
-where
-    I: Iterator<Item = TokenWithLocation<'a>>,
-{
-    // Consume 'option' token
-    let option_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
-        .expect(Token::Option)?;
+        // Parse field name
+        let name = parse_field_name(tokens)?;
 
-    // Parse option name
-    let name_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(option_token.location))?;
-    let name = match &name_token.token {
-        Token::Identifier(s) => s.to_string(),
-        _ => {
-            return Err(ParseError::UnexpectedToken(
-                format!("Expected option name, found {:?}", name_token.token),
-                name_token.location,
-            ));
-        }
+        (typ, name)
     };
 
-    // Expect equals sign
+    // Expect '=' token
     tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(name_token.location))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Equals)?;
 
-    // Parse option value
-    let value_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(name_token.location))?;
-    let value = match &value_token.token {
-        Token::StringLiteral(s) => EnumValueOptionValue::String(s.to_string()),
-        Token::Identifier(s) => EnumValueOptionValue::Identifier(s.to_string()),
-        Token::DecimalIntLiteral(i) => EnumValueOptionValue::DecimalInt(*i),
-        Token::FloatLiteral(f) => EnumValueOptionValue::Float(*f),
+    // Parse field number
+    let number_token = tokens.next().ok_or_else(unexpected_end)?;
+    let number = match number_token.token {
+        Token::DecimalIntLiteral(num) => NumberValue::DecimalInt(num),
+        Token::FloatLiteral(num) => NumberValue::Float(num),
+        Token::HexIntLiteral(num) => NumberValue::Hex(num),
+        Token::OctalIntLiteral(num) => NumberValue::Octal(num),
         _ => {
             return Err(ParseError::UnexpectedToken(
-                format!("Expected option value, found {:?}", value_token.token),
-                value_token.location,
+                format!("Expected field number, found {:?}", number_token.token),
+                number_token.location,
             ));
         }
     };
 
+    // Parse options if present
+    let options = parse_field_options(tokens)?;
+
     // Expect semicolon
-    tokens
+    let semicolon_token = tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(value_token.location))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Semicolon)?;
 
-    Ok(EnumValueOption { name, value })
+    let (trailing, zod) = parse_trailing_comment(tokens, semicolon_token.location.line)?;
+
+    Ok(Field {
+        name,
+        label,
+        proto3_optional,
+        typ,
+        number,
+        number_span: number_token.span,
+        options,
+        zod,
+        comments: Comments {
+            trailing,
+            ..Comments::default()
+        },
+        span: start_span.to(semicolon_token.span),
+    })
 }
 
-/// Parses an enum value from the token stream.
+/// Parses an optional bracketed list of field options.
 ///
-/// This function expects to parse an enum value name, '=' token, and an integer value.
-/// It also handles optional enum value options enclosed in square brackets.
+/// Field options follow the field number, e.g.
+/// `[deprecated = true, (validate.rules).string.min_len = 3]`.
+/// If the next token is not '[', no tokens are consumed and an empty list is returned.
 ///
 /// # Arguments
 ///
@@ -813,30 +1258,256 @@ where
 ///
 /// # Returns
 ///
-/// * `Result<EnumValue, ParseError>` - A Result containing the parsed EnumValue on success,
+/// * `Result<Vec<ProtoOption>, ParseError>` - The parsed options on success,
 ///   or a ParseError on failure.
-fn parse_enum_value<'a, I>(tokens: &mut Peekable<I>) -> Result<EnumValue, ParseError>
+fn parse_field_options<'a, I>(tokens: &mut Peekable<I>) -> Result<Vec<ProtoOption>, ParseError>
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
-    // Parse enum value name
-    let name_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
+    let mut options = Vec::new();
+
+    let open_bracket_token = match tokens.peek() {
+        Some(TokenWithLocation {
+            token: Token::OpenBracket,
+            ..
+        }) => tokens.next().unwrap(),
+        _ => return Ok(options),
+    };
+
+    loop {
+        let start_span = tokens.peek().map_or(open_bracket_token.span, |t| t.span);
+        let name = parse_option_name(tokens)?;
+
+        tokens
+            .next()
+            .ok_or_else(unexpected_end)?
+            .expect(Token::Equals)?;
+
+        let (value, value_span) = parse_option_value(tokens)?;
+        options.push(ProtoOption {
+            span: start_span.to(value_span),
+            ..ProtoOption::new(name, value)
+        });
+
+        match tokens.next() {
+            Some(TokenWithLocation {
+                token: Token::Comma,
+                ..
+            }) => {}
+            Some(TokenWithLocation {
+                token: Token::CloseBracket,
+                ..
+            }) => break,
+            Some(t) => {
+                return Err(ParseError::UnexpectedToken(
+                    format!("Expected ',' or ']', found {:?}", t.token),
+                    t.location,
+                ))
+            }
+            None => return Err(unexpected_end()),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parses a field name from the token stream.
+///
+/// Keywords are only reserved where they start a statement, so a field may be
+/// named after any of them, e.g. `bool required = 1;` or `string to = 2;`.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+///
+/// # Returns
+///
+/// * `Result<String, ParseError>` - The parsed field name on success, or a ParseError on failure.
+///
+/// # Errors
+///
+/// Returns a ParseError if:
+/// - Unexpected end of input is encountered
+/// - The next token is neither an identifier nor a keyword
+fn parse_field_name<'a, I>(tokens: &mut Peekable<I>) -> Result<String, ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let token = tokens.peek().ok_or_else(unexpected_end)?;
+    debug!("Parsing field name, current token: {:?}", token.token);
+    match name_part(&token.token) {
+        Some(name) => {
+            tokens.next(); // Consume the name
+            Ok(name)
+        }
+        None => Err(ParseError::MissingIdentifier(
+            "Expected field name".to_string(),
+            token.location,
+        )),
+    }
+}
+
+/// Parses an enum definition from the token stream.
+///
+/// It parses the enum name, opening brace, enum body (including enum values
+/// and options), and closing brace.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+///
+/// # Returns
+///
+/// * `Result<Enum, ParseError>` - A Result containing the parsed Enum on success,
+///   or a ParseError on failure.
+fn parse_enum<'a, I>(tokens: &mut Peekable<I>, recovery: &mut Recovery) -> Result<Enum, ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
+{
+    // Expect 'enum' keyword
+    let enum_token = tokens.next().ok_or_else(unexpected_end)?;
+    if enum_token.token != Token::Enum {
+        return Err(ParseError::UnexpectedToken(
+            format!("Expected 'enum', found {:?}", enum_token.token),
+            enum_token.location,
+        ));
+    }
+
+    // Parse enum name
+    let name_token = tokens.next().ok_or_else(unexpected_end)?;
     let name = match &name_token.token {
         Token::Identifier(name) => name.to_string(),
         _ => {
             return Err(ParseError::UnexpectedToken(
-                format!("Expected enum value name, found {:?}", name_token.token),
+                format!("Expected enum name, found {:?}", name_token.token),
                 name_token.location,
             ))
         }
     };
 
+    let zod = parse_leading_annotation(tokens)?;
+
+    // Expect opening brace
+    let open_brace_token = tokens.next().ok_or_else(unexpected_end)?;
+    if open_brace_token.token != Token::OpenBrace {
+        return Err(ParseError::UnexpectedToken(
+            format!("Expected '{{', found {:?}", open_brace_token.token),
+            open_brace_token.location,
+        ));
+    }
+
+    let (trailing, trailing_zod) = parse_trailing_comment(tokens, open_brace_token.location.line)?;
+
+    let mut enum_def = Enum::new(name);
+    enum_def.zod = zod.or(trailing_zod);
+    enum_def.comments.trailing = trailing;
+
+    loop {
+        let comments = parse_leading_comments(tokens);
+        let Some(token_with_location) = tokens.peek() else {
+            break;
+        };
+
+        match &token_with_location.token {
+            Token::CloseBrace => {
+                let line = token_with_location.location.line;
+                enum_def.span = enum_token.span.to(token_with_location.span);
+                tokens.next(); // Consume closing brace
+                take_trailing_comment(tokens, line);
+                return Ok(enum_def);
+            }
+            _ => {
+                recovery.statement(tokens, |tokens, _| {
+                    let token_with_location = tokens.peek().expect("peeked above");
+                    match &token_with_location.token {
+                        Token::Option => {
+                            let option_token = tokens.next().expect("peeked above");
+                            if let Some(TokenWithLocation {
+                                token: Token::Equals,
+                                ..
+                            }) = tokens.peek()
+                            {
+                                // A value named `option`, e.g. `option = 1;`
+                                let mut value = parse_enum_value_body(tokens, option_token)?;
+                                attach_leading_comments(&mut value.comments, comments);
+                                enum_def.values.push(value);
+                            } else {
+                                parse_option_body(tokens, option_token, &mut enum_def.options)?;
+                            }
+                        }
+                        t if name_part(t).is_some() => {
+                            // Parse enum value
+                            let mut value = parse_enum_value(tokens)?;
+                            attach_leading_comments(&mut value.comments, comments);
+                            enum_def.values.push(value);
+                        }
+                        _ => {
+                            return Err(ParseError::UnexpectedToken(
+                                format!(
+                                    "Unexpected token in enum body: {:?}",
+                                    token_with_location.token
+                                ),
+                                token_with_location.location,
+                            ));
+                        }
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+    }
+
+    Err(unexpected_end())
+}
+
+/// Parses an enum value from the token stream.
+///
+/// This function expects to parse an enum value name, '=' token, and an integer value.
+/// It also handles optional enum value options enclosed in square brackets.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+///
+/// # Returns
+///
+/// * `Result<EnumValue, ParseError>` - A Result containing the parsed EnumValue on success,
+///   or a ParseError on failure.
+fn parse_enum_value<'a, I>(tokens: &mut Peekable<I>) -> Result<EnumValue, ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let name_token = tokens.next().ok_or_else(unexpected_end)?;
+    parse_enum_value_body(tokens, name_token)
+}
+
+/// Parses the rest of an enum value after its name, which may be a keyword.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+/// * `name_token` - The already consumed name of the value.
+///
+/// # Returns
+///
+/// * `Result<EnumValue, ParseError>` - A Result containing the parsed EnumValue on success,
+///   or a ParseError on failure.
+fn parse_enum_value_body<'a, I>(
+    tokens: &mut Peekable<I>,
+    name_token: TokenWithLocation<'a>,
+) -> Result<EnumValue, ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let name = name_part(&name_token.token).ok_or_else(|| {
+        ParseError::UnexpectedToken(
+            format!("Expected enum value name, found {:?}", name_token.token),
+            name_token.location,
+        )
+    })?;
+
     // Expect '='
-    let equals_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(name_token.location))?;
+    let equals_token = tokens.next().ok_or_else(unexpected_end)?;
     if equals_token.token != Token::Equals {
         return Err(ParseError::UnexpectedToken(
             format!("Expected '=', found {:?}", equals_token.token),
@@ -845,9 +1516,7 @@ where
     }
 
     // Parse enum value number
-    let number_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(equals_token.location))?;
+    let number_token = tokens.next().ok_or_else(unexpected_end)?;
     let number = match &number_token.token {
         Token::DecimalIntLiteral(num) => NumberValue::DecimalInt(*num),
         Token::FloatLiteral(num) => NumberValue::Float(*num),
@@ -861,47 +1530,11 @@ where
         }
     };
 
-    // Parse options if present
-    let mut options = Vec::new();
-    if let Some(TokenWithLocation {
-        token: Token::OpenBracket,
-        ..
-    }) = tokens.peek()
-    {
-        tokens.next(); // Consume '['
-        loop {
-            let option = parse_enum_value_option(tokens)?;
-            options.push(option);
-
-            match tokens.peek() {
-                Some(TokenWithLocation {
-                    token: Token::Comma,
-                    ..
-                }) => {
-                    tokens.next(); // Consume comma
-                }
-                Some(TokenWithLocation {
-                    token: Token::CloseBracket,
-                    ..
-                }) => {
-                    tokens.next(); // Consume closing bracket
-                    break;
-                }
-                Some(t) => {
-                    return Err(ParseError::UnexpectedToken(
-                        format!("Expected ',' or ']', found {:?}", t.token),
-                        t.location,
-                    ))
-                }
-                None => return Err(ParseError::UnexpectedEndOfInput(number_token.location)),
-            }
-        }
-    }
+    // Parse options if present; they are written like field options
+    let options = parse_field_options(tokens)?;
 
     // Expect semicolon
-    let semicolon_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(number_token.location))?;
+    let semicolon_token = tokens.next().ok_or_else(unexpected_end)?;
     if semicolon_token.token != Token::Semicolon {
         return Err(ParseError::UnexpectedToken(
             format!("Expected ';', found {:?}", semicolon_token.token),
@@ -909,72 +1542,33 @@ where
         ));
     }
 
+    let trailing = take_trailing_comment(tokens, semicolon_token.location.line);
+
     Ok(EnumValue {
         name,
         number,
         options,
+        comments: Comments {
+            trailing: trailing.map(|(comment, _)| comment_text(comment)),
+            ..Comments::default()
+        },
+        span: name_token.span.to(semicolon_token.span),
     })
 }
 
-fn parse_enum_value_option<'a, I>(tokens: &mut Peekable<I>) -> Result<EnumValueOption, ParseError>
-where
-    I: Iterator<Item = TokenWithLocation<'a>>,
-{
-    // Parse option name
-    let name_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
-    let name = match &name_token.token {
-        Token::Identifier(name) => name.to_string(),
-        _ => {
-            return Err(ParseError::UnexpectedToken(
-                format!("Expected option name, found {:?}", name_token.token),
-                name_token.location,
-            ))
-        }
-    };
-
-    // Expect '='
-    let equals_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(name_token.location))?;
-    if equals_token.token != Token::Equals {
-        return Err(ParseError::UnexpectedToken(
-            format!("Expected '=', found {:?}", equals_token.token),
-            equals_token.location,
-        ));
-    }
-
-    // Parse option value
-    let value_token = tokens
-        .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(equals_token.location))?;
-    let value = match &value_token.token {
-        Token::StringLiteral(s) => EnumValueOptionValue::String(s.to_string()),
-        Token::Identifier(s) => EnumValueOptionValue::Identifier(s.to_string()),
-        Token::DecimalIntLiteral(i) => EnumValueOptionValue::DecimalInt(*i),
-        Token::FloatLiteral(f) => EnumValueOptionValue::Float(*f),
-        _ => {
-            return Err(ParseError::UnexpectedToken(
-                format!("Expected option value, found {:?}", value_token.token),
-                value_token.location,
-            ))
-        }
-    };
-
-    Ok(EnumValueOption { name, value })
-}
-
-fn parse_service<'a, I>(tokens: &mut Peekable<I>) -> Result<Service, ParseError>
+fn parse_service<'a, I>(
+    tokens: &mut Peekable<I>,
+    recovery: &mut Recovery,
+) -> Result<Service, ParseError>
 where
-    I: Iterator<Item = TokenWithLocation<'a>>,
+    I: Iterator<Item = TokenWithLocation<'a>> + Clone,
 {
     skip_comments_and_whitespace(tokens);
 
     // Expect 'service' keyword
-    tokens
+    let service_token = tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Service)?;
 
     skip_comments_and_whitespace(tokens);
@@ -985,36 +1579,29 @@ where
     skip_comments_and_whitespace(tokens);
 
     // Expect opening brace
-    tokens
+    let open_brace_token = tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::OpenBrace)?;
 
+    let trailing = take_trailing_comment(tokens, open_brace_token.location.line);
+
     let mut methods = Vec::new();
     let mut options = Vec::new();
 
-    loop {
-        skip_comments_and_whitespace(tokens);
+    let close_brace_span = loop {
+        let comments = parse_leading_comments(tokens);
 
         match tokens.peek() {
             Some(TokenWithLocation {
                 token: Token::CloseBrace,
-                ..
+                location,
+                span,
             }) => {
+                let (line, span) = (location.line, *span);
                 tokens.next(); // Consume '}'
-                break;
-            }
-            Some(TokenWithLocation {
-                token: Token::Rpc, ..
-            }) => {
-                let method = parse_method(tokens)?;
-                methods.push(method);
-            }
-            Some(TokenWithLocation {
-                token: Token::Option,
-                ..
-            }) => {
-                parse_option(tokens, &mut options)?;
+                take_trailing_comment(tokens, line);
+                break span;
             }
             Some(TokenWithLocation {
                 token: Token::Comment(_),
@@ -1022,20 +1609,37 @@ where
             }) => {
                 tokens.next(); // Skip comments
             }
-            Some(t) => {
-                return Err(ParseError::UnexpectedToken(
-                    format!("Unexpected token in service body: {:?}", t.token),
-                    t.location,
-                ))
+            Some(_) => {
+                recovery.statement(tokens, |tokens, _| {
+                    let t = tokens.peek().expect("peeked above");
+                    match &t.token {
+                        Token::Rpc => {
+                            let mut method = parse_method(tokens)?;
+                            attach_leading_comments(&mut method.comments, comments);
+                            methods.push(method);
+                            Ok(())
+                        }
+                        Token::Option => parse_option(tokens, &mut options),
+                        _ => Err(ParseError::UnexpectedToken(
+                            format!("Unexpected token in service body: {:?}", t.token),
+                            t.location,
+                        )),
+                    }
+                })?;
             }
-            None => return Err(ParseError::UnexpectedEndOfInput(Location::new(0, 0))),
+            None => return Err(unexpected_end()),
         }
-    }
+    };
 
     Ok(Service {
         name,
         methods,
         options,
+        comments: Comments {
+            trailing: trailing.map(|(comment, _)| comment_text(comment)),
+            ..Comments::default()
+        },
+        span: service_token.span.to(close_brace_span),
     })
 }
 
@@ -1046,9 +1650,9 @@ where
     skip_comments_and_whitespace(tokens);
 
     // Expect 'rpc' keyword
-    tokens
+    let rpc_token = tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Rpc)?;
 
     skip_comments_and_whitespace(tokens);
@@ -1061,7 +1665,7 @@ where
     // Expect opening parenthesis
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::OpenParen)?;
 
     skip_comments_and_whitespace(tokens);
@@ -1086,7 +1690,7 @@ where
     // Expect closing parenthesis
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::CloseParen)?;
 
     skip_comments_and_whitespace(tokens);
@@ -1094,7 +1698,7 @@ where
     // Expect 'returns' keyword
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Returns)?;
 
     skip_comments_and_whitespace(tokens);
@@ -1102,7 +1706,7 @@ where
     // Expect opening parenthesis
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::OpenParen)?;
 
     skip_comments_and_whitespace(tokens);
@@ -1127,24 +1731,32 @@ where
     // Expect closing parenthesis
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::CloseParen)?;
 
     skip_comments_and_whitespace(tokens);
 
     let mut options = Vec::new();
+    let trailing;
+    let mut end_span = Span::default();
 
     // Check for options or semicolon
     match tokens.peek() {
         Some(TokenWithLocation {
             token: Token::OpenBrace,
+            location,
             ..
         }) => {
+            let line = location.line;
             tokens.next(); // Consume '{'
+            trailing = take_trailing_comment(tokens, line);
             while let Some(token) = tokens.peek() {
                 match &token.token {
                     Token::CloseBrace => {
+                        let line = token.location.line;
+                        end_span = token.span;
                         tokens.next(); // Consume '}'
+                        take_trailing_comment(tokens, line);
                         break;
                     }
                     Token::Option => {
@@ -1165,9 +1777,13 @@ where
         }
         Some(TokenWithLocation {
             token: Token::Semicolon,
-            ..
+            location,
+            span,
         }) => {
+            let line = location.line;
+            end_span = *span;
             tokens.next(); // Consume ';'
+            trailing = take_trailing_comment(tokens, line);
         }
         Some(t) => {
             return Err(ParseError::UnexpectedToken(
@@ -1178,7 +1794,7 @@ where
                 t.location,
             ))
         }
-        None => return Err(ParseError::UnexpectedEndOfInput(Location::new(0, 0))),
+        None => return Err(unexpected_end()),
     }
 
     Ok(Method {
@@ -1188,6 +1804,11 @@ where
         client_streaming,
         server_streaming,
         options,
+        comments: Comments {
+            trailing: trailing.map(|(comment, _)| comment_text(comment)),
+            ..Comments::default()
+        },
+        span: rpc_token.span.to(end_span),
     })
 }
 
@@ -1201,86 +1822,391 @@ where
     // Consume 'option' token
     let option_token = tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Option)?;
+    parse_option_body(tokens, option_token, options)
+}
 
-    // Parse option name (which may include dots)
-    let name = parse_dotted_identifier(tokens)?;
+/// Parses the rest of an option statement after the `option` keyword.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+/// * `option_token` - The already consumed `option` keyword.
+/// * `options` - The options to which the parsed option is added.
+fn parse_option_body<'a, I>(
+    tokens: &mut Peekable<I>,
+    option_token: TokenWithLocation<'a>,
+    options: &mut Vec<ProtoOption>,
+) -> Result<(), ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    // Parse option name (which may include dots and parenthesized extensions)
+    let name = parse_option_name(tokens)?;
 
     // Expect equals sign
     tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(option_token.location))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Equals)?;
 
     // Parse option value
-    let value = parse_option_value(tokens)?;
+    let (value, _) = parse_option_value(tokens)?;
 
     // Expect semicolon
-    tokens
+    let semicolon_token = tokens
         .next()
-        .ok_or(ParseError::UnexpectedEndOfInput(option_token.location))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Semicolon)?;
+    take_trailing_comment(tokens, semicolon_token.location.line);
 
-    options.push(ProtoOption::new(name, value));
+    options.push(ProtoOption {
+        span: option_token.span.to(semicolon_token.span),
+        ..ProtoOption::new(name, value)
+    });
 
     Ok(())
 }
 
-fn parse_dotted_identifier<'a, I>(tokens: &mut Peekable<I>) -> Result<String, ParseError>
+/// Parses an option name, which may contain parenthesized extension names,
+/// e.g. `deprecated`, `(my_option)` or `(validate.rules).string.min_len`.
+///
+/// Each dot-separated component becomes one [`OptionNamePart`]; the dots inside
+/// parentheses belong to the extension name and are kept as part of it.
+fn parse_option_name<'a, I>(tokens: &mut Peekable<I>) -> Result<OptionName, ParseError>
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
     let mut parts = Vec::new();
 
-    while let Some(token) = tokens.peek() {
+    loop {
+        let token = tokens.next().ok_or_else(unexpected_end)?;
+
         match &token.token {
-            Token::Identifier(s) => {
-                parts.push(s.to_string());
-                tokens.next(); // Consume the identifier
-            }
-            Token::Dot => {
-                if parts.is_empty() {
-                    return Err(ParseError::UnexpectedToken(
-                        "Unexpected dot at the beginning of identifier".to_string(),
-                        token.location,
-                    ));
+            Token::OpenParen => {
+                let mut name = String::new();
+                loop {
+                    let part = tokens.next().ok_or_else(unexpected_end)?;
+                    match &part.token {
+                        Token::CloseParen => break,
+                        Token::Dot => name.push('.'),
+                        t => match name_part(t) {
+                            Some(s) => name.push_str(&s),
+                            None => {
+                                return Err(ParseError::UnexpectedToken(
+                                    format!("Unexpected token in option name: {:?}", t),
+                                    part.location,
+                                ))
+                            }
+                        },
+                    }
+                }
+                if name.is_empty() {
+                    return Err(ParseError::MissingIdentifier(
+                        "Expected extension name inside parentheses".to_string(),
+                        token.location,
+                    ));
+                }
+                parts.push(OptionNamePart::Extension(name));
+            }
+            t => match name_part(t) {
+                Some(s) => parts.push(OptionNamePart::Field(s)),
+                None => {
+                    return Err(ParseError::UnexpectedToken(
+                        format!("Expected option name, found {:?}", t),
+                        token.location,
+                    ))
                 }
+            },
+        }
+
+        match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::Dot, ..
+            }) => {
                 tokens.next(); // Consume the dot
             }
             _ => break,
         }
     }
 
-    if parts.is_empty() {
-        return Err(ParseError::UnexpectedEndOfInput(
-            tokens.peek().map_or(Location::new(0, 0), |t| t.location),
-        ));
-    }
+    Ok(OptionName::new(parts))
+}
 
-    Ok(parts.join("."))
+/// Returns the text of a token that may appear as part of a dotted name.
+///
+/// Keywords are valid name parts in option and type names (e.g. `string` in
+/// `(validate.rules).string.min_len`), so they are accepted alongside identifiers.
+fn name_part(token: &Token) -> Option<String> {
+    match token {
+        Token::Identifier(s) => Some(s.to_string()),
+        Token::Syntax
+        | Token::Proto2
+        | Token::Proto3
+        | Token::Import
+        | Token::Package
+        | Token::Message
+        | Token::Enum
+        | Token::Service
+        | Token::Rpc
+        | Token::Returns
+        | Token::Option
+        | Token::Repeated
+        | Token::Oneof
+        | Token::Map
+        | Token::Reserved
+        | Token::To
+        | Token::Weak
+        | Token::Stream
+        | Token::Public
+        | Token::Extensions
+        | Token::Extend
+        | Token::Optional
+        | Token::Required
+        | Token::StringType => Some(token.to_string()),
+        _ => None,
+    }
 }
 
-fn parse_option_value<'a, I>(tokens: &mut Peekable<I>) -> Result<OptionValue, ParseError>
+/// Parses an option value from the token stream.
+///
+/// Besides scalar constants this accepts `true`/`false`, `inf`/`nan` (optionally
+/// negated), adjacent string literals (which are concatenated), lists in square
+/// brackets and protobuf text-format aggregates in braces or angle brackets,
+/// e.g. `{ get: "/v1/x" additional_bindings { post: "/v1/y" } }`.
+///
+/// # Arguments
+///
+/// * `tokens` - A mutable reference to a peekable iterator of TokenWithLocation.
+///
+/// # Returns
+///
+/// * `Result<(OptionValue, Span), ParseError>` - The parsed value and its source range
+///   on success, or a ParseError on failure.
+fn parse_option_value<'a, I>(tokens: &mut Peekable<I>) -> Result<(OptionValue, Span), ParseError>
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
-    let value_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
-
-    match &value_token.token {
-        Token::StringLiteral(s) => Ok(OptionValue::String(s.to_string())),
-        Token::Identifier(s) => Ok(OptionValue::Identifier(s.to_string())),
-        Token::DecimalIntLiteral(num) => Ok(OptionValue::DecimalInt(*num)),
-        Token::OctalIntLiteral(num) => Ok(OptionValue::Octal(*num)),
-        Token::HexIntLiteral(num) => Ok(OptionValue::Hex(*num)),
-        Token::FloatLiteral(f) => Ok(OptionValue::Float(*f)),
-        _ => Err(ParseError::UnexpectedToken(
-            format!("Expected option value, found {:?}", value_token.token),
-            value_token.location,
+    let value_token = tokens.next().ok_or_else(unexpected_end)?;
+    let span = value_token.span;
+
+    let value = match &value_token.token {
+        Token::StringLiteral(s) => {
+            let mut value = s.to_string();
+            let mut span = span;
+            while let Some(TokenWithLocation {
+                token: Token::StringLiteral(next),
+                span: next_span,
+                ..
+            }) = tokens.peek()
+            {
+                value.push_str(next);
+                span = span.to(*next_span);
+                tokens.next(); // Consume the adjacent string literal
+            }
+            return Ok((OptionValue::String(value), span));
+        }
+        Token::Identifier("true") => OptionValue::Bool(true),
+        Token::Identifier("false") => OptionValue::Bool(false),
+        Token::Identifier("inf") | Token::Identifier("infinity") => {
+            OptionValue::Float(f64::INFINITY)
+        }
+        Token::Identifier("nan") => OptionValue::Float(f64::NAN),
+        Token::Identifier(s) => OptionValue::Identifier(s.to_string()),
+        Token::DecimalIntLiteral(num) => OptionValue::DecimalInt(*num),
+        Token::OctalIntLiteral(num) => OptionValue::Octal(*num),
+        Token::HexIntLiteral(num) => OptionValue::Hex(*num),
+        Token::FloatLiteral(f) => OptionValue::Float(*f),
+        Token::Minus => {
+            let (value, value_span) = parse_option_value(tokens)?;
+            let negated = match value {
+                OptionValue::DecimalInt(num) => OptionValue::DecimalInt(-num),
+                OptionValue::Octal(num) => OptionValue::Octal(-num),
+                OptionValue::Hex(num) => OptionValue::Hex(-num),
+                OptionValue::Float(f) => OptionValue::Float(-f),
+                other => {
+                    return Err(ParseError::InvalidSyntax(
+                        format!("Cannot negate option value {:?}", other),
+                        value_token.location,
+                    ))
+                }
+            };
+            return Ok((negated, span.to(value_span)));
+        }
+        Token::OpenBrace => return parse_aggregate_value(tokens, &value_token, Token::CloseBrace),
+        Token::LessThan => return parse_aggregate_value(tokens, &value_token, Token::GreaterThan),
+        Token::OpenBracket => return parse_list_value(tokens, &value_token),
+        _ => {
+            return Err(ParseError::UnexpectedToken(
+                format!("Expected option value, found {:?}", value_token.token),
+                value_token.location,
+            ))
+        }
+    };
+
+    Ok((value, span))
+}
+
+/// Parses a complete option value from its text, e.g. the aggregate value of
+/// an uninterpreted option in a descriptor.
+///
+/// # Arguments
+///
+/// * `text` - The value, e.g. `{ min: 1 }`
+///
+/// # Returns
+///
+/// * `Result<OptionValue, ParseError>` - The parsed value, or a ParseError if
+///   the text is not a single valid value.
+pub(crate) fn parse_option_value_text(text: &str) -> Result<OptionValue, ParseError> {
+    let tokens = tokenize(text)?;
+    let end = end_of_input(&tokens);
+    let mut tokens = tokens
+        .into_iter()
+        .filter(|t| !matches!(t.token, Token::Comment(_)))
+        .peekable();
+    let (value, _) = parse_option_value(&mut tokens).map_err(|e| locate_end_of_input(e, end))?;
+    match tokens.next() {
+        Some(extra) => Err(ParseError::UnexpectedToken(
+            format!("Unexpected {:?} after option value", extra.token),
+            extra.location,
         )),
+        None => Ok(value),
+    }
+}
+
+/// Parses the body of a text-format aggregate value up to the closing delimiter.
+///
+/// Fields are written as `name: value` or, for message values, `name { ... }`,
+/// and may be separated by commas or semicolons. Extension fields are written
+/// as `[full.name]`.
+fn parse_aggregate_value<'a, I>(
+    tokens: &mut Peekable<I>,
+    open_token: &TokenWithLocation<'a>,
+    close: Token,
+) -> Result<(OptionValue, Span), ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let mut fields = Vec::new();
+
+    let close_span = loop {
+        skip_comments_and_whitespace(tokens);
+
+        let token = tokens.next().ok_or_else(unexpected_end)?;
+
+        let name = match &token.token {
+            t if *t == close => break token.span,
+            Token::Comma | Token::Semicolon => continue,
+            Token::OpenBracket => {
+                let mut name = String::from("[");
+                loop {
+                    let part = tokens.next().ok_or_else(unexpected_end)?;
+                    match &part.token {
+                        Token::CloseBracket => break,
+                        Token::Dot => name.push('.'),
+                        Token::Slash => name.push('/'),
+                        t => match name_part(t) {
+                            Some(s) => name.push_str(&s),
+                            None => {
+                                return Err(ParseError::UnexpectedToken(
+                                    format!("Unexpected token in extension name: {:?}", t),
+                                    part.location,
+                                ))
+                            }
+                        },
+                    }
+                }
+                name.push(']');
+                name
+            }
+            t => name_part(t).ok_or_else(|| {
+                ParseError::UnexpectedToken(
+                    format!("Expected field name in aggregate value, found {:?}", t),
+                    token.location,
+                )
+            })?,
+        };
+
+        skip_comments_and_whitespace(tokens);
+
+        let value = match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::Colon,
+                ..
+            }) => {
+                tokens.next(); // Consume the colon
+                skip_comments_and_whitespace(tokens);
+                parse_option_value(tokens)?.0
+            }
+            Some(TokenWithLocation {
+                token: Token::OpenBrace | Token::LessThan | Token::OpenBracket,
+                ..
+            }) => parse_option_value(tokens)?.0,
+            Some(t) => {
+                return Err(ParseError::UnexpectedToken(
+                    format!("Expected ':' or '{{' after '{}', found {:?}", name, t.token),
+                    t.location,
+                ))
+            }
+            None => return Err(unexpected_end()),
+        };
+
+        fields.push((name, value));
+    };
+
+    Ok((OptionValue::Message(fields), open_token.span.to(close_span)))
+}
+
+/// Parses a comma-separated list value up to the closing ']'.
+fn parse_list_value<'a, I>(
+    tokens: &mut Peekable<I>,
+    open_token: &TokenWithLocation<'a>,
+) -> Result<(OptionValue, Span), ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let mut values = Vec::new();
+
+    skip_comments_and_whitespace(tokens);
+    if let Some(TokenWithLocation {
+        token: Token::CloseBracket,
+        span,
+        ..
+    }) = tokens.peek()
+    {
+        let span = open_token.span.to(*span);
+        tokens.next(); // Consume ']'
+        return Ok((OptionValue::List(values), span));
     }
+
+    let close_span = loop {
+        skip_comments_and_whitespace(tokens);
+        values.push(parse_option_value(tokens)?.0);
+        skip_comments_and_whitespace(tokens);
+
+        match tokens.next() {
+            Some(TokenWithLocation {
+                token: Token::Comma,
+                ..
+            }) => {}
+            Some(TokenWithLocation {
+                token: Token::CloseBracket,
+                span,
+                ..
+            }) => break span,
+            Some(t) => {
+                return Err(ParseError::UnexpectedToken(
+                    format!("Expected ',' or ']' in list value, found {:?}", t.token),
+                    t.location,
+                ))
+            }
+            None => return Err(unexpected_end()),
+        }
+    };
+
+    Ok((OptionValue::List(values), open_token.span.to(close_span)))
 }
 
 fn parse_field_type(token: &TokenWithLocation) -> Result<FieldType, ParseError> {
@@ -1310,13 +2236,67 @@ fn parse_field_type(token: &TokenWithLocation) -> Result<FieldType, ParseError>
     }
 }
 
+/// Parses a field type that may be a dotted message or enum name.
+///
+/// Scalar types are a single token and are handled by `parse_field_type`.
+/// Message and enum references may be qualified (`google.protobuf.Timestamp`)
+/// or fully-qualified with a leading dot (`.example.Outer.Inner`); the dots are
+/// kept in the resulting `FieldType::MessageOrEnum` name.
+fn parse_qualified_field_type<'a, I>(tokens: &mut Peekable<I>) -> Result<FieldType, ParseError>
+where
+    I: Iterator<Item = TokenWithLocation<'a>>,
+{
+    let first_token = tokens.next().ok_or_else(unexpected_end)?;
+
+    let mut name = String::new();
+    let mut part_token = if first_token.token == Token::Dot {
+        name.push('.');
+        tokens.next().ok_or_else(unexpected_end)?
+    } else {
+        let is_qualified = matches!(
+            tokens.peek(),
+            Some(TokenWithLocation {
+                token: Token::Dot,
+                ..
+            })
+        );
+        if !is_qualified {
+            return parse_field_type(&first_token);
+        }
+        first_token
+    };
+
+    loop {
+        match name_part(&part_token.token) {
+            Some(part) => name.push_str(&part),
+            None => {
+                return Err(ParseError::UnexpectedToken(
+                    format!("Unexpected token in type name: {:?}", part_token.token),
+                    part_token.location,
+                ))
+            }
+        }
+
+        match tokens.peek() {
+            Some(TokenWithLocation {
+                token: Token::Dot, ..
+            }) => {
+                tokens.next(); // Consume the dot
+                name.push('.');
+                part_token = tokens.next().ok_or_else(unexpected_end)?;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(FieldType::MessageOrEnum(name))
+}
+
 fn parse_identifier<'a, I>(tokens: &mut Peekable<I>) -> Result<String, ParseError>
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
-    let token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
+    let token = tokens.next().ok_or_else(unexpected_end)?;
 
     match token.token {
         Token::Identifier(s) => Ok(s.to_string()),
@@ -1332,45 +2312,18 @@ where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
     let mut type_name = String::new();
-    let mut first = true;
 
     loop {
         skip_comments_and_whitespace(tokens);
 
         match tokens.peek() {
-            Some(TokenWithLocation {
-                token: Token::Identifier(s),
-                ..
-            }) => {
-                if !first {
-                    type_name.push('.');
-                }
-                type_name.push_str(s);
-                first = false;
-                tokens.next(); // Consume the identifier
-            }
             Some(TokenWithLocation {
                 token: Token::Dot, ..
             }) => {
-                if first {
-                    return Err(ParseError::UnexpectedToken(
-                        "Unexpected dot at the beginning of type name".to_string(),
-                        tokens.peek().unwrap().location,
-                    ));
-                }
+                // A leading dot makes the name fully-qualified
                 type_name.push('.');
                 tokens.next(); // Consume the dot
             }
-            Some(TokenWithLocation {
-                token: Token::Rpc, ..
-            }) => {
-                // Special case: 'rpc' is part of the type name (e.g., google.rpc.Status)
-                if !first {
-                    type_name.push('.');
-                }
-                type_name.push_str("rpc");
-                tokens.next(); // Consume the 'rpc' token
-            }
             Some(TokenWithLocation {
                 token: Token::CloseParen,
                 ..
@@ -1379,19 +2332,31 @@ where
                 // End of type name
                 break;
             }
-            Some(t) => {
-                return Err(ParseError::UnexpectedToken(
-                    format!("Unexpected token in type name: {:?}", t.token),
-                    t.location,
-                ));
-            }
+            Some(t) => match name_part(&t.token) {
+                // Keywords are valid name parts, e.g. `google.rpc.Status`
+                Some(part) => {
+                    type_name.push_str(&part);
+                    tokens.next(); // Consume the name part
+                }
+                None => {
+                    return Err(ParseError::UnexpectedToken(
+                        format!("Unexpected token in type name: {:?}", t.token),
+                        t.location,
+                    ));
+                }
+            },
         }
     }
 
-    if type_name.is_empty() {
-        Err(ParseError::UnexpectedEndOfInput(Location::new(0, 0)))
-    } else {
-        Ok(type_name)
+    if !type_name.is_empty() {
+        return Ok(type_name);
+    }
+    match tokens.peek() {
+        Some(t) => Err(ParseError::MissingIdentifier(
+            "type name".to_string(),
+            t.location,
+        )),
+        None => Err(unexpected_end()),
     }
 }
 
@@ -1422,31 +2387,26 @@ where
     // Expect '<'
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::LessThan)?;
 
     // Parse key type
-    let key_type_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
+    let key_type_token = tokens.next().ok_or_else(unexpected_end)?;
     let key_type = parse_field_type(&key_type_token)?;
 
     // Expect ','
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Comma)?;
 
     // Parse value type
-    let value_type_token = tokens
-        .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?;
-    let value_type = parse_field_type(&value_type_token)?;
+    let value_type = parse_qualified_field_type(tokens)?;
 
     // Expect '>'
     tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::GreaterThan)?;
 
     // Parse field name
@@ -1460,56 +2420,61 @@ where
 
 fn parse_reserved<'a, I>(
     tokens: &mut Peekable<I>,
-    reserved: &mut Vec<crate::parser::ast::Reserved>,
+    reserved: &mut Vec<Reserved>,
 ) -> Result<(), ParseError>
 where
     I: Iterator<Item = TokenWithLocation<'a>>,
 {
     // Consume 'reserved' token
-    let reserved_token = tokens
+    tokens
         .next()
-        .ok_or_else(|| ParseError::UnexpectedEndOfInput(Location::new(0, 0)))?
+        .ok_or_else(unexpected_end)?
         .expect(Token::Reserved)?;
 
-    let mut last_location = reserved_token.location;
-
     loop {
         match tokens.next() {
             Some(token_with_location) => {
                 match token_with_location.token {
-                    Token::DecimalIntLiteral(start) => {
-                        let start = start as i32;
+                    Token::DecimalIntLiteral(_)
+                    | Token::HexIntLiteral(_)
+                    | Token::OctalIntLiteral(_) => {
+                        let start = range_bound(&token_with_location, false)?;
                         if let Some(TokenWithLocation {
                             token: Token::To, ..
                         }) = tokens.peek()
                         {
                             tokens.next(); // Consume 'to' token
-                            if let Some(TokenWithLocation {
-                                token: Token::DecimalIntLiteral(end),
-                                ..
-                            }) = tokens.next()
-                            {
-                                let end = end as i32;
-                                if start <= end {
-                                    reserved.push(crate::parser::ast::Reserved::Range(start, end));
-                                } else {
-                                    return Err(ParseError::InvalidRange(
-                                        start,
-                                        end,
-                                        token_with_location.location,
-                                    ));
-                                }
+                            let end_token = tokens.next().ok_or_else(unexpected_end)?;
+                            let end = range_bound(&end_token, true)?;
+                            if start <= end {
+                                reserved.push(Reserved {
+                                    kind: ReservedKind::Range(start, end),
+                                    span: token_with_location.span.to(end_token.span),
+                                });
                             } else {
-                                return Err(ParseError::UnexpectedEndOfInput(last_location));
+                                return Err(ParseError::InvalidRange(
+                                    start,
+                                    end,
+                                    token_with_location.location,
+                                ));
                             }
                         } else {
-                            reserved.push(crate::parser::ast::Reserved::Number(start));
+                            reserved.push(Reserved {
+                                kind: ReservedKind::Number(start),
+                                span: token_with_location.span,
+                            });
                         }
                     }
                     Token::StringLiteral(name) => {
-                        reserved.push(crate::parser::ast::Reserved::FieldName(name.to_string()));
+                        reserved.push(Reserved {
+                            kind: ReservedKind::FieldName(name.to_string()),
+                            span: token_with_location.span,
+                        });
+                    }
+                    Token::Semicolon => {
+                        take_trailing_comment(tokens, token_with_location.location.line);
+                        break;
                     }
-                    Token::Semicolon => break,
                     Token::Comma => continue,
                     _ => {
                         return Err(ParseError::UnexpectedToken(
@@ -1521,9 +2486,8 @@ where
                         ))
                     }
                 }
-                last_location = token_with_location.location;
             }
-            None => return Err(ParseError::UnexpectedEndOfInput(last_location)),
+            None => return Err(unexpected_end()),
         }
     }
     Ok(())
@@ -1590,21 +2554,1004 @@ mod tests {
         assert_eq!(message.name, "TestReserved");
         assert_eq!(message.reserved.len(), 5);
 
-        assert!(message
-            .reserved
-            .contains(&crate::parser::ast::Reserved::Number(2)));
-        assert!(message
-            .reserved
-            .contains(&crate::parser::ast::Reserved::Number(15)));
-        assert!(message
-            .reserved
-            .contains(&crate::parser::ast::Reserved::Range(9, 11)));
-        assert!(message
-            .reserved
-            .contains(&crate::parser::ast::Reserved::FieldName("foo".to_string())));
-        assert!(message
+        let kinds: Vec<&ReservedKind> = message.reserved.iter().map(|r| &r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &ReservedKind::Number(2),
+                &ReservedKind::Number(15),
+                &ReservedKind::Range(9, 11),
+                &ReservedKind::FieldName("foo".to_string()),
+                &ReservedKind::FieldName("bar".to_string()),
+            ]
+        );
+        let texts: Vec<&str> = message
             .reserved
-            .contains(&crate::parser::ast::Reserved::FieldName("bar".to_string())));
+            .iter()
+            .map(|r| r.span.text(input))
+            .collect();
+        assert_eq!(texts, vec!["2", "15", "9 to 11", "\"foo\"", "\"bar\""]);
+    }
+
+    #[test]
+    fn test_parse_reserved_numbers() {
+        let input = r#"syntax = "proto3"; message M { reserved 0x10, 017 to max; }"#;
+        let message = &parse_proto_file(input).unwrap().messages[0];
+        let kinds: Vec<&ReservedKind> = message.reserved.iter().map(|r| &r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &ReservedKind::Number(16),
+                &ReservedKind::Range(15, MAX_FIELD_NUMBER),
+            ]
+        );
+
+        // Numbers that do not fit are reported, not truncated
+        for (input, location) in [
+            ("message M { reserved 4294967297; }", Location::new(2, 22)),
+            (
+                "message M { reserved 1 to 0x100000001; }",
+                Location::new(2, 27),
+            ),
+        ] {
+            match parse_proto_file(&format!("syntax = \"proto3\";\n{}", input)) {
+                Err(ParseError::InvalidFieldNumber(_, loc)) => assert_eq!(loc, location),
+                other => panic!("expected an invalid field number, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_oneof() {
+        let input = r#"
+            syntax = "proto3";
+            message SampleMessage {
+                string id = 1;
+                oneof test_oneof {
+                    option deprecated = true;
+                    // The name variant
+                    string name = 4;
+                    SubMessage sub_message = 9; // trailing comment
+                }
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+
+        let proto_file = result.unwrap();
+        let message = &proto_file.messages[0];
+        assert_eq!(message.fields.len(), 1);
+        assert_eq!(message.oneofs.len(), 1);
+
+        let oneof = &message.oneofs[0];
+        assert_eq!(oneof.name, "test_oneof");
+        assert_eq!(oneof.options.len(), 1);
+        assert!(oneof.options[0].name.is("deprecated"));
+        assert_eq!(oneof.fields.len(), 2);
+        assert_eq!(oneof.fields[0].name, "name");
+        assert_eq!(oneof.fields[0].typ, FieldType::String);
+        assert_eq!(oneof.fields[0].number, NumberValue::DecimalInt(4));
+        assert_eq!(oneof.fields[1].name, "sub_message");
+        assert_eq!(
+            oneof.fields[1].typ,
+            FieldType::MessageOrEnum("SubMessage".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_oneof_rejects_labels() {
+        let input = r#"
+            syntax = "proto3";
+            message SampleMessage {
+                oneof choice {
+                    repeated string names = 1;
+                }
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(matches!(result, Err(ParseError::InvalidSyntax(_, _))));
+    }
+
+    #[test]
+    fn test_parse_keywords_as_names() {
+        let input = r#"
+            syntax = "proto2";
+            message M {
+                optional bool required = 1;
+                optional string optional = 2;
+                required string extend = 3;
+                repeated int32 to = 4;
+                optional int64 max = 5;
+                optional bool option = 6 [(my.rules).to = 1];
+                map<string, string> reserved = 7;
+            }
+            enum E {
+                option = 0;
+                reserved = 1;
+                required = 2 [deprecated = true];
+                option allow_alias = true;
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+        let proto_file = result.unwrap();
+
+        let fields = &proto_file.messages[0].fields;
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["required", "optional", "extend", "to", "max", "option", "reserved"]
+        );
+        assert_eq!(fields[1].label, FieldLabel::Optional);
+        assert_eq!(fields[2].label, FieldLabel::Required);
+        assert_eq!(fields[5].options[0].name.to_string(), "(my.rules).to");
+
+        let enum_def = &proto_file.enums[0];
+        let names: Vec<&str> = enum_def.values.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["option", "reserved", "required"]);
+        assert_eq!(enum_def.options.len(), 1);
+        assert_eq!(enum_def.options[0].name.to_string(), "allow_alias");
+    }
+
+    #[test]
+    fn test_parse_proto3_optional() {
+        let input = r#"
+            syntax = "proto3";
+            message M {
+                string a = 1;
+                optional string b = 2;
+            }
+        "#;
+        let fields = &parse_proto_file(input).unwrap().messages[0].fields;
+        assert!(!fields[0].proto3_optional);
+        assert!(fields[1].proto3_optional);
+        assert_eq!(fields[0].label, fields[1].label);
+
+        // In proto2, `optional` is the ordinary label
+        let input = r#"syntax = "proto2"; message M { optional string a = 1; }"#;
+        let fields = &parse_proto_file(input).unwrap().messages[0].fields;
+        assert!(!fields[0].proto3_optional);
+        assert_eq!(fields[0].label, FieldLabel::Optional);
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let input = r#"
+            syntax = "proto3";
+            message First {
+                string a = 1;
+                string b = ;
+                oneof choice {
+                    repeated string c = 2;
+                    string d = 3;
+                }
+                string e = 4;
+            }
+            enum Kind {
+                KIND_A = 0;
+                message Oops {}
+                KIND_B = 1;
+            }
+            foo bar;
+            service Api {
+                rpc Get(First) returns First;
+                rpc List(First) returns (First);
+            }
+            message Second {
+                string f = 1;
+            }
+        "#;
+
+        let (proto_file, errors) = parse_proto_file_recovering(input);
+
+        let lines: Vec<usize> = errors.iter().map(|e| e.location().line).collect();
+        assert_eq!(lines, vec![5, 7, 14, 17, 19]);
+        assert!(matches!(errors[1], ParseError::InvalidSyntax(_, _)));
+
+        let messages: Vec<&str> = proto_file
+            .messages
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(messages, vec!["First", "Second"]);
+        let first = &proto_file.messages[0];
+        let fields: Vec<&str> = first.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(fields, vec!["a", "e"]);
+        assert_eq!(first.oneofs[0].fields[0].name, "d");
+
+        let values: Vec<&str> = proto_file.enums[0]
+            .values
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(values, vec!["KIND_A", "KIND_B"]);
+        assert_eq!(proto_file.services[0].methods.len(), 1);
+        assert_eq!(proto_file.services[0].methods[0].name, "List");
+    }
+
+    #[test]
+    fn test_parse_recovering_missing_semicolon_before_brace() {
+        let input = "syntax = \"proto3\";\n\
+                     message A {\n  int32 a = 1\n}\n\
+                     message B { int32 b = 1; }\n\
+                     message C { int32 c = 1; }\n";
+
+        let (proto_file, errors) = parse_proto_file_recovering(input);
+
+        // The `}` the field failed on still closes A
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], ParseError::UnexpectedToken(_, _)));
+        assert_eq!(errors[0].location(), Location::new(4, 1));
+        let messages: Vec<&str> = proto_file
+            .messages
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(messages, vec!["A", "B", "C"]);
+        assert!(proto_file.messages[0].nested_messages.is_empty());
+    }
+
+    #[test]
+    fn test_parse_recovering_stray_block() {
+        let input = "syntax = \"proto3\";\n\
+                     message A {\n  int32 a = 1 { x }\n  int32 b = 2;\n}\n\
+                     message B {}\n";
+
+        let (proto_file, errors) = parse_proto_file_recovering(input);
+
+        // The block is skipped with the field, and A goes on after it
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location(), Location::new(3, 15));
+        let messages: Vec<&str> = proto_file
+            .messages
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(messages, vec!["A", "B"]);
+        assert_eq!(proto_file.messages[0].fields[0].name, "b");
+    }
+
+    #[test]
+    fn test_parse_recovering_valid_and_unterminated() {
+        let input = r#"syntax = "proto3"; message A { string a = 1; }"#;
+        let (proto_file, errors) = parse_proto_file_recovering(input);
+        assert!(errors.is_empty());
+        assert_eq!(proto_file, parse_proto_file(input).unwrap());
+
+        // The body of B is never closed, so B is dropped
+        let input = r#"syntax = "proto3"; message A {} message B { string b = 1;"#;
+        let (proto_file, errors) = parse_proto_file_recovering(input);
+        assert_eq!(proto_file.messages.len(), 1);
+        assert!(matches!(errors[..], [ParseError::UnexpectedEndOfInput(_)]));
+
+        // The first error still ends a parse without recovery
+        assert!(parse_proto_file("syntax = \"proto3\"; foo; message A {}").is_err());
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let error = |input: &str| parse_proto_file(input).unwrap_err();
+
+        // An unexpected end of input is reported right after the last token
+        let end_of_input = [
+            "syntax = \"proto3\";\nservice S {\n  rpc Get(A",
+            "syntax = \"proto3\";\nmessage M {\n  map<string, A",
+            "syntax = \"proto3\";\nmessage M {\n  reserved 1 to",
+            "syntax = \"proto3\";\noption (a).b = {",
+        ];
+        for input in end_of_input {
+            let last_line = input.lines().last().unwrap();
+            assert_eq!(
+                error(input).location(),
+                Location::new(input.lines().count(), last_line.len() + 1),
+                "{:?}",
+                input
+            );
+            assert!(matches!(error(input), ParseError::UnexpectedEndOfInput(_)));
+        }
+
+        assert!(matches!(
+            error("syntax = \"proto3\";\nmessage M { string s = 1 [default = \"abc]; }"),
+            ParseError::UnterminatedStringLiteral(loc) if loc == Location::new(2, 37)
+        ));
+        assert!(matches!(
+            error("syntax = \"proto3\";\nmessage M {\n  # comment\n}"),
+            ParseError::UnexpectedCharacter('#', loc) if loc == Location::new(3, 3)
+        ));
+        assert!(matches!(
+            error("syntax = \"proto3\";\nmessage M {\n  string s = 1 }"),
+            ParseError::UnexpectedToken(_, loc) if loc == Location::new(3, 16)
+        ));
+        assert!(matches!(
+            error("syntax = \"proto3\";\nmessage M {\n  reserved 10 to 5;\n}"),
+            ParseError::InvalidRange(10, 5, loc) if loc == Location::new(3, 12)
+        ));
+        assert!(matches!(
+            error("syntax = \"proto3\";\nservice S {\n  rpc Get() returns (A);\n}"),
+            ParseError::MissingIdentifier(_, loc) if loc == Location::new(3, 11)
+        ));
+        assert!(matches!(
+            error("syntax = \"proto3\";\nmessage M {\n  reserved 1 to foo;\n}"),
+            ParseError::UnexpectedToken(_, loc) if loc == Location::new(3, 17)
+        ));
+    }
+
+    #[test]
+    fn test_parse_recovering_end_of_input_location() {
+        let input = "syntax = \"proto3\";\nmessage A {}\nmessage B {\n  string b = 1;\n\n";
+        let (_, errors) = parse_proto_file_recovering(input);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location(), Location::new(4, 16));
+    }
+
+    #[test]
+    fn test_parse_zod_comment_end_of_input_location() {
+        // The cut-off annotation ends on line 3, not at the end of the file
+        let input = "syntax = \"proto3\";\nmessage A {\n  string a = 1; // @zod { min:\n  string b = 2;\n}\n";
+        let error = parse_proto_file(input).unwrap_err();
+        assert!(matches!(error, ParseError::UnexpectedEndOfInput(_)));
+        assert_eq!(error.location(), Location::new(3, 31));
+
+        let (_, errors) = parse_proto_file_recovering(input);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location(), Location::new(3, 31));
+    }
+
+    #[test]
+    fn test_parse_field_options() {
+        let input = r#"
+            syntax = "proto3";
+            message Options {
+                int32 a = 1 [deprecated = true];
+                string b = 2 [json_name = "bee", (validate.rules).string.min_len = 3];
+                map<string, int32> c = 3 [(custom) = 1.5];
+                string d = 4;
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+
+        let message = &result.unwrap().messages[0];
+        assert_eq!(message.fields.len(), 4);
+        // Compare names and values only, without the source spans
+        let options = |field: &Field| {
+            field
+                .options
+                .iter()
+                .map(|o| ProtoOption::new(o.name.clone(), o.value.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            options(&message.fields[0]),
+            vec![ProtoOption::new(
+                OptionName::simple("deprecated"),
+                OptionValue::Bool(true)
+            )]
+        );
+        assert_eq!(
+            options(&message.fields[1]),
+            vec![
+                ProtoOption::new(
+                    OptionName::simple("json_name"),
+                    OptionValue::String("bee".to_string())
+                ),
+                ProtoOption::new(
+                    OptionName::new(vec![
+                        OptionNamePart::Extension("validate.rules".to_string()),
+                        OptionNamePart::Field("string".to_string()),
+                        OptionNamePart::Field("min_len".to_string()),
+                    ]),
+                    OptionValue::DecimalInt(3)
+                ),
+            ]
+        );
+        assert_eq!(
+            options(&message.fields[2]),
+            vec![ProtoOption::new(
+                OptionName::new(vec![OptionNamePart::Extension("custom".to_string())]),
+                OptionValue::Float(1.5)
+            )]
+        );
+        assert!(message.fields[3].options.is_empty());
+    }
+
+    #[test]
+    fn test_parse_field_options_unterminated() {
+        let input = r#"
+            syntax = "proto3";
+            message Options {
+                int32 a = 1 [deprecated = true;
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(matches!(result, Err(ParseError::UnexpectedToken(_, _))));
+    }
+
+    #[test]
+    fn test_parse_option_values() {
+        let input = r#"
+            syntax = "proto3";
+            option java_multiple_files = true;
+            option lowest = -inf;
+            option not_a_number = nan;
+            option offset = -0x10;
+            option greeting = "hello, " "world";
+            option level = HIGH;
+            option tags = [1, -2, "three"];
+            option http = {
+                get: "/v1/messages/{message_id}"
+                additional_bindings {
+                    get: "/v1/users/{user_id}/messages/{message_id}";
+                    body: "*"
+                }
+                [ext.field]: false,
+                rules < min_len: 3 >
+            };
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+
+        let options = result.unwrap().options;
+        assert_eq!(options.len(), 8);
+        assert_eq!(options[0].value, OptionValue::Bool(true));
+        assert_eq!(options[1].value, OptionValue::Float(f64::NEG_INFINITY));
+        assert!(matches!(options[2].value, OptionValue::Float(f) if f.is_nan()));
+        assert_eq!(options[3].value, OptionValue::Hex(-16));
+        assert_eq!(
+            options[4].value,
+            OptionValue::String("hello, world".to_string())
+        );
+        assert_eq!(
+            options[5].value,
+            OptionValue::Identifier("HIGH".to_string())
+        );
+        assert_eq!(
+            options[6].value,
+            OptionValue::List(vec![
+                OptionValue::DecimalInt(1),
+                OptionValue::DecimalInt(-2),
+                OptionValue::String("three".to_string()),
+            ])
+        );
+        assert_eq!(
+            options[7].value,
+            OptionValue::Message(vec![
+                (
+                    "get".to_string(),
+                    OptionValue::String("/v1/messages/{message_id}".to_string())
+                ),
+                (
+                    "additional_bindings".to_string(),
+                    OptionValue::Message(vec![
+                        (
+                            "get".to_string(),
+                            OptionValue::String(
+                                "/v1/users/{user_id}/messages/{message_id}".to_string()
+                            )
+                        ),
+                        ("body".to_string(), OptionValue::String("*".to_string())),
+                    ])
+                ),
+                ("[ext.field]".to_string(), OptionValue::Bool(false)),
+                (
+                    "rules".to_string(),
+                    OptionValue::Message(vec![("min_len".to_string(), OptionValue::DecimalInt(3))])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_custom_option_names() {
+        let input = r#"
+            syntax = "proto3";
+            package example;
+            import "google/api/annotations.proto";
+            option (my.file_option) = "file";
+
+            service Messaging {
+                rpc GetMessage(GetMessageRequest) returns (Message) {
+                    option (google.api.http) = {
+                        get: "/v1/messages/{message_id}"
+                    };
+                    option (google.api.method_signature).name = "message_id";
+                }
+            }
+
+            enum Kind {
+                option (.my.enum_option) = true;
+                A = 0 [(my.value_option) = 1];
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+        let proto_file = result.unwrap();
+
+        assert_eq!(
+            proto_file.options[0].name.extension(),
+            Some("my.file_option")
+        );
+
+        let method = &proto_file.services[0].methods[0];
+        assert_eq!(method.options.len(), 2);
+        assert_eq!(method.options[0].name.extension(), Some("google.api.http"));
+        assert!(method.options[0].name.path().is_empty());
+        assert!(matches!(method.options[0].value, OptionValue::Message(_)));
+        assert_eq!(
+            method.options[1].name.to_string(),
+            "(google.api.method_signature).name"
+        );
+        assert_eq!(method.options[1].name.path(), vec!["name"]);
+
+        let enum_def = &proto_file.enums[0];
+        assert_eq!(
+            enum_def.options[0].name.extension(),
+            Some(".my.enum_option")
+        );
+        assert_eq!(
+            enum_def.values[0].options[0].name.extension(),
+            Some("my.value_option")
+        );
+    }
+
+    #[test]
+    fn test_parse_any_type_url() {
+        let input = r#"
+            syntax = "proto3";
+            option (my.any) = {
+                [type.googleapis.com/pkg.Msg] { id: 1 }
+            };
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+        assert_eq!(
+            result.unwrap().options[0].value,
+            OptionValue::Message(vec![(
+                "[type.googleapis.com/pkg.Msg]".to_string(),
+                OptionValue::Message(vec![("id".to_string(), OptionValue::DecimalInt(1))])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_parse_enum_option_values() {
+        let input = r#"
+            syntax = "proto3";
+            enum Kind {
+                option (my.scale) = -1.5;
+                option (my.names) = ["a", "b"];
+                A = 0 [(my.rules) = { min: 1 }, (my.tags) = [1, 2], (my.label) = "a" "b"];
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+        let enum_def = &result.unwrap().enums[0];
+        assert_eq!(enum_def.options[0].value, OptionValue::Float(-1.5));
+        assert_eq!(
+            enum_def.options[1].value,
+            OptionValue::List(vec![
+                OptionValue::String("a".to_string()),
+                OptionValue::String("b".to_string()),
+            ])
+        );
+
+        let options = &enum_def.values[0].options;
+        assert_eq!(
+            options[0].value,
+            OptionValue::Message(vec![("min".to_string(), OptionValue::DecimalInt(1))])
+        );
+        assert_eq!(
+            options[1].value,
+            OptionValue::List(vec![OptionValue::DecimalInt(1), OptionValue::DecimalInt(2)])
+        );
+        assert_eq!(options[2].value, OptionValue::String("ab".to_string()));
+    }
+
+    #[test]
+    fn test_parse_empty_option_extension() {
+        let input = r#"
+            syntax = "proto3";
+            option () = 1;
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(matches!(result, Err(ParseError::MissingIdentifier(_, _))));
+    }
+
+    #[test]
+    fn test_parse_option_value_unterminated_aggregate() {
+        let input = r#"
+            syntax = "proto3";
+            option http = { get: "/v1/x" ;
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_extend_and_extensions() {
+        let input = r#"
+            syntax = "proto2";
+            package example;
+            import "google/protobuf/descriptor.proto";
+
+            extend google.protobuf.FieldOptions {
+                optional string my_option = 51234;
+                // Trailing comment inside the block
+                repeated int32 my_list = 51235;
+            }
+
+            message Extendable {
+                optional string name = 1;
+                required int32 id = 2;
+                extensions 100 to 199, 500;
+                extensions 1000 to max [verification = UNVERIFIED];
+                reserved 10 to max;
+
+                extend Extendable {
+                    optional int32 nested_ext = 100;
+                }
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+        let proto_file = result.unwrap();
+
+        assert_eq!(proto_file.extends.len(), 1);
+        let extend = &proto_file.extends[0];
+        assert_eq!(extend.extendee, "google.protobuf.FieldOptions");
+        assert_eq!(extend.fields.len(), 2);
+        assert_eq!(extend.fields[0].name, "my_option");
+        assert_eq!(extend.fields[0].number, NumberValue::DecimalInt(51234));
+        assert_eq!(extend.fields[1].label, FieldLabel::Repeated);
+
+        let message = &proto_file.messages[0];
+        assert_eq!(message.fields.len(), 2);
+        assert_eq!(message.fields[1].label, FieldLabel::Required);
+        assert_eq!(
+            message
+                .extensions
+                .iter()
+                .map(|r| (r.start, r.end))
+                .collect::<Vec<_>>(),
+            vec![(100, 199), (500, 500), (1000, MAX_FIELD_NUMBER)]
+        );
+        assert!(message.extensions[0].options.is_empty());
+        assert_eq!(message.extensions[2].options.len(), 1);
+        assert!(message
+            .reserved
+            .iter()
+            .any(|r| r.kind == ReservedKind::Range(10, MAX_FIELD_NUMBER)));
+
+        assert_eq!(message.extends.len(), 1);
+        assert_eq!(message.extends[0].extendee, "Extendable");
+        assert_eq!(message.extends[0].fields[0].name, "nested_ext");
+    }
+
+    #[test]
+    fn test_parse_extensions_invalid_range() {
+        let input = r#"
+            syntax = "proto2";
+            message Extendable {
+                extensions 200 to 100;
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(matches!(result, Err(ParseError::InvalidRange(200, 100, _))));
+    }
+
+    #[test]
+    fn test_parse_qualified_field_types() {
+        let input = r#"
+            syntax = "proto3";
+            message Event {
+                google.protobuf.Timestamp created_at = 1;
+                .example.Outer.Inner inner = 2;
+                repeated google.rpc.Status errors = 3;
+                map<string, google.protobuf.Value> attributes = 4;
+                Local local = 5;
+            }
+        "#;
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+
+        let fields = &result.unwrap().messages[0].fields;
+        let types: Vec<_> = fields.iter().map(|f| (&f.name[..], &f.typ)).collect();
+        assert_eq!(
+            types,
+            vec![
+                (
+                    "created_at",
+                    &FieldType::MessageOrEnum("google.protobuf.Timestamp".to_string())
+                ),
+                (
+                    "inner",
+                    &FieldType::MessageOrEnum(".example.Outer.Inner".to_string())
+                ),
+                (
+                    "errors",
+                    &FieldType::MessageOrEnum("google.rpc.Status".to_string())
+                ),
+                (
+                    "attributes",
+                    &FieldType::Map(
+                        Box::new(FieldType::String),
+                        Box::new(FieldType::MessageOrEnum(
+                            "google.protobuf.Value".to_string()
+                        ))
+                    )
+                ),
+                ("local", &FieldType::MessageOrEnum("Local".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_zod_annotations() {
+        let input = include_str!("../../files/with-zod-comments.proto");
+
+        let result = parse_proto_file(input);
+        assert!(
+            result.is_ok(),
+            "Failed to parse proto file: {:?}",
+            result.err()
+        );
+        let proto_file = result.unwrap();
+
+        let user = &proto_file.messages[0];
+        assert_eq!(
+            user.zod.as_ref().and_then(|z| z.description.as_deref()),
+            Some("Represents a user in the system")
+        );
+
+        let username = user.fields[0].zod.as_ref().unwrap();
+        assert_eq!(username.min, Some(3.0));
+        assert_eq!(username.max, Some(50.0));
+
+        assert!(user.fields[1].zod.as_ref().unwrap().email);
+        assert_eq!(
+            user.fields[3].zod.as_ref().unwrap().regex.as_deref(),
+            Some(r"^(?=.*[A-Za-z])(?=.*\d)[A-Za-z\d]{8,}$")
+        );
+        assert_eq!(
+            user.fields[4]
+                .zod
+                .as_ref()
+                .and_then(|z| z.array.as_ref())
+                .map(|a| (a.min, a.max)),
+            Some((Some(1), Some(10)))
+        );
+        assert_eq!(
+            user.fields[5].zod.as_ref().unwrap().default,
+            Some(OptionValue::String("STANDARD".to_string()))
+        );
+
+        assert_eq!(
+            proto_file.enums[0]
+                .zod
+                .as_ref()
+                .and_then(|z| z.description.as_deref()),
+            Some("Types of users in the system")
+        );
+    }
+
+    #[test]
+    fn test_parse_zod_annotation_placement() {
+        let input = r#"
+            syntax = "proto3";
+            message Plain { // @zod { description: "same line as the brace" }
+                // @zod { min: 1 }
+                string name = 1;
+                string note = 2; // not an annotation
+            }
+        "#;
+
+        let proto_file = parse_proto_file(input).unwrap();
+        let message = &proto_file.messages[0];
+        assert!(message.zod.is_some());
+        // Annotations on their own line do not trail a definition
+        assert_eq!(message.fields[0].zod, None);
+        assert_eq!(message.fields[1].zod, None);
+
+        let result = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            message Invalid {
+                string name = 1; // @zod { min: "three" }
+            }
+        "#,
+        );
+        assert!(matches!(result, Err(ParseError::InvalidSyntax(..))));
+    }
+
+    #[test]
+    fn test_parse_spans() {
+        let input = r#"syntax = "proto3";
+import "other.proto";
+
+message Person {
+  string name = 1 [deprecated = true];
+  oneof contact {
+    string email = 2;
+  }
+  extensions 100 to max;
+}
+
+enum Kind {
+  KIND_UNKNOWN = 0 [(tag) = "x"];
+}
+
+service People {
+  rpc Get(Person) returns (Person);
+}
+"#;
+
+        let proto = parse_proto_file(input).unwrap();
+
+        assert_eq!(
+            proto.imports[0].span.text(input),
+            r#"import "other.proto";"#
+        );
+
+        let message = &proto.messages[0];
+        assert!(message.span.text(input).starts_with("message Person {"));
+        assert!(message.span.text(input).ends_with("max;\n}"));
+        assert_eq!(message.span.location(), Location::new(4, 1));
+        assert_eq!(
+            message.fields[0].span.text(input),
+            "string name = 1 [deprecated = true];"
+        );
+        assert_eq!(message.fields[0].span.location(), Location::new(5, 3));
+        assert_eq!(message.fields[0].number_span.text(input), "1");
+        assert_eq!(
+            message.fields[0].options[0].span.text(input),
+            "deprecated = true"
+        );
+        assert_eq!(
+            message.oneofs[0].span.text(input),
+            "oneof contact {\n    string email = 2;\n  }"
+        );
+        assert_eq!(message.extensions[0].span.text(input), "100 to max");
+
+        let enum_def = &proto.enums[0];
+        assert_eq!(enum_def.span.location(), Location::new(12, 1));
+        assert_eq!(
+            enum_def.values[0].span.text(input),
+            r#"KIND_UNKNOWN = 0 [(tag) = "x"];"#
+        );
+        assert_eq!(
+            enum_def.values[0].options[0].span.text(input),
+            r#"(tag) = "x""#
+        );
+
+        let service = &proto.services[0];
+        assert!(service.span.text(input).starts_with("service People {"));
+        assert!(service.span.text(input).ends_with('}'));
+        assert_eq!(
+            service.methods[0].span.text(input),
+            "rpc Get(Person) returns (Person);"
+        );
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let input = r#"syntax = "proto3";
+
+// Detached comment
+
+// Leading comment
+// for Person
+message Person { // Trailing person
+  /* Name of
+   * the person */
+  string name = 1; // Trailing name
+  reserved 2; // Not attached to the next field
+  int32 age = 3;
+}
+
+enum Kind {
+  // First
+  A = 0; // Trailing A
+}
+
+service Greeter {
+  // Says hello
+  rpc Hello(Person) returns (Person); // Trailing Hello
+}
+"#;
+
+        let proto_file = parse_proto_file(input).unwrap();
+
+        let person = &proto_file.messages[0];
+        assert_eq!(
+            person.comments,
+            Comments {
+                leading: Some(" Leading comment\n for Person\n".to_string()),
+                trailing: Some(" Trailing person\n".to_string()),
+                leading_detached: vec![" Detached comment\n".to_string()],
+            }
+        );
+        assert_eq!(
+            person.fields[0].comments.leading.as_deref(),
+            Some(" Name of\n the person ")
+        );
+        assert_eq!(
+            person.fields[0].comments.trailing.as_deref(),
+            Some(" Trailing name\n")
+        );
+        assert_eq!(person.fields[1].comments, Comments::default());
+
+        let value = &proto_file.enums[0].values[0];
+        assert_eq!(value.comments.leading.as_deref(), Some(" First\n"));
+        assert_eq!(value.comments.trailing.as_deref(), Some(" Trailing A\n"));
+
+        let method = &proto_file.services[0].methods[0];
+        assert_eq!(method.comments.leading.as_deref(), Some(" Says hello\n"));
+        assert_eq!(
+            method.comments.trailing.as_deref(),
+            Some(" Trailing Hello\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_logdservice_comments() {
+        let proto_file = parse_proto_file(include_str!("../../files/logdservice.proto")).unwrap();
+
+        let documented = proto_file
+            .messages
+            .iter()
+            .filter(|m| m.comments.leading.is_some())
+            .count();
+        assert!(documented > 0, "expected documented messages");
     }
 
     #[test]
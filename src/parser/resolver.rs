@@ -0,0 +1,420 @@
+//! Resolution of message and enum type references
+//!
+//! The parser stores a type reference exactly as written, e.g. `Inner`,
+//! `foo.Bar` or `.pkg.Baz`. The resolver looks these names up using the
+//! protobuf scoping rules and replaces them with fully-qualified references
+//! that record whether the type is a message or an enum.
+
+use std::collections::{HashMap, HashSet};
+
+use super::ast::{Extend, Field, FieldType, Message, ProtoFile};
+use super::error::Location;
+use super::ParseError;
+
+/// Whether a type name refers to a message or an enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Message,
+    Enum,
+}
+
+/// A type reference resolved to its definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedType {
+    /// The fully-qualified name, without a leading dot, e.g. `pkg.Outer.Inner`
+    pub full_name: String,
+    pub kind: TypeKind,
+}
+
+impl ResolvedType {
+    /// Returns the resolved field type, which refers to the definition by its
+    /// fully-qualified name with a leading dot, like protoc's `type_name`.
+    pub fn field_type(&self) -> FieldType {
+        let name = format!(".{}", self.full_name);
+        match self.kind {
+            TypeKind::Message => FieldType::Message(name),
+            TypeKind::Enum => FieldType::Enum(name),
+        }
+    }
+}
+
+/// The messages and enums visible from a file, by fully-qualified name
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    types: HashMap<String, TypeKind>,
+    packages: HashSet<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    /// Registers the package and all (nested) messages and enums of a file.
+    pub fn add_file(&mut self, proto_file: &ProtoFile) {
+        let package = proto_file.package.clone().unwrap_or_default();
+        if !package.is_empty() {
+            let mut end = 0;
+            for part in package.split('.') {
+                end += part.len();
+                self.packages.insert(package[..end].to_string());
+                end += 1;
+            }
+        }
+
+        for enum_def in &proto_file.enums {
+            self.types
+                .insert(qualify(&package, &enum_def.name), TypeKind::Enum);
+        }
+        for message in &proto_file.messages {
+            self.add_message(&package, message);
+        }
+    }
+
+    fn add_message(&mut self, scope: &str, message: &Message) {
+        let full_name = qualify(scope, &message.name);
+        for enum_def in &message.nested_enums {
+            self.types
+                .insert(qualify(&full_name, &enum_def.name), TypeKind::Enum);
+        }
+        for nested in &message.nested_messages {
+            self.add_message(&full_name, nested);
+        }
+        self.types.insert(full_name, TypeKind::Message);
+    }
+
+    /// Returns the kind of the type with the given fully-qualified name
+    /// (without a leading dot).
+    pub fn get(&self, full_name: &str) -> Option<TypeKind> {
+        self.types.get(full_name).copied()
+    }
+
+    /// Resolves a type reference relative to the scope it appears in.
+    ///
+    /// Like protoc, names with a leading dot are fully-qualified; other names
+    /// are searched from the innermost scope outwards. For a dotted name, the
+    /// search stops at the first scope where its first component names a
+    /// message or package, so `foo.Bar` does not fall back to an outer `foo`.
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - The fully-qualified name of the enclosing message, or the
+    ///   package for top-level references.
+    /// * `type_name` - The type name as written in the source.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<ResolvedType>` - The referenced definition, or `None` if the
+    ///   name is not defined.
+    pub fn lookup(&self, scope: &str, type_name: &str) -> Option<ResolvedType> {
+        if let Some(absolute) = type_name.strip_prefix('.') {
+            return self.resolved(absolute.to_string());
+        }
+
+        let first = type_name.split('.').next().unwrap_or(type_name);
+        let mut scope = scope;
+        loop {
+            let candidate = qualify(scope, first);
+            if first.len() == type_name.len() {
+                if self.types.contains_key(&candidate) {
+                    return self.resolved(candidate);
+                }
+            } else if self.get(&candidate) == Some(TypeKind::Message)
+                || self.packages.contains(&candidate)
+            {
+                return self.resolved(qualify(scope, type_name));
+            }
+
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rfind('.').map_or("", |i| &scope[..i]);
+        }
+    }
+
+    fn resolved(&self, full_name: String) -> Option<ResolvedType> {
+        let kind = self.get(&full_name)?;
+        Some(ResolvedType { full_name, kind })
+    }
+}
+
+/// Resolves every type reference in a file.
+///
+/// Field types written as [`FieldType::MessageOrEnum`] are replaced with
+/// [`FieldType::Message`] or [`FieldType::Enum`], and the extendees of
+/// `extend` blocks and the input and output types of methods are replaced
+/// with their fully-qualified names (with a leading dot).
+///
+/// # Arguments
+///
+/// * `proto_file` - The file to resolve.
+/// * `imports` - The files whose definitions are visible from `proto_file`.
+///
+/// # Returns
+///
+/// * `Result<(), ParseError>` - `UnknownType` for the first reference that
+///   is not defined, or names an enum where a message is required.
+pub fn resolve_types(proto_file: &mut ProtoFile, imports: &[&ProtoFile]) -> Result<(), ParseError> {
+    let mut symbols = SymbolTable::new();
+    symbols.add_file(proto_file);
+    for import in imports {
+        symbols.add_file(import);
+    }
+
+    let package = proto_file.package.clone().unwrap_or_default();
+    for message in &mut proto_file.messages {
+        resolve_message(&symbols, &package, message)?;
+    }
+    for extend in &mut proto_file.extends {
+        resolve_extend(&symbols, &package, extend)?;
+    }
+    for service in &mut proto_file.services {
+        for method in &mut service.methods {
+            let location = method.span.location();
+            method.input_type =
+                resolve_message_name(&symbols, &package, &method.input_type, location)?;
+            method.output_type =
+                resolve_message_name(&symbols, &package, &method.output_type, location)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve_message(
+    symbols: &SymbolTable,
+    scope: &str,
+    message: &mut Message,
+) -> Result<(), ParseError> {
+    let full_name = qualify(scope, &message.name);
+
+    let fields = message
+        .fields
+        .iter_mut()
+        .chain(message.oneofs.iter_mut().flat_map(|o| o.fields.iter_mut()));
+    for field in fields {
+        resolve_field(symbols, &full_name, field)?;
+    }
+    for extend in &mut message.extends {
+        resolve_extend(symbols, &full_name, extend)?;
+    }
+    for nested in &mut message.nested_messages {
+        resolve_message(symbols, &full_name, nested)?;
+    }
+
+    Ok(())
+}
+
+fn resolve_extend(
+    symbols: &SymbolTable,
+    scope: &str,
+    extend: &mut Extend,
+) -> Result<(), ParseError> {
+    extend.extendee =
+        resolve_message_name(symbols, scope, &extend.extendee, extend.span.location())?;
+    for field in &mut extend.fields {
+        resolve_field(symbols, scope, field)?;
+    }
+    Ok(())
+}
+
+fn resolve_field(symbols: &SymbolTable, scope: &str, field: &mut Field) -> Result<(), ParseError> {
+    let location = field.span.location();
+    resolve_field_type(symbols, scope, &mut field.typ, location)
+}
+
+fn resolve_field_type(
+    symbols: &SymbolTable,
+    scope: &str,
+    typ: &mut FieldType,
+    location: Location,
+) -> Result<(), ParseError> {
+    match typ {
+        FieldType::MessageOrEnum(name) => {
+            let resolved = symbols
+                .lookup(scope, name)
+                .ok_or_else(|| ParseError::UnknownType(name.clone(), location))?;
+            *typ = resolved.field_type();
+        }
+        FieldType::Map(key, value) => {
+            resolve_field_type(symbols, scope, key, location)?;
+            resolve_field_type(symbols, scope, value, location)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Resolves a name that must refer to a message, returning it fully-qualified.
+fn resolve_message_name(
+    symbols: &SymbolTable,
+    scope: &str,
+    type_name: &str,
+    location: Location,
+) -> Result<String, ParseError> {
+    match symbols.lookup(scope, type_name) {
+        Some(ResolvedType {
+            full_name,
+            kind: TypeKind::Message,
+        }) => Ok(format!(".{}", full_name)),
+        Some(ResolvedType { full_name, .. }) => Err(ParseError::UnknownType(
+            format!(
+                "{} (expected a message, found enum {})",
+                type_name, full_name
+            ),
+            location,
+        )),
+        None => Err(ParseError::UnknownType(type_name.to_string(), location)),
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_proto_file;
+
+    #[test]
+    fn test_lookup_scopes() {
+        let proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            package foo.bar;
+            message Outer {
+                message Inner {}
+                enum Kind { KIND_UNKNOWN = 0; }
+            }
+            message Inner {}
+        "#,
+        )
+        .unwrap();
+
+        let mut symbols = SymbolTable::new();
+        symbols.add_file(&proto_file);
+
+        let lookup = |scope: &str, name: &str| symbols.lookup(scope, name).map(|t| t.full_name);
+
+        // The innermost definition wins
+        assert_eq!(
+            lookup("foo.bar.Outer", "Inner").as_deref(),
+            Some("foo.bar.Outer.Inner")
+        );
+        assert_eq!(lookup("foo.bar", "Inner").as_deref(), Some("foo.bar.Inner"));
+        assert_eq!(
+            lookup("foo.bar.Outer", ".foo.bar.Inner").as_deref(),
+            Some("foo.bar.Inner")
+        );
+        assert_eq!(
+            lookup("foo.bar", "Outer.Kind").as_deref(),
+            Some("foo.bar.Outer.Kind")
+        );
+        assert_eq!(
+            lookup("other", "foo.bar.Outer").as_deref(),
+            Some("foo.bar.Outer")
+        );
+        assert_eq!(
+            symbols.lookup("foo.bar", "Outer.Kind").unwrap().kind,
+            TypeKind::Enum
+        );
+
+        assert_eq!(lookup("foo.bar", "Missing"), None);
+        assert_eq!(lookup("foo.bar", ".Inner"), None);
+        // `bar` names the package, so `bar.Outer.Missing` does not search further out
+        assert_eq!(lookup("foo.bar.Outer", "bar.Outer.Missing"), None);
+    }
+
+    #[test]
+    fn test_resolve_types() {
+        let imported = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            package common;
+            enum Status { STATUS_UNKNOWN = 0; }
+            message Id { string value = 1; }
+        "#,
+        )
+        .unwrap();
+
+        let mut proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            package app;
+            message User {
+                message Address { string city = 1; }
+                common.Id id = 1;
+                Address address = 2;
+                map<string, .common.Status> statuses = 3;
+                oneof contact { Address other = 4; }
+            }
+            service Users {
+                rpc Get(common.Id) returns (User);
+            }
+        "#,
+        )
+        .unwrap();
+
+        resolve_types(&mut proto_file, &[&imported]).unwrap();
+
+        let user = &proto_file.messages[0];
+        assert_eq!(
+            user.fields[0].typ,
+            FieldType::Message(".common.Id".to_string())
+        );
+        assert_eq!(
+            user.fields[1].typ,
+            FieldType::Message(".app.User.Address".to_string())
+        );
+        assert_eq!(
+            user.fields[2].typ,
+            FieldType::Map(
+                Box::new(FieldType::String),
+                Box::new(FieldType::Enum(".common.Status".to_string()))
+            )
+        );
+        assert_eq!(
+            user.oneofs[0].fields[0].typ,
+            FieldType::Message(".app.User.Address".to_string())
+        );
+
+        let method = &proto_file.services[0].methods[0];
+        assert_eq!(method.input_type, ".common.Id");
+        assert_eq!(method.output_type, ".app.User");
+    }
+
+    #[test]
+    fn test_resolve_unknown_type() {
+        let mut proto_file = parse_proto_file(
+            r#"syntax = "proto3";
+message User {
+  string name = 1;
+  Address address = 2;
+}
+"#,
+        )
+        .unwrap();
+
+        let err = resolve_types(&mut proto_file, &[]).unwrap_err();
+        assert!(matches!(&err, ParseError::UnknownType(name, _) if name == "Address"));
+        assert_eq!(err.location(), Location::new(4, 3));
+
+        let mut proto_file = parse_proto_file(
+            r#"
+            syntax = "proto3";
+            enum Kind { KIND_UNKNOWN = 0; }
+            service Kinds { rpc Get(Kind) returns (Kind); }
+        "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            resolve_types(&mut proto_file, &[]),
+            Err(ParseError::UnknownType(..))
+        ));
+    }
+}
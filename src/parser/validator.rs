@@ -0,0 +1,444 @@
+//! Semantic checks of parsed files
+//!
+//! The parser accepts any number literal as a field number or enum value and
+//! does not look at other fields. The validator normalizes these numbers to
+//! the integer types of protobuf, [`field_tag`] and [`enum_value_number`], and
+//! checks the fields of every message against each other and against the
+//! message's `reserved` declarations, the way protoc does before generating
+//! code.
+
+use std::collections::{HashMap, HashSet};
+
+use super::ast::{
+    Enum, EnumValue, Extend, Field, Message, NumberValue, ProtoFile, Reserved, ReservedKind,
+    MAX_FIELD_NUMBER,
+};
+use super::ParseError;
+
+/// Field numbers reserved for the protobuf implementation
+const IMPLEMENTATION_RANGE: std::ops::RangeInclusive<u32> = 19_000..=19_999;
+
+/// Returns the tag of a field, the number it is encoded with.
+///
+/// # Arguments
+///
+/// * `field` - The field whose number to convert.
+///
+/// # Returns
+///
+/// * `Result<u32, ParseError>` - The tag, or `InvalidFieldNumber` at the
+///   number if it is a float, is not positive or is greater than
+///   [`MAX_FIELD_NUMBER`].
+pub fn field_tag(field: &Field) -> Result<u32, ParseError> {
+    let error = |problem: String| {
+        ParseError::InvalidFieldNumber(
+            format!("{} of field '{}' {}", field.number, field.name, problem),
+            field.number_span.location(),
+        )
+    };
+    let number = match field.number {
+        NumberValue::DecimalInt(n) | NumberValue::Octal(n) | NumberValue::Hex(n) => n,
+        NumberValue::Float(_) => return Err(error("is not an integer".to_string())),
+    };
+    match u32::try_from(number) {
+        Err(_) if number < 0 => Err(error("is negative".to_string())),
+        Ok(0) => Err(error("is zero; field numbers start at 1".to_string())),
+        Ok(tag) if tag <= MAX_FIELD_NUMBER as u32 => Ok(tag),
+        _ => Err(error(format!(
+            "is greater than the maximum {}",
+            MAX_FIELD_NUMBER
+        ))),
+    }
+}
+
+/// Returns the number of an enum value.
+///
+/// # Arguments
+///
+/// * `value` - The enum value whose number to convert.
+///
+/// # Returns
+///
+/// * `Result<i32, ParseError>` - The number, or `InvalidEnumValue` at the
+///   value if its number is a float or does not fit in an `i32`.
+pub fn enum_value_number(value: &EnumValue) -> Result<i32, ParseError> {
+    let error = |problem: &str| {
+        ParseError::InvalidEnumValue(
+            format!("{} of '{}' {}", value.number, value.name, problem),
+            value.span.location(),
+        )
+    };
+    match value.number {
+        NumberValue::DecimalInt(n) | NumberValue::Octal(n) | NumberValue::Hex(n) => {
+            i32::try_from(n).map_err(|_| error("does not fit in a 32-bit integer"))
+        }
+        NumberValue::Float(_) => Err(error("is not an integer")),
+    }
+}
+
+/// Checks the numbers of every field and enum value of a file, and the fields
+/// of every (nested) message.
+///
+/// # Arguments
+///
+/// * `proto_file` - The file to check.
+///
+/// # Returns
+///
+/// * `Vec<ParseError>` - Every error found, empty if the file is valid:
+///   `InvalidFieldNumber` for field and reserved numbers that are not a valid
+///   tag and for field numbers in the implementation range,
+///   `ReservedFieldNumber` for field numbers that are reserved,
+///   `InvalidEnumValue` for enum values that are not an `i32`, and
+///   `DuplicateDefinition` for numbers and names used twice in a message or
+///   names that are reserved.
+pub fn validate_file(proto_file: &ProtoFile) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    for message in &proto_file.messages {
+        validate_message(message, &mut errors);
+    }
+    for enum_def in &proto_file.enums {
+        validate_enum(enum_def, &mut errors);
+    }
+    for extend in &proto_file.extends {
+        validate_extend(extend, &mut errors);
+    }
+    errors
+}
+
+/// Checks the reserved numbers of a message, then its fields, including the
+/// members of its oneofs, and then its nested messages, enums and extends.
+///
+/// Errors are pushed in source order, a duplicate being reported at its
+/// second occurrence.
+///
+/// # Arguments
+///
+/// * `message` - The message to check.
+/// * `errors` - The errors found so far, to which new errors are added.
+pub fn validate_message(message: &Message, errors: &mut Vec<ParseError>) {
+    for reserved in &message.reserved {
+        let (start, end) = match reserved.kind {
+            ReservedKind::Number(n) => (n, n),
+            ReservedKind::Range(start, end) => (start, end),
+            ReservedKind::FieldName(_) => continue,
+        };
+        if start < 1 || end > MAX_FIELD_NUMBER {
+            let range = if start == end {
+                start.to_string()
+            } else {
+                format!("{} to {}", start, end)
+            };
+            errors.push(ParseError::InvalidFieldNumber(
+                format!(
+                    "reserved {} of message '{}' is outside the field numbers 1 to {}",
+                    range, message.name, MAX_FIELD_NUMBER
+                ),
+                reserved.span.location(),
+            ));
+        }
+    }
+
+    let mut fields: Vec<&Field> = message
+        .fields
+        .iter()
+        .chain(message.oneofs.iter().flat_map(|oneof| &oneof.fields))
+        .collect();
+    fields.sort_by_key(|field| field.span.start.offset);
+
+    let mut numbers: HashMap<u32, &str> = HashMap::new();
+    let mut names: HashSet<&str> = HashSet::new();
+    for field in fields {
+        let number_location = field.number_span.location();
+        let location = field.span.location();
+
+        match field_tag(field) {
+            Err(error) => errors.push(error),
+            Ok(number) if IMPLEMENTATION_RANGE.contains(&number) => {
+                errors.push(ParseError::InvalidFieldNumber(
+                    format!(
+                        "{} of field '{}' is in the range 19000 to 19999 reserved for the \
+                         protobuf implementation",
+                        number, field.name
+                    ),
+                    number_location,
+                ));
+            }
+            Ok(number) if is_reserved_number(&message.reserved, number) => {
+                errors.push(ParseError::ReservedFieldNumber(
+                    format!(
+                        "{} of field '{}' is reserved in message '{}'",
+                        number, field.name, message.name
+                    ),
+                    number_location,
+                ));
+            }
+            Ok(number) => {
+                if let Some(other) = numbers.insert(number, &field.name) {
+                    errors.push(ParseError::DuplicateDefinition(
+                        format!(
+                            "field number {} of '{}' is already used by '{}' in message '{}'",
+                            number, field.name, other, message.name
+                        ),
+                        number_location,
+                    ));
+                }
+            }
+        }
+
+        if is_reserved_name(&message.reserved, &field.name) {
+            errors.push(ParseError::DuplicateDefinition(
+                format!(
+                    "field name '{}' is reserved in message '{}'",
+                    field.name, message.name
+                ),
+                location,
+            ));
+        } else if !names.insert(&field.name) {
+            errors.push(ParseError::DuplicateDefinition(
+                format!(
+                    "field '{}' is already defined in message '{}'",
+                    field.name, message.name
+                ),
+                location,
+            ));
+        }
+    }
+
+    for nested in &message.nested_messages {
+        validate_message(nested, errors);
+    }
+    for enum_def in &message.nested_enums {
+        validate_enum(enum_def, errors);
+    }
+    for extend in &message.extends {
+        validate_extend(extend, errors);
+    }
+}
+
+fn validate_enum(enum_def: &Enum, errors: &mut Vec<ParseError>) {
+    for value in &enum_def.values {
+        if let Err(error) = enum_value_number(value) {
+            errors.push(error);
+        }
+    }
+}
+
+/// Checks that the fields of an extend block have valid tags; whether they
+/// are in an extension range of the extendee is left to protoc.
+fn validate_extend(extend: &Extend, errors: &mut Vec<ParseError>) {
+    for field in &extend.fields {
+        if let Err(error) = field_tag(field) {
+            errors.push(error);
+        }
+    }
+}
+
+fn is_reserved_number(reserved: &[Reserved], number: u32) -> bool {
+    let number = i64::from(number);
+    reserved.iter().any(|reserved| match reserved.kind {
+        ReservedKind::Number(n) => i64::from(n) == number,
+        ReservedKind::Range(start, end) => (i64::from(start)..=i64::from(end)).contains(&number),
+        ReservedKind::FieldName(_) => false,
+    })
+}
+
+fn is_reserved_name(reserved: &[Reserved], name: &str) -> bool {
+    reserved
+        .iter()
+        .any(|reserved| matches!(&reserved.kind, ReservedKind::FieldName(n) if n == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::error::Location;
+    use crate::parser::parse_proto_file;
+
+    fn errors(input: &str) -> Vec<(String, Location)> {
+        let proto_file = parse_proto_file(input).unwrap();
+        validate_file(&proto_file)
+            .iter()
+            .map(|error| (error.message(), error.location()))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_message() {
+        let input = r#"
+            syntax = "proto3";
+            message M {
+                reserved 2, 10 to 20;
+                reserved "old";
+                string a = 1;
+                oneof choice {
+                    string b = 3;
+                    int32 c = 536870911;
+                }
+                message Nested {
+                    string a = 1;
+                }
+            }
+        "#;
+        assert_eq!(errors(input), vec![]);
+    }
+
+    #[test]
+    fn test_field_number_range() {
+        let input =
+            "syntax = \"proto3\";\nmessage M {\n  string a = 0;\n  string b = 536870912;\n  \
+                     string c = 19500;\n  string d = 18999;\n}";
+        assert_eq!(
+            errors(input),
+            vec![
+                (
+                    "Invalid field number: 0 of field 'a' is zero; field numbers start at 1"
+                        .to_string(),
+                    Location::new(3, 14)
+                ),
+                (
+                    "Invalid field number: 536870912 of field 'b' is greater than the maximum 536870911"
+                        .to_string(),
+                    Location::new(4, 14)
+                ),
+                (
+                    "Invalid field number: 19500 of field 'c' is in the range 19000 to 19999 \
+                     reserved for the protobuf implementation"
+                        .to_string(),
+                    Location::new(5, 14)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let input = "syntax = \"proto3\";\nmessage M {\n  string a = 1;\n  oneof o {\n    \
+                     string b = 1;\n    string a = 2;\n  }\n}";
+        assert_eq!(
+            errors(input),
+            vec![
+                (
+                    "Duplicate definition: field number 1 of 'b' is already used by 'a' in \
+                     message 'M'"
+                        .to_string(),
+                    Location::new(5, 16)
+                ),
+                (
+                    "Duplicate definition: field 'a' is already defined in message 'M'".to_string(),
+                    Location::new(6, 5)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reserved_collisions() {
+        let input = "syntax = \"proto3\";\nmessage Outer {\n  message M {\n    reserved 3, 5 to 7;\n    \
+                     reserved \"gone\";\n    string a = 3;\n    string b = 6;\n    string gone = 8;\n  \
+                     }\n}";
+        let errors = errors(input);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0].0,
+            "Reserved field number: 3 of field 'a' is reserved in message 'M'"
+        );
+        assert_eq!(errors[1].1, Location::new(7, 16));
+        assert_eq!(
+            errors[2],
+            (
+                "Duplicate definition: field name 'gone' is reserved in message 'M'".to_string(),
+                Location::new(8, 5)
+            )
+        );
+
+        // The range of valid field numbers does not explain a reserved one
+        let proto_file = parse_proto_file(input).unwrap();
+        assert_eq!(
+            validate_file(&proto_file)[0].help(),
+            Some("use a number that is not reserved, or remove it from `reserved`")
+        );
+    }
+
+    #[test]
+    fn test_reserved_number_range() {
+        let input = "syntax = \"proto3\";\nmessage M {\n  reserved 0, 2 to max, 0x10;\n  \
+                     reserved 536870911 to 536870912, -3 to -1;\n}";
+        assert_eq!(
+            errors(input),
+            vec![
+                (
+                    "Invalid field number: reserved 0 of message 'M' is outside the field \
+                     numbers 1 to 536870911"
+                        .to_string(),
+                    Location::new(3, 12)
+                ),
+                (
+                    "Invalid field number: reserved 536870911 to 536870912 of message 'M' is \
+                     outside the field numbers 1 to 536870911"
+                        .to_string(),
+                    Location::new(4, 12)
+                ),
+                (
+                    "Invalid field number: reserved -3 to -1 of message 'M' is outside the \
+                     field numbers 1 to 536870911"
+                        .to_string(),
+                    Location::new(4, 36)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_integer_field_numbers() {
+        let input = "syntax = \"proto3\";\nmessage M {\n  int32 a = 2.5;\n  int32 b = -1;\n  \
+                     int32 c = 0x100000000;\n}\nextend M {\n  int32 d = 1e3;\n}";
+        assert_eq!(
+            errors(input),
+            vec![
+                (
+                    "Invalid field number: 2.5 of field 'a' is not an integer".to_string(),
+                    Location::new(3, 13)
+                ),
+                (
+                    "Invalid field number: -1 of field 'b' is negative".to_string(),
+                    Location::new(4, 13)
+                ),
+                (
+                    "Invalid field number: 0x100000000 of field 'c' is greater than the \
+                     maximum 536870911"
+                        .to_string(),
+                    Location::new(5, 13)
+                ),
+                (
+                    "Invalid field number: 1000.0 of field 'd' is not an integer".to_string(),
+                    Location::new(8, 13)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enum_value_numbers() {
+        let input = "syntax = \"proto3\";\nenum E {\n  A = 0;\n  B = -2147483648;\n  \
+                     C = 2147483648;\n  D = 1.5;\n}";
+        let proto_file = parse_proto_file(input).unwrap();
+        let values = &proto_file.enums[0].values;
+        assert_eq!(enum_value_number(&values[1]).unwrap(), i32::MIN);
+
+        assert_eq!(
+            errors(input),
+            vec![
+                (
+                    "Invalid enum value: 2147483648 of 'C' does not fit in a 32-bit integer"
+                        .to_string(),
+                    Location::new(5, 3)
+                ),
+                (
+                    "Invalid enum value: 1.5 of 'D' is not an integer".to_string(),
+                    Location::new(6, 3)
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Parsing of `// @zod { ... }` comment annotations
+//!
+//! The body of an annotation uses the same syntax as aggregate option values,
+//! so it is tokenized and parsed with the regular option value parser and then
+//! checked against the options described in `docs/zod_commets_format.md`.
+
+use super::ast::{OptionValue, ZodAnnotation, ZodArrayRules};
+use super::error::Location;
+use super::lexer::{tokenize, Token, TokenWithLocation};
+use super::{parse_option_value, ParseError};
+
+/// Parses a comment into a [`ZodAnnotation`] if it is a `@zod` annotation.
+///
+/// Comments that do not start with `@zod` (including the `@zod-version`
+/// marker) are not annotations and yield `Ok(None)`.
+///
+/// # Arguments
+///
+/// * `comment` - The comment text, including the `//` or `/* */` delimiters.
+/// * `location` - The location of the comment in the source file.
+///
+/// # Returns
+///
+/// * `Result<Option<ZodAnnotation>, ParseError>` - The annotation, `None` for
+///   other comments, or a ParseError if the annotation is malformed.
+pub(crate) fn parse_zod_comment(
+    comment: &str,
+    location: Location,
+) -> Result<Option<ZodAnnotation>, ParseError> {
+    let text = match comment.strip_prefix("//") {
+        Some(text) => text,
+        None => comment
+            .strip_prefix("/*")
+            .map(|text| text.strip_suffix("*/").unwrap_or(text))
+            .unwrap_or(comment),
+    };
+
+    let rest = match text.trim_start().strip_prefix("@zod") {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    if rest
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Ok(None);
+    }
+
+    let body = rest.trim_start();
+    if !body.starts_with('{') {
+        return Err(ParseError::InvalidSyntax(
+            "Expected '{' after @zod".to_string(),
+            location,
+        ));
+    }
+
+    // Report errors at their position in the file rather than in the comment
+    let offset = comment[..comment.len() - body.len()].chars().count();
+    let tokens = tokenize(body)?
+        .into_iter()
+        .map(|t| TokenWithLocation {
+            location: if t.location.line == 1 {
+                Location::new(
+                    location.line,
+                    location.column + offset + t.location.column - 1,
+                )
+            } else {
+                Location::new(location.line + t.location.line - 1, t.location.column)
+            },
+            ..t
+        })
+        .collect::<Vec<_>>();
+
+    let mut tokens = tokens.into_iter().peekable();
+    let (value, _) = parse_option_value(&mut tokens).map_err(|e| match e {
+        // The annotation ends with the comment, not with the file
+        ParseError::UnexpectedEndOfInput(_) => {
+            ParseError::UnexpectedEndOfInput(end_of_comment(comment, location))
+        }
+        other => other,
+    })?;
+    if let Some(extra) = tokens.find(|t| !matches!(t.token, Token::Comment(_))) {
+        return Err(ParseError::UnexpectedToken(
+            format!("Unexpected {:?} after @zod annotation", extra.token),
+            extra.location,
+        ));
+    }
+
+    let fields = match value {
+        OptionValue::Message(fields) => fields,
+        _ => unreachable!("a value starting with '{{' is an aggregate"),
+    };
+
+    let mut annotation = ZodAnnotation::default();
+    for (key, value) in fields {
+        match key.as_str() {
+            "min" => annotation.min = Some(number(&key, value, location)?),
+            "max" => annotation.max = Some(number(&key, value, location)?),
+            "regex" => annotation.regex = Some(string(&key, value, location)?),
+            "email" => annotation.email = flag(&key, value, location)?,
+            "url" => annotation.url = flag(&key, value, location)?,
+            "uuid" => annotation.uuid = flag(&key, value, location)?,
+            "positive" => annotation.positive = flag(&key, value, location)?,
+            "negative" => annotation.negative = flag(&key, value, location)?,
+            "int" => annotation.int = flag(&key, value, location)?,
+            "description" => annotation.description = Some(string(&key, value, location)?),
+            "default" => annotation.default = Some(unescape_value(value)),
+            "optional" => annotation.optional = flag(&key, value, location)?,
+            "nullable" => annotation.nullable = flag(&key, value, location)?,
+            "array" => annotation.array = Some(array_rules(value, location)?),
+            "custom" => annotation.custom = Some(string(&key, value, location)?),
+            _ => {
+                return Err(ParseError::InvalidSyntax(
+                    format!("Unknown @zod option '{}'", key),
+                    location,
+                ))
+            }
+        }
+    }
+
+    Ok(Some(annotation))
+}
+
+fn array_rules(value: OptionValue, location: Location) -> Result<ZodArrayRules, ParseError> {
+    let fields = match value {
+        OptionValue::Message(fields) => fields,
+        other => return Err(invalid_value("array", "an object", &other, location)),
+    };
+
+    let mut rules = ZodArrayRules::default();
+    for (key, value) in fields {
+        let target = match key.as_str() {
+            "min" => &mut rules.min,
+            "max" => &mut rules.max,
+            "length" => &mut rules.length,
+            _ => {
+                return Err(ParseError::InvalidSyntax(
+                    format!("Unknown @zod array option '{}'", key),
+                    location,
+                ))
+            }
+        };
+        *target = Some(match value {
+            OptionValue::DecimalInt(n) | OptionValue::Octal(n) | OptionValue::Hex(n) if n >= 0 => {
+                n as u64
+            }
+            other => {
+                return Err(invalid_value(
+                    &key,
+                    "a non-negative integer",
+                    &other,
+                    location,
+                ))
+            }
+        });
+    }
+
+    Ok(rules)
+}
+
+fn number(key: &str, value: OptionValue, location: Location) -> Result<f64, ParseError> {
+    match value {
+        OptionValue::DecimalInt(n) | OptionValue::Octal(n) | OptionValue::Hex(n) => Ok(n as f64),
+        OptionValue::Float(f) if f.is_finite() => Ok(f),
+        other => Err(invalid_value(key, "a number", &other, location)),
+    }
+}
+
+fn string(key: &str, value: OptionValue, location: Location) -> Result<String, ParseError> {
+    match value {
+        OptionValue::String(s) => Ok(unescape(&s)),
+        other => Err(invalid_value(key, "a string", &other, location)),
+    }
+}
+
+fn flag(key: &str, value: OptionValue, location: Location) -> Result<bool, ParseError> {
+    match value {
+        OptionValue::Bool(b) => Ok(b),
+        other => Err(invalid_value(key, "true or false", &other, location)),
+    }
+}
+
+fn invalid_value(key: &str, expected: &str, found: &OptionValue, location: Location) -> ParseError {
+    ParseError::InvalidSyntax(
+        format!(
+            "Invalid value for @zod option '{}': expected {}, found {:?}",
+            key, expected, found
+        ),
+        location,
+    )
+}
+
+/// Unescapes the string literals inside a `default` value.
+fn unescape_value(value: OptionValue) -> OptionValue {
+    match value {
+        OptionValue::String(s) => OptionValue::String(unescape(&s)),
+        OptionValue::List(values) => {
+            OptionValue::List(values.into_iter().map(unescape_value).collect())
+        }
+        OptionValue::Message(fields) => OptionValue::Message(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, unescape_value(value)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Resolves the escapes of a string literal. Unknown escapes such as `\d` are
+/// kept as written, so regular expressions do not need to be double-escaped.
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some(c @ ('\\' | '"' | '\'')) => result.push(c),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Returns the location right after the last character of a comment that
+/// starts at `location`.
+fn end_of_comment(comment: &str, location: Location) -> Location {
+    match comment.rsplit_once('\n') {
+        Some((before, last)) => Location::new(
+            location.line + before.matches('\n').count() + 1,
+            last.chars().count() + 1,
+        ),
+        None => Location::new(location.line, location.column + comment.chars().count()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(comment: &str) -> Result<Option<ZodAnnotation>, ParseError> {
+        parse_zod_comment(comment, Location::new(3, 20))
+    }
+
+    #[test]
+    fn test_parse_zod_comment() {
+        let annotation = parse(
+            r#"// @zod { min: 3, max: 50.5, regex: "^\\d+$", email: true, description: "User's name" }"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(annotation.min, Some(3.0));
+        assert_eq!(annotation.max, Some(50.5));
+        assert_eq!(annotation.regex.as_deref(), Some(r"^\d+$"));
+        assert!(annotation.email);
+        assert!(!annotation.url);
+        assert_eq!(annotation.description.as_deref(), Some("User's name"));
+
+        let annotation = parse(
+            r#"/* @zod { array: { min: 1, length: 4 }, default: "STANDARD", nullable: true, custom: "check" } */"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            annotation.array,
+            Some(ZodArrayRules {
+                min: Some(1),
+                max: None,
+                length: Some(4),
+            })
+        );
+        assert_eq!(
+            annotation.default,
+            Some(OptionValue::String("STANDARD".to_string()))
+        );
+        assert!(annotation.nullable);
+        assert_eq!(annotation.custom.as_deref(), Some("check"));
+    }
+
+    #[test]
+    fn test_parse_non_annotation_comments() {
+        assert_eq!(parse("// just a comment").unwrap(), None);
+        assert_eq!(parse("// @zod-version: 1.0").unwrap(), None);
+        assert_eq!(parse("/* @zodiac { min: 1 } */").unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_annotations() {
+        assert!(matches!(
+            parse("// @zod { minimum: 3 }"),
+            Err(ParseError::InvalidSyntax(msg, _)) if msg.contains("minimum")
+        ));
+        assert!(matches!(
+            parse("// @zod { email: \"yes\" }"),
+            Err(ParseError::InvalidSyntax(msg, _)) if msg.contains("true or false")
+        ));
+        assert!(matches!(
+            parse("// @zod { array: { min: -1 } }"),
+            Err(ParseError::InvalidSyntax(..))
+        ));
+        assert!(matches!(
+            parse("// @zod min: 3"),
+            Err(ParseError::InvalidSyntax(..))
+        ));
+
+        // `3` is the 15th character of the comment, which starts at 3:20
+        assert_eq!(
+            parse("// @zod { min 3 }").unwrap_err().location(),
+            Location::new(3, 34)
+        );
+
+        // A cut-off annotation ends at the end of its comment
+        assert!(matches!(
+            parse("// @zod { min:"),
+            Err(ParseError::UnexpectedEndOfInput(loc)) if loc == Location::new(3, 34)
+        ));
+        assert!(matches!(
+            parse("/* @zod {\n  min: */"),
+            Err(ParseError::UnexpectedEndOfInput(loc)) if loc == Location::new(4, 10)
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+//! protoc plugin protocol
+//!
+//! protoc and buf run `protoc-gen-zod` with a `CodeGeneratorRequest` on stdin
+//! and expect a `CodeGeneratorResponse` on stdout. The request carries the
+//! descriptors of the files to generate and of everything they import, so the
+//! plugin needs no include paths of its own.
+//!
+//! Generator options are passed as a comma-separated parameter, e.g.
+//! `--zod_opt=layout=flat,int64=bigint,json_names`.
+
+use prost_types::compiler::code_generator_response::{Feature, File};
+use prost_types::compiler::{CodeGeneratorRequest, CodeGeneratorResponse};
+
+use crate::descriptor::unit_from_descriptors;
+use crate::generator::{generate_zod_for_unit, output_path, GeneratorOptions, Layout};
+use crate::ConversionError;
+
+/// Runs the generator for a plugin request.
+///
+/// Errors are reported through the `error` field of the response, which
+/// protoc prints before failing.
+///
+/// # Arguments
+///
+/// * `request` - The request decoded from stdin
+///
+/// # Returns
+///
+/// * `CodeGeneratorResponse` - One `.zod.ts` file per file to generate
+pub fn generate(request: &CodeGeneratorRequest) -> CodeGeneratorResponse {
+    match generate_files(request) {
+        Ok(file) => CodeGeneratorResponse {
+            file,
+            supported_features: Some(Feature::Proto3Optional as u64),
+            ..Default::default()
+        },
+        Err(e) => CodeGeneratorResponse {
+            error: Some(e.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+fn generate_files(request: &CodeGeneratorRequest) -> Result<Vec<File>, ConversionError> {
+    let (layout, options) = parse_parameter(request.parameter())?;
+
+    // protoc lists every file after the files it imports
+    let unit = unit_from_descriptors(&request.proto_file, request.file_to_generate.clone())?;
+
+    let mut generated = Vec::new();
+    for name in &request.file_to_generate {
+        generated.push(File {
+            name: Some(output_path(name, layout)),
+            content: Some(generate_zod_for_unit(&unit, name, layout, &options)?),
+            ..Default::default()
+        });
+    }
+    Ok(generated)
+}
+
+/// Parses the comma-separated plugin parameter into the layout and the
+/// generator options.
+///
+/// # Arguments
+///
+/// * `parameter` - The parameter, e.g. `layout=flat,int64=bigint`
+///
+/// # Returns
+///
+/// * `Result<(Layout, GeneratorOptions), ConversionError>` - The parsed
+///   settings, or a `GenerationError` for an unknown or malformed option
+pub fn parse_parameter(parameter: &str) -> Result<(Layout, GeneratorOptions), ConversionError> {
+    let mut layout = Layout::Tree;
+    let mut options = GeneratorOptions::default();
+
+    for option in parameter
+        .split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+    {
+        let (key, value) = match option.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim())),
+            None => (option, None),
+        };
+        let error = |message: String| ConversionError::GenerationError(message);
+
+        match (key, value) {
+            ("layout", Some(value)) => layout = value.parse().map_err(error)?,
+            ("int64", Some(value)) => options.int64 = value.parse().map_err(error)?,
+            ("enum_style", Some(value)) => options.enum_style = value.parse().map_err(error)?,
+            ("oneof_style", Some(value)) => options.oneof_style = value.parse().map_err(error)?,
+            ("timestamp", Some(value)) => options.timestamp = value.parse().map_err(error)?,
+            ("custom_validators", Some(value)) => {
+                options.custom_validators = Some(value.to_string())
+            }
+            ("json_names", value) => options.json_names = parse_bool(key, value)?,
+            ("export_types", value) => options.export_types = parse_bool(key, value)?,
+            ("comments", value) => options.comments = parse_bool(key, value)?,
+            _ => {
+                return Err(ConversionError::GenerationError(format!(
+                    "Unknown plugin option '{}'",
+                    option
+                )))
+            }
+        }
+    }
+
+    Ok((layout, options))
+}
+
+/// Parses the value of a flag, which may be given without one to enable it.
+fn parse_bool(key: &str, value: Option<&str>) -> Result<bool, ConversionError> {
+    match value {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(other) => Err(ConversionError::GenerationError(format!(
+            "Invalid value '{}' for plugin option '{}', expected 'true' or 'false'",
+            other, key
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::Int64Mode;
+    use crate::loader::Loader;
+    use prost::Message;
+    use prost_types::FileDescriptorSet;
+    use std::path::{Path, PathBuf};
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("files/descriptors")
+    }
+
+    #[test]
+    fn test_generate_matches_loader() {
+        let set = std::fs::read(fixtures().join("shop.binpb")).unwrap();
+        let set = FileDescriptorSet::decode(set.as_slice()).unwrap();
+        let request = CodeGeneratorRequest {
+            file_to_generate: vec!["shop.proto".to_string()],
+            parameter: Some("int64=bigint".to_string()),
+            proto_file: set.file,
+            ..Default::default()
+        };
+
+        let response = generate(&request);
+        assert_eq!(response.error, None);
+        assert_eq!(response.file.len(), 1);
+        assert_eq!(response.file[0].name(), "shop.zod.ts");
+
+        // The same schemas are generated from the source
+        let mut unit = Loader::new([fixtures()])
+            .load(&[fixtures().join("shop.proto")])
+            .unwrap();
+        unit.resolve().unwrap();
+        let (layout, options) = parse_parameter("int64=bigint").unwrap();
+        let expected = generate_zod_for_unit(&unit, "shop.proto", layout, &options).unwrap();
+        assert_eq!(response.file[0].content(), expected);
+    }
+
+    #[test]
+    fn test_generate_proto3_optional() {
+        let set = std::fs::read(fixtures().join("shop.binpb")).unwrap();
+        let set = FileDescriptorSet::decode(set.as_slice()).unwrap();
+        let order = &set.file[1].message_type[0];
+        assert!(order.field[4].proto3_optional());
+
+        let request = CodeGeneratorRequest {
+            file_to_generate: vec!["shop.proto".to_string()],
+            proto_file: set.file,
+            ..Default::default()
+        };
+        let response = generate(&request);
+        assert_eq!(
+            response.supported_features,
+            Some(Feature::Proto3Optional as u64)
+        );
+
+        // `note` keeps its presence, while the other proto3 fields do not
+        let content = response.file[0].content();
+        assert!(content.contains("  note: z.string().optional(),\n"));
+        assert!(content.contains("  status: Order_Status,\n"));
+    }
+
+    #[test]
+    fn test_generate_reports_errors() {
+        let request = CodeGeneratorRequest {
+            parameter: Some("layout=nested".to_string()),
+            ..Default::default()
+        };
+        let response = generate(&request);
+        assert!(response.error.unwrap().contains("Unknown layout"));
+        assert!(response.file.is_empty());
+    }
+
+    #[test]
+    fn test_parse_parameter() {
+        let (layout, options) =
+            parse_parameter("layout=flat, int64=bigint,json_names,comments=false").unwrap();
+        assert_eq!(layout, Layout::Flat);
+        assert_eq!(options.int64, Int64Mode::BigInt);
+        assert!(options.json_names);
+        assert!(!options.comments);
+        assert!(options.export_types);
+
+        assert_eq!(
+            parse_parameter("").unwrap(),
+            (Layout::Tree, GeneratorOptions::default())
+        );
+        assert!(parse_parameter("nested").is_err());
+        assert!(parse_parameter("json_names=yes").is_err());
+    }
+}
@@ -1,3 +1,13 @@
+#[allow(clippy::module_inception)]
 mod visitor;
+mod visitor_mut;
 
-pub use self::visitor::Visitor;
+pub use self::visitor::{
+    walk_enum, walk_enum_value, walk_extend, walk_extension_range, walk_field, walk_message,
+    walk_method, walk_oneof, walk_proto_file, walk_service, Visitor,
+};
+pub use self::visitor_mut::{
+    walk_enum_mut, walk_enum_value_mut, walk_extend_mut, walk_extension_range_mut, walk_field_mut,
+    walk_message_mut, walk_method_mut, walk_oneof_mut, walk_proto_file_mut, walk_service_mut,
+    VisitorMut,
+};
@@ -1,6 +1,366 @@
-use crate::parser::ast::ProtoFile;
+//! Read-only traversal of the AST
+//!
+//! Every `visit_*` method of [`Visitor`] defaults to the `walk_*` function of
+//! the same node, which visits the node's children. An analysis overrides
+//! only the methods of the nodes it is interested in, and calls the `walk_*`
+//! function from its override to keep descending into the children.
 
+use crate::parser::ast::{
+    Enum, EnumValue, EnumValueOption, Extend, ExtensionRange, Field, Import, Message, Method,
+    OneOf, ProtoFile, ProtoOption, Reserved, Service,
+};
+
+/// A read-only visitor over the nodes of a [`ProtoFile`]
+///
+/// Children are visited in the order of the fields of their parent node in
+/// `ast.rs`, not in source order, e.g. the fields of a message before its
+/// nested messages and enums, and those before its options.
 pub trait Visitor {
-    fn visit_proto_file(&mut self, proto_file: &ProtoFile);
-    // Other visit methods...
+    fn visit_proto_file(&mut self, proto_file: &ProtoFile) {
+        walk_proto_file(self, proto_file);
+    }
+
+    fn visit_import(&mut self, _import: &Import) {}
+
+    fn visit_option(&mut self, _option: &ProtoOption) {}
+
+    fn visit_message(&mut self, message: &Message) {
+        walk_message(self, message);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        walk_field(self, field);
+    }
+
+    fn visit_oneof(&mut self, oneof: &OneOf) {
+        walk_oneof(self, oneof);
+    }
+
+    fn visit_reserved(&mut self, _reserved: &Reserved) {}
+
+    fn visit_extension_range(&mut self, range: &ExtensionRange) {
+        walk_extension_range(self, range);
+    }
+
+    fn visit_extend(&mut self, extend: &Extend) {
+        walk_extend(self, extend);
+    }
+
+    fn visit_enum(&mut self, enum_def: &Enum) {
+        walk_enum(self, enum_def);
+    }
+
+    fn visit_enum_value(&mut self, value: &EnumValue) {
+        walk_enum_value(self, value);
+    }
+
+    fn visit_enum_value_option(&mut self, _option: &EnumValueOption) {}
+
+    fn visit_service(&mut self, service: &Service) {
+        walk_service(self, service);
+    }
+
+    fn visit_method(&mut self, method: &Method) {
+        walk_method(self, method);
+    }
+}
+
+/// Visits the imports, options, messages, enums, services and extends of a
+/// file.
+pub fn walk_proto_file<V: Visitor + ?Sized>(visitor: &mut V, proto_file: &ProtoFile) {
+    for import in &proto_file.imports {
+        visitor.visit_import(import);
+    }
+    for option in &proto_file.options {
+        visitor.visit_option(option);
+    }
+    for message in &proto_file.messages {
+        visitor.visit_message(message);
+    }
+    for enum_def in &proto_file.enums {
+        visitor.visit_enum(enum_def);
+    }
+    for service in &proto_file.services {
+        visitor.visit_service(service);
+    }
+    for extend in &proto_file.extends {
+        visitor.visit_extend(extend);
+    }
+}
+
+/// Visits the fields, oneofs, nested definitions, options, reserved
+/// declarations, extension ranges and extends of a message.
+pub fn walk_message<V: Visitor + ?Sized>(visitor: &mut V, message: &Message) {
+    for field in &message.fields {
+        visitor.visit_field(field);
+    }
+    for oneof in &message.oneofs {
+        visitor.visit_oneof(oneof);
+    }
+    for nested in &message.nested_messages {
+        visitor.visit_message(nested);
+    }
+    for enum_def in &message.nested_enums {
+        visitor.visit_enum(enum_def);
+    }
+    for option in &message.options {
+        visitor.visit_option(option);
+    }
+    for reserved in &message.reserved {
+        visitor.visit_reserved(reserved);
+    }
+    for range in &message.extensions {
+        visitor.visit_extension_range(range);
+    }
+    for extend in &message.extends {
+        visitor.visit_extend(extend);
+    }
+}
+
+/// Visits the options of a field.
+pub fn walk_field<V: Visitor + ?Sized>(visitor: &mut V, field: &Field) {
+    for option in &field.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the fields and options of a oneof.
+pub fn walk_oneof<V: Visitor + ?Sized>(visitor: &mut V, oneof: &OneOf) {
+    for field in &oneof.fields {
+        visitor.visit_field(field);
+    }
+    for option in &oneof.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the options of an extension range.
+pub fn walk_extension_range<V: Visitor + ?Sized>(visitor: &mut V, range: &ExtensionRange) {
+    for option in &range.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the fields of an extend block.
+pub fn walk_extend<V: Visitor + ?Sized>(visitor: &mut V, extend: &Extend) {
+    for field in &extend.fields {
+        visitor.visit_field(field);
+    }
+}
+
+/// Visits the values and options of an enum.
+pub fn walk_enum<V: Visitor + ?Sized>(visitor: &mut V, enum_def: &Enum) {
+    for value in &enum_def.values {
+        visitor.visit_enum_value(value);
+    }
+    for option in &enum_def.options {
+        visitor.visit_enum_value_option(option);
+    }
+}
+
+/// Visits the options of an enum value.
+pub fn walk_enum_value<V: Visitor + ?Sized>(visitor: &mut V, value: &EnumValue) {
+    for option in &value.options {
+        visitor.visit_enum_value_option(option);
+    }
+}
+
+/// Visits the methods and options of a service.
+pub fn walk_service<V: Visitor + ?Sized>(visitor: &mut V, service: &Service) {
+    for method in &service.methods {
+        visitor.visit_method(method);
+    }
+    for option in &service.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the options of a method.
+pub fn walk_method<V: Visitor + ?Sized>(visitor: &mut V, method: &Method) {
+    for option in &method.options {
+        visitor.visit_option(option);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_proto_file;
+
+    const INPUT: &str = r#"
+        syntax = "proto2";
+        import "other.proto";
+        option java_package = "com.example";
+
+        message Outer {
+            option deprecated = true;
+            message Inner {
+                optional string name = 1 [deprecated = true];
+            }
+            enum Kind {
+                KIND_A = 0 [deprecated = true];
+            }
+            optional Inner inner = 1;
+            oneof choice {
+                string text = 2;
+                int32 number = 3;
+            }
+            reserved 4;
+            extensions 100 to 199;
+            extend Outer {
+                optional int32 extra = 100;
+            }
+        }
+
+        service Greeter {
+            rpc Greet(Outer) returns (Outer) { option deprecated = true; }
+        }
+    "#;
+
+    /// Records the names of the visited nodes
+    #[derive(Default)]
+    struct Recorder {
+        visited: Vec<String>,
+    }
+
+    impl Visitor for Recorder {
+        fn visit_import(&mut self, import: &Import) {
+            self.visited.push(format!("import {}", import.path));
+        }
+
+        fn visit_option(&mut self, option: &ProtoOption) {
+            self.visited.push(format!("option {}", option.name));
+        }
+
+        fn visit_message(&mut self, message: &Message) {
+            self.visited.push(format!("message {}", message.name));
+            walk_message(self, message);
+        }
+
+        fn visit_field(&mut self, field: &Field) {
+            self.visited.push(format!("field {}", field.name));
+            walk_field(self, field);
+        }
+
+        fn visit_oneof(&mut self, oneof: &OneOf) {
+            self.visited.push(format!("oneof {}", oneof.name));
+            walk_oneof(self, oneof);
+        }
+
+        fn visit_reserved(&mut self, _reserved: &Reserved) {
+            self.visited.push("reserved".to_string());
+        }
+
+        fn visit_extension_range(&mut self, range: &ExtensionRange) {
+            self.visited.push(format!("extensions {}", range.start));
+        }
+
+        fn visit_extend(&mut self, extend: &Extend) {
+            self.visited.push(format!("extend {}", extend.extendee));
+            walk_extend(self, extend);
+        }
+
+        fn visit_enum(&mut self, enum_def: &Enum) {
+            self.visited.push(format!("enum {}", enum_def.name));
+            walk_enum(self, enum_def);
+        }
+
+        fn visit_enum_value(&mut self, value: &EnumValue) {
+            self.visited.push(format!("value {}", value.name));
+            walk_enum_value(self, value);
+        }
+
+        fn visit_enum_value_option(&mut self, option: &EnumValueOption) {
+            self.visited.push(format!("enum option {}", option.name));
+        }
+
+        fn visit_service(&mut self, service: &Service) {
+            self.visited.push(format!("service {}", service.name));
+            walk_service(self, service);
+        }
+
+        fn visit_method(&mut self, method: &Method) {
+            self.visited.push(format!("method {}", method.name));
+            walk_method(self, method);
+        }
+    }
+
+    #[test]
+    fn test_visit_order() {
+        let proto_file = parse_proto_file(INPUT).unwrap();
+        let mut recorder = Recorder::default();
+        recorder.visit_proto_file(&proto_file);
+
+        assert_eq!(
+            recorder.visited,
+            vec![
+                "import other.proto",
+                "option java_package",
+                "message Outer",
+                "field inner",
+                "oneof choice",
+                "field text",
+                "field number",
+                "message Inner",
+                "field name",
+                "option deprecated",
+                "enum Kind",
+                "value KIND_A",
+                "enum option deprecated",
+                "option deprecated",
+                "reserved",
+                "extensions 100",
+                "extend Outer",
+                "field extra",
+                "service Greeter",
+                "method Greet",
+                "option deprecated",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_message_children_order() {
+        let input = r#"
+            syntax = "proto3";
+            message M {
+                option deprecated = true;
+                enum E { E_A = 0; }
+                message N {}
+                int32 a = 1;
+            }
+        "#;
+        let proto_file = parse_proto_file(input).unwrap();
+        let mut recorder = Recorder::default();
+        recorder.visit_proto_file(&proto_file);
+
+        assert_eq!(
+            recorder.visited,
+            vec![
+                "message M",
+                "field a",
+                "message N",
+                "enum E",
+                "value E_A",
+                "option deprecated",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_methods_walk_children() {
+        /// Counts fields only, relying on the defaults to reach them
+        struct FieldCounter(usize);
+
+        impl Visitor for FieldCounter {
+            fn visit_field(&mut self, _field: &Field) {
+                self.0 += 1;
+            }
+        }
+
+        let proto_file = parse_proto_file(INPUT).unwrap();
+        let mut counter = FieldCounter(0);
+        counter.visit_proto_file(&proto_file);
+        assert_eq!(counter.0, 5);
+    }
 }
@@ -0,0 +1,267 @@
+//! Mutable traversal of the AST
+//!
+//! [`VisitorMut`] is the counterpart of [`Visitor`](super::Visitor) for
+//! rewrite passes, e.g. renaming fields or removing deprecated ones. Every
+//! `visit_*` method defaults to the `walk_*_mut` function of the same node,
+//! which visits the node's children. A pass overrides only the methods of the
+//! nodes it rewrites, and calls the `walk_*_mut` function from its override
+//! to keep descending into the children.
+
+use crate::parser::ast::{
+    Enum, EnumValue, EnumValueOption, Extend, ExtensionRange, Field, Import, Message, Method,
+    OneOf, ProtoFile, ProtoOption, Reserved, Service,
+};
+
+/// A visitor that can modify the nodes of a [`ProtoFile`]
+///
+/// Children are visited in the same order as by
+/// [`Visitor`](super::Visitor). A method can add or remove children of its
+/// node before walking it, e.g. `visit_message` can drop fields with
+/// `retain`.
+pub trait VisitorMut {
+    fn visit_proto_file(&mut self, proto_file: &mut ProtoFile) {
+        walk_proto_file_mut(self, proto_file);
+    }
+
+    fn visit_import(&mut self, _import: &mut Import) {}
+
+    fn visit_option(&mut self, _option: &mut ProtoOption) {}
+
+    fn visit_message(&mut self, message: &mut Message) {
+        walk_message_mut(self, message);
+    }
+
+    fn visit_field(&mut self, field: &mut Field) {
+        walk_field_mut(self, field);
+    }
+
+    fn visit_oneof(&mut self, oneof: &mut OneOf) {
+        walk_oneof_mut(self, oneof);
+    }
+
+    fn visit_reserved(&mut self, _reserved: &mut Reserved) {}
+
+    fn visit_extension_range(&mut self, range: &mut ExtensionRange) {
+        walk_extension_range_mut(self, range);
+    }
+
+    fn visit_extend(&mut self, extend: &mut Extend) {
+        walk_extend_mut(self, extend);
+    }
+
+    fn visit_enum(&mut self, enum_def: &mut Enum) {
+        walk_enum_mut(self, enum_def);
+    }
+
+    fn visit_enum_value(&mut self, value: &mut EnumValue) {
+        walk_enum_value_mut(self, value);
+    }
+
+    fn visit_enum_value_option(&mut self, _option: &mut EnumValueOption) {}
+
+    fn visit_service(&mut self, service: &mut Service) {
+        walk_service_mut(self, service);
+    }
+
+    fn visit_method(&mut self, method: &mut Method) {
+        walk_method_mut(self, method);
+    }
+}
+
+/// Visits the imports, options, messages, enums, services and extends of a
+/// file.
+pub fn walk_proto_file_mut<V: VisitorMut + ?Sized>(visitor: &mut V, proto_file: &mut ProtoFile) {
+    for import in &mut proto_file.imports {
+        visitor.visit_import(import);
+    }
+    for option in &mut proto_file.options {
+        visitor.visit_option(option);
+    }
+    for message in &mut proto_file.messages {
+        visitor.visit_message(message);
+    }
+    for enum_def in &mut proto_file.enums {
+        visitor.visit_enum(enum_def);
+    }
+    for service in &mut proto_file.services {
+        visitor.visit_service(service);
+    }
+    for extend in &mut proto_file.extends {
+        visitor.visit_extend(extend);
+    }
+}
+
+/// Visits the fields, oneofs, nested definitions, options, reserved
+/// declarations, extension ranges and extends of a message.
+pub fn walk_message_mut<V: VisitorMut + ?Sized>(visitor: &mut V, message: &mut Message) {
+    for field in &mut message.fields {
+        visitor.visit_field(field);
+    }
+    for oneof in &mut message.oneofs {
+        visitor.visit_oneof(oneof);
+    }
+    for nested in &mut message.nested_messages {
+        visitor.visit_message(nested);
+    }
+    for enum_def in &mut message.nested_enums {
+        visitor.visit_enum(enum_def);
+    }
+    for option in &mut message.options {
+        visitor.visit_option(option);
+    }
+    for reserved in &mut message.reserved {
+        visitor.visit_reserved(reserved);
+    }
+    for range in &mut message.extensions {
+        visitor.visit_extension_range(range);
+    }
+    for extend in &mut message.extends {
+        visitor.visit_extend(extend);
+    }
+}
+
+/// Visits the options of a field.
+pub fn walk_field_mut<V: VisitorMut + ?Sized>(visitor: &mut V, field: &mut Field) {
+    for option in &mut field.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the fields and options of a oneof.
+pub fn walk_oneof_mut<V: VisitorMut + ?Sized>(visitor: &mut V, oneof: &mut OneOf) {
+    for field in &mut oneof.fields {
+        visitor.visit_field(field);
+    }
+    for option in &mut oneof.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the options of an extension range.
+pub fn walk_extension_range_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    range: &mut ExtensionRange,
+) {
+    for option in &mut range.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the fields of an extend block.
+pub fn walk_extend_mut<V: VisitorMut + ?Sized>(visitor: &mut V, extend: &mut Extend) {
+    for field in &mut extend.fields {
+        visitor.visit_field(field);
+    }
+}
+
+/// Visits the values and options of an enum.
+pub fn walk_enum_mut<V: VisitorMut + ?Sized>(visitor: &mut V, enum_def: &mut Enum) {
+    for value in &mut enum_def.values {
+        visitor.visit_enum_value(value);
+    }
+    for option in &mut enum_def.options {
+        visitor.visit_enum_value_option(option);
+    }
+}
+
+/// Visits the options of an enum value.
+pub fn walk_enum_value_mut<V: VisitorMut + ?Sized>(visitor: &mut V, value: &mut EnumValue) {
+    for option in &mut value.options {
+        visitor.visit_enum_value_option(option);
+    }
+}
+
+/// Visits the methods and options of a service.
+pub fn walk_service_mut<V: VisitorMut + ?Sized>(visitor: &mut V, service: &mut Service) {
+    for method in &mut service.methods {
+        visitor.visit_method(method);
+    }
+    for option in &mut service.options {
+        visitor.visit_option(option);
+    }
+}
+
+/// Visits the options of a method.
+pub fn walk_method_mut<V: VisitorMut + ?Sized>(visitor: &mut V, method: &mut Method) {
+    for option in &mut method.options {
+        visitor.visit_option(option);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::to_lower_camel_case;
+    use crate::parser::ast::{OptionValue, Syntax};
+    use crate::parser::parse_proto_file;
+
+    const INPUT: &str = r#"
+        syntax = "proto3";
+
+        message User {
+            string user_name = 1;
+            string legacy_id = 2 [deprecated = true];
+            oneof contact {
+                string email_address = 3;
+                string phone_number = 4 [deprecated = true];
+            }
+            message Address {
+                string street_name = 1;
+            }
+        }
+    "#;
+
+    fn is_deprecated(field: &Field) -> bool {
+        field.options.iter().any(|option| {
+            option.name.to_string() == "deprecated" && option.value == OptionValue::Bool(true)
+        })
+    }
+
+    /// Renames fields to lowerCamelCase
+    struct CamelCase;
+
+    impl VisitorMut for CamelCase {
+        fn visit_field(&mut self, field: &mut Field) {
+            field.name = to_lower_camel_case(&field.name);
+        }
+    }
+
+    /// Removes deprecated fields, oneof members included
+    struct StripDeprecated;
+
+    impl VisitorMut for StripDeprecated {
+        fn visit_message(&mut self, message: &mut Message) {
+            message.fields.retain(|field| !is_deprecated(field));
+            walk_message_mut(self, message);
+        }
+
+        fn visit_oneof(&mut self, oneof: &mut OneOf) {
+            oneof.fields.retain(|field| !is_deprecated(field));
+        }
+    }
+
+    #[test]
+    fn test_rename_fields() {
+        let mut proto_file = parse_proto_file(INPUT).unwrap();
+        CamelCase.visit_proto_file(&mut proto_file);
+
+        let user = &proto_file.messages[0];
+        assert_eq!(user.fields[0].name, "userName");
+        assert_eq!(user.oneofs[0].fields[0].name, "emailAddress");
+        assert_eq!(user.nested_messages[0].fields[0].name, "streetName");
+        assert_eq!(proto_file.syntax, Syntax::Proto3);
+    }
+
+    #[test]
+    fn test_strip_deprecated() {
+        let mut proto_file = parse_proto_file(INPUT).unwrap();
+        StripDeprecated.visit_proto_file(&mut proto_file);
+
+        let user = &proto_file.messages[0];
+        let names: Vec<&str> = user.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["user_name"]);
+        assert_eq!(user.oneofs[0].fields.len(), 1);
+        assert_eq!(user.oneofs[0].fields[0].name, "email_address");
+        assert_eq!(user.nested_messages[0].fields.len(), 1);
+    }
+}